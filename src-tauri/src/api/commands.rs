@@ -0,0 +1,24 @@
+use crate::api::http_client::HttpClientConfig;
+use crate::error::NexaError;
+use crate::AppData;
+use tauri::State;
+
+#[tauri::command]
+pub async fn get_http_client_config(
+    state: State<'_, AppData>,
+) -> Result<HttpClientConfig, NexaError> {
+    Ok(state.http_client.lock().await.get_config())
+}
+
+// Applied to the next request of every provider, nothing has to be restarted
+#[tauri::command]
+pub async fn set_http_client_config(
+    state: State<'_, AppData>,
+    config: HttpClientConfig,
+) -> Result<(), NexaError> {
+    state
+        .http_client
+        .lock()
+        .await
+        .set_config(config, &state.secret_store)
+}
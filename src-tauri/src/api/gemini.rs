@@ -1,11 +1,11 @@
+use crate::api::transport::{split_lines, HttpRequest, HttpTransport};
 use crate::error::NexaError;
-use futures::stream::{self, StreamExt};
+use crate::llm::base::GenerationConfig;
+use futures::stream::StreamExt;
 use futures_util::Stream;
 use serde::{Deserialize, Serialize};
 use serde_json::{self, json, Value};
 use std::collections::HashMap;
-use std::str::from_utf8;
-use tauri_plugin_http::reqwest;
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -144,22 +144,154 @@ pub enum Scheduling {
     Interrupt,
 }
 
+// A prompt blocked by the safety filters comes back with `promptFeedback` and
+// no candidates at all
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
 pub struct GeminiGenerateContentResponse {
+    #[serde(default)]
     pub candidates: Vec<Candidate>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_feedback: Option<PromptFeedback>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage_metadata: Option<UsageMetadata>,
+
     #[serde(flatten)]
     pub extra_fields: Value,
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_token_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub candidates_token_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cached_content_token_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thoughts_token_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_token_count: Option<u64>,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct Candidate {
+    // Missing when the candidate was blocked before producing anything
+    #[serde(default)]
     pub content: Content,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<FinishReason>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub safety_ratings: Option<Vec<SafetyRating>>,
+
     #[serde(flatten)]
     pub extra_fields: Value,
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptFeedback {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_reason: Option<BlockReason>,
+
+    #[serde(default)]
+    pub safety_ratings: Vec<SafetyRating>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SafetyRating {
+    pub category: HarmCategory,
+    pub probability: HarmProbability,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocked: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SafetySetting {
+    pub category: HarmCategory,
+    pub threshold: HarmBlockThreshold,
+}
+
+// The enums below get new values from time to time, anything unknown is
+// folded into `Unspecified` instead of failing the whole response
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum HarmCategory {
+    #[serde(rename = "HARM_CATEGORY_HARASSMENT")]
+    Harassment,
+    #[serde(rename = "HARM_CATEGORY_HATE_SPEECH")]
+    HateSpeech,
+    #[serde(rename = "HARM_CATEGORY_SEXUALLY_EXPLICIT")]
+    SexuallyExplicit,
+    #[serde(rename = "HARM_CATEGORY_DANGEROUS_CONTENT")]
+    DangerousContent,
+    #[serde(rename = "HARM_CATEGORY_CIVIC_INTEGRITY")]
+    CivicIntegrity,
+    #[serde(rename = "HARM_CATEGORY_UNSPECIFIED", other)]
+    Unspecified,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum HarmBlockThreshold {
+    #[serde(rename = "HARM_BLOCK_THRESHOLD_UNSPECIFIED")]
+    Unspecified,
+    BlockLowAndAbove,
+    BlockMediumAndAbove,
+    BlockOnlyHigh,
+    BlockNone,
+    Off,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum HarmProbability {
+    Negligible,
+    Low,
+    Medium,
+    High,
+    #[serde(rename = "HARM_PROBABILITY_UNSPECIFIED", other)]
+    Unspecified,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum BlockReason {
+    Safety,
+    Blocklist,
+    ProhibitedContent,
+    ImageSafety,
+    Other,
+    #[serde(rename = "BLOCK_REASON_UNSPECIFIED", other)]
+    Unspecified,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FinishReason {
+    Stop,
+    MaxTokens,
+    Safety,
+    Recitation,
+    Language,
+    Blocklist,
+    ProhibitedContent,
+    Spii,
+    MalformedFunctionCall,
+    ImageSafety,
+    UnexpectedToolCall,
+    Other,
+    #[serde(rename = "FINISH_REASON_UNSPECIFIED", other)]
+    Unspecified,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct GeminiGenerateContentRequest {
@@ -168,13 +300,20 @@ pub struct GeminiGenerateContentRequest {
     tools: Option<Vec<Tool>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     tool_config: Option<ToolConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    safety_settings: Option<Vec<SafetySetting>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system_instruction: Option<Content>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    generation_config: Option<GenerationConfig>,
 
     #[serde(flatten)]
     extra_fields: Value,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
 pub struct Content {
+    #[serde(default)]
     pub parts: Vec<GeminiPart>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub role: Option<String>,
@@ -240,6 +379,18 @@ pub struct ToolConfig {
     extra_fields: Value,
 }
 
+impl ToolConfig {
+    pub fn new(mode: FunctionCallingMode, allowed_function_names: Option<Vec<String>>) -> Self {
+        ToolConfig {
+            function_calling_config: Some(FunctionCallingConfig {
+                mode,
+                allowed_function_names,
+            }),
+            extra_fields: json!({}),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct FunctionCallingConfig {
@@ -262,15 +413,35 @@ pub enum FunctionCallingMode {
     Validated,
 }
 
+// Cheapest call that needs a valid key, for checking one before it's used
+pub async fn check_gemini_api_key(
+    transport: &dyn HttpTransport,
+    api_key: &str,
+) -> Result<(), NexaError> {
+    transport
+        .send(
+            HttpRequest::get("https://generativelanguage.googleapis.com/v1beta/models?pageSize=1")
+                .header("x-goog-api-key", api_key),
+        )
+        .await?
+        .error_for_status(NexaError::Gemini)
+        .await?;
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn gemini_chat(
+    transport: &dyn HttpTransport,
     chat_history: Vec<Content>,
     tools: Vec<Tool>,
     model_id: String,
     api_key: String,
     tool_config: Option<ToolConfig>,
+    safety_settings: Option<Vec<SafetySetting>>,
+    system_prompt: Option<String>,
+    generation_config: Option<GenerationConfig>,
 ) -> Result<impl Stream<Item = Result<GeminiGenerateContentResponse, NexaError>>, NexaError> {
-    let client = reqwest::Client::new();
-
     let gemini_request = GeminiGenerateContentRequest {
         contents: chat_history,
         tools: match tools.len() {
@@ -278,56 +449,53 @@ pub async fn gemini_chat(
             _ => Some(tools),
         },
         tool_config: tool_config,
+        safety_settings,
+        // The system instruction has no role, it isn't a turn of the chat
+        system_instruction: system_prompt.map(|text| Content {
+            parts: vec![GeminiPart {
+                thought: None,
+                thought_signature: None,
+                data: GeminiPartData::Text(text),
+                metadata: None,
+                part_metadata: None,
+            }],
+            role: None,
+        }),
+        generation_config: generation_config
+            .filter(|generation_config| *generation_config != GenerationConfig::default()),
         extra_fields: json!({}),
     };
 
     dbg!(serde_json::to_value(&gemini_request));
 
-    let response = client
-        .post(format!(
-            "https://generativelanguage.googleapis.com/v1beta/models/{}:streamGenerateContent?alt=sse",
-            model_id
-        ))
-        .header("x-goog-api-key", api_key)
-        .header("Content-Type", "application/json")
-        .json(&gemini_request)
-        .send()
+    let request = HttpRequest::post(format!(
+        "https://generativelanguage.googleapis.com/v1beta/models/{}:streamGenerateContent?alt=sse",
+        model_id
+    ))
+    .header("x-goog-api-key", api_key)
+    .json(&gemini_request)?;
+
+    let response = transport
+        .send(request)
+        .await?
+        .error_for_status(NexaError::Gemini)
         .await?;
 
-    let result = response.error_for_status();
-
-    if let Err(e) = result {
-        dbg!("Reqwest error!!!!!");
-        dbg!(&e);
-        return Err(NexaError::Reqwest(e));
-    }
-
-    let result = result.unwrap();
-    let stream = result.bytes_stream();
-
-    let stream = stream::unfold(stream, |mut stream| async move {
-        if let Some(item) = stream.next().await {
-            return match item {
-                Ok(bytes) => {
-                    let msg = from_utf8(&bytes).unwrap();
-                    let trimed_msg = msg.trim().trim_start_matches("data: ");
-                    if trimed_msg.is_empty() {
-                        return None;
-                    }
-
-                    let res = serde_json::from_str::<GeminiGenerateContentResponse>(trimed_msg);
-                    match res {
-                        Ok(response) => Some((Ok(response), stream)),
-                        Err(e) => {
-                            dbg!(trimed_msg);
-                            Some((Err(NexaError::SerdeJson(e)), stream))
-                        }
-                    }
-                }
-                Err(e) => Some((Err(NexaError::Reqwest(e)), stream)),
-            };
+    // Only the "data:" lines of the SSE stream carry a response
+    let stream = split_lines(response.body).filter_map(|line| async move {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => return Some(Err(e)),
+        };
+        let data = line.strip_prefix("data:")?.trim();
+
+        match serde_json::from_str::<GeminiGenerateContentResponse>(data) {
+            Ok(response) => Some(Ok(response)),
+            Err(e) => Some(Err(NexaError::Gemini(format!(
+                "Can't parse the response {}: {}",
+                data, e
+            )))),
         }
-        None
     });
 
     Ok(stream)
@@ -336,17 +504,17 @@ pub async fn gemini_chat(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::api::transport::tests::get_fixture_path;
+    use crate::api::transport::{RecordingTransport, ReplayTransport, ReqwestTransport};
     use futures::pin_mut;
     // Import your structs from the parent module
     use serde_json::json; // Use the json! macro for easy Value creation
     use std::env;
+    use std::sync::Arc;
+    use tauri_plugin_http::reqwest;
 
-    // Just a handy test for the Gemini API
-    // #[tokio::test]
-    async fn test_gemini_toolcall_response() {
-        dotenv::dotenv().ok();
-
-        let chat_history = vec![Content {
+    fn get_toolcall_chat_history() -> Vec<Content> {
+        vec![Content {
             parts: vec![GeminiPart {
                 thought: None,
                 thought_signature: None,
@@ -355,8 +523,10 @@ mod tests {
                 part_metadata: None,
             }],
             role: Some("user".to_string()),
-        }];
+        }]
+    }
 
+    fn get_toolcall_tools() -> Vec<Tool> {
         let schedule_meeting_properties = json!({
             "type": "object",
             "required": ["attendees", "date", "time", "topic"],
@@ -383,7 +553,7 @@ mod tests {
             }
         });
 
-        let tools = vec![Tool {
+        vec![Tool {
             function_declarations: Some(vec![
                 FunctionDeclaration {
                     name: "schedule_meeting".to_string(),
@@ -410,14 +580,30 @@ mod tests {
                 },
             ]),
             extra_fields: json!({}),
-        }];
+        }]
+    }
+
+    // Just a handy test for the Gemini API, it also refreshes the fixture
+    // replayed by test_gemini_toolcall_replay
+    // #[tokio::test]
+    async fn test_gemini_toolcall_response() {
+        dotenv::dotenv().ok();
+
+        let transport = RecordingTransport::new(
+            Arc::new(ReqwestTransport::new(reqwest::Client::new())),
+            get_fixture_path("gemini_toolcall.json"),
+        );
 
         let stream = gemini_chat(
-            chat_history,
-            tools,
+            &transport,
+            get_toolcall_chat_history(),
+            get_toolcall_tools(),
             "gemini-2.5-pro".to_string(),
             env::var("GEMINI_API_KEY").expect("GEMINI_API_KEY must be set for this test."),
             None,
+            None,
+            None,
+            None,
         )
         .await
         .unwrap();
@@ -437,6 +623,50 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_gemini_toolcall_replay() {
+        let transport = ReplayTransport::load(&get_fixture_path("gemini_toolcall.json")).unwrap();
+
+        let stream = gemini_chat(
+            &transport,
+            get_toolcall_chat_history(),
+            get_toolcall_tools(),
+            "gemini-2.5-pro".to_string(),
+            String::from("fake-api-key"),
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let responses: Vec<GeminiGenerateContentResponse> =
+            stream.map(|response| response.unwrap()).collect().await;
+
+        assert_eq!(responses.len(), 2);
+
+        let first_part = &responses[0].candidates[0].content.parts[0];
+        assert!(first_part.thought_signature.is_some());
+        assert_eq!(
+            first_part.data,
+            GeminiPartData::FunctionCall {
+                id: None,
+                name: "schedule_meeting".to_string(),
+                args: Some(json!({
+                    "attendees": ["Bob", "Alice"],
+                    "date": "2025-03-27",
+                    "time": "10:00",
+                    "topic": "Q3 planning"
+                })),
+            }
+        );
+
+        let usage_metadata = responses[1].usage_metadata.clone().unwrap();
+        assert_eq!(usage_metadata.prompt_token_count, Some(173));
+        assert_eq!(usage_metadata.candidates_token_count, Some(37));
+    }
+
     #[test]
     fn test_gemini_part_serde() {
         test_function_call_part_serde();
@@ -0,0 +1,280 @@
+use crate::api::transport::{HttpRequest, HttpTransport};
+use crate::error::NexaError;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri_plugin_http::reqwest::Url;
+use tokio::time::{sleep, Duration};
+
+static GEMINI_API_BASE_URL: &str = "https://generativelanguage.googleapis.com";
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum GeminiFileState {
+    #[serde(rename = "STATE_UNSPECIFIED")]
+    Unspecified,
+    #[serde(rename = "PROCESSING")]
+    Processing,
+    #[serde(rename = "ACTIVE")]
+    Active,
+    #[serde(rename = "FAILED")]
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiFile {
+    // Resource name, e.g. "files/abc-123"
+    pub name: String,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    pub mime_type: String,
+    // int64 is sent as a string
+    #[serde(default)]
+    pub size_bytes: Option<String>,
+    #[serde(default)]
+    pub create_time: Option<String>,
+    #[serde(default)]
+    pub expiration_time: Option<String>,
+    #[serde(default)]
+    pub sha256_hash: Option<String>,
+    pub uri: String,
+    #[serde(default)]
+    pub state: Option<GeminiFileState>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiListFilesResponse {
+    #[serde(default)]
+    pub files: Vec<GeminiFile>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_page_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GeminiUploadFileResponse {
+    file: GeminiFile,
+}
+
+// Resumable upload: the first request only announces the file and gets back an
+// upload URL, the second one sends the bytes and finalizes the upload
+pub async fn upload_gemini_file(
+    transport: &dyn HttpTransport,
+    api_key: &str,
+    bytes: Vec<u8>,
+    mime_type: &str,
+    display_name: &str,
+) -> Result<GeminiFile, NexaError> {
+    let start_request = HttpRequest::post(format!("{GEMINI_API_BASE_URL}/upload/v1beta/files"))
+        .header("x-goog-api-key", api_key)
+        .header("X-Goog-Upload-Protocol", "resumable")
+        .header("X-Goog-Upload-Command", "start")
+        .header(
+            "X-Goog-Upload-Header-Content-Length",
+            bytes.len().to_string(),
+        )
+        .header("X-Goog-Upload-Header-Content-Type", mime_type)
+        .json(&json!({
+            "file": {
+                "displayName": display_name
+            }
+        }))?;
+
+    let start_response = transport
+        .send(start_request)
+        .await?
+        .error_for_status(NexaError::Gemini)
+        .await?;
+    let upload_url = start_response
+        .header("x-goog-upload-url")
+        .ok_or(NexaError::Gemini(String::from(
+            "Upload URL is missing from the upload start response",
+        )))?
+        .to_string();
+
+    let upload_request = HttpRequest::post(upload_url)
+        .header("Content-Length", bytes.len().to_string())
+        .header("X-Goog-Upload-Offset", "0")
+        .header("X-Goog-Upload-Command", "upload, finalize")
+        .body(bytes);
+
+    let upload_response: GeminiUploadFileResponse = transport
+        .send(upload_request)
+        .await?
+        .error_for_status(NexaError::Gemini)
+        .await?
+        .json()
+        .await?;
+
+    Ok(upload_response.file)
+}
+
+pub async fn get_gemini_file(
+    transport: &dyn HttpTransport,
+    api_key: &str,
+    name: &str,
+) -> Result<GeminiFile, NexaError> {
+    transport
+        .send(
+            HttpRequest::get(format!("{GEMINI_API_BASE_URL}/v1beta/{name}"))
+                .header("x-goog-api-key", api_key),
+        )
+        .await?
+        .error_for_status(NexaError::Gemini)
+        .await?
+        .json()
+        .await
+}
+
+pub async fn list_gemini_files(
+    transport: &dyn HttpTransport,
+    api_key: &str,
+    page_token: Option<String>,
+) -> Result<GeminiListFilesResponse, NexaError> {
+    let mut params = vec![("pageSize", String::from("100"))];
+    if let Some(page_token) = page_token {
+        params.push(("pageToken", page_token));
+    }
+    let url = Url::parse_with_params(&format!("{GEMINI_API_BASE_URL}/v1beta/files"), &params)
+        .map_err(|e| NexaError::Gemini(e.to_string()))?;
+
+    transport
+        .send(HttpRequest::get(url).header("x-goog-api-key", api_key))
+        .await?
+        .error_for_status(NexaError::Gemini)
+        .await?
+        .json()
+        .await
+}
+
+pub async fn delete_gemini_file(
+    transport: &dyn HttpTransport,
+    api_key: &str,
+    name: &str,
+) -> Result<(), NexaError> {
+    transport
+        .send(
+            HttpRequest::delete(format!("{GEMINI_API_BASE_URL}/v1beta/{name}"))
+                .header("x-goog-api-key", api_key),
+        )
+        .await?
+        .error_for_status(NexaError::Gemini)
+        .await?;
+
+    Ok(())
+}
+
+// Videos (and sometimes audio) stay in PROCESSING for a while after the upload
+// and Gemini rejects requests that reference them until they are ACTIVE
+pub async fn wait_for_gemini_file(
+    transport: &dyn HttpTransport,
+    api_key: &str,
+    mut file: GeminiFile,
+) -> Result<GeminiFile, NexaError> {
+    for _ in 0..60 {
+        match file.state {
+            Some(GeminiFileState::Processing) => {
+                sleep(Duration::from_secs(2)).await;
+                file = get_gemini_file(transport, api_key, &file.name).await?;
+            }
+            Some(GeminiFileState::Failed) => {
+                return Err(NexaError::Gemini(format!(
+                    "Processing of {} failed",
+                    file.name
+                )))
+            }
+            _ => return Ok(file),
+        }
+    }
+
+    Err(NexaError::Gemini(format!(
+        "{} is still processing, try again later",
+        file.name
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::transport::tests::get_fixture_path;
+    use crate::api::transport::{
+        Cassette, HttpMethod, Interaction, RecordedRequest, RecordedResponse, ReplayTransport,
+    };
+
+    #[test]
+    fn gemini_file_parsing_test() {
+        let raw_json_string = r#"{
+            "name": "files/abc-123",
+            "displayName": "talk.mp4",
+            "mimeType": "video/mp4",
+            "sizeBytes": "1048576",
+            "createTime": "2025-06-02T18:00:00.000000Z",
+            "updateTime": "2025-06-02T18:00:00.000000Z",
+            "expirationTime": "2025-06-04T18:00:00.000000Z",
+            "sha256Hash": "ZjQ5ZDc3",
+            "uri": "https://generativelanguage.googleapis.com/v1beta/files/abc-123",
+            "state": "PROCESSING",
+            "source": "UPLOADED"
+        }"#;
+
+        let file: GeminiFile = serde_json::from_str(raw_json_string).unwrap();
+        assert_eq!(file.name, "files/abc-123");
+        assert_eq!(file.size_bytes, Some(String::from("1048576")));
+        assert_eq!(file.state, Some(GeminiFileState::Processing));
+
+        let list: GeminiListFilesResponse = serde_json::from_str("{}").unwrap();
+        assert!(list.files.is_empty());
+        assert!(list.next_page_token.is_none());
+    }
+
+    #[tokio::test]
+    async fn upload_gemini_file_replay_test() {
+        let transport =
+            ReplayTransport::load(&get_fixture_path("gemini_file_upload.json")).unwrap();
+
+        let file = upload_gemini_file(
+            &transport,
+            "test-key",
+            b"%PDF-1.4 test".to_vec(),
+            "application/pdf",
+            "report.pdf",
+        )
+        .await
+        .unwrap();
+        assert_eq!(file.state, Some(GeminiFileState::Processing));
+
+        let file = wait_for_gemini_file(&transport, "test-key", file)
+            .await
+            .unwrap();
+        assert_eq!(file.state, Some(GeminiFileState::Active));
+        assert_eq!(
+            file.uri,
+            "https://generativelanguage.googleapis.com/v1beta/files/report-123"
+        );
+    }
+
+    #[tokio::test]
+    async fn list_gemini_files_test() {
+        let transport = ReplayTransport::new(Cassette {
+            interactions: vec![Interaction {
+                request: RecordedRequest {
+                    method: HttpMethod::Get,
+                    url: String::from(
+                        "https://generativelanguage.googleapis.com/v1beta/files?pageSize=100&pageToken=a%2Bb%26c%3D",
+                    ),
+                    body: None,
+                },
+                response: RecordedResponse {
+                    status: 200,
+                    headers: vec![],
+                    chunks: vec![String::from("{}")],
+                },
+            }],
+        });
+
+        // The token is sent as one escaped query value
+        let list = list_gemini_files(&transport, "test-key", Some(String::from("a+b&c=")))
+            .await
+            .unwrap();
+        assert!(list.files.is_empty());
+    }
+}
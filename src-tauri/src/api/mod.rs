@@ -1 +1,5 @@
+pub mod commands;
 pub mod gemini;
+pub mod gemini_files;
+pub mod http_client;
+pub mod transport;
@@ -0,0 +1,521 @@
+use crate::error::NexaError;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::{self, BoxStream, StreamExt};
+use futures_util::Stream;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use tauri_plugin_http::reqwest;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum HttpMethod {
+    Get,
+    Post,
+    Delete,
+}
+
+#[derive(Clone, Debug)]
+pub struct HttpRequest {
+    pub method: HttpMethod,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<Vec<u8>>,
+}
+
+impl HttpRequest {
+    pub fn new(method: HttpMethod, url: impl Into<String>) -> Self {
+        HttpRequest {
+            method,
+            url: url.into(),
+            headers: vec![],
+            body: None,
+        }
+    }
+
+    pub fn get(url: impl Into<String>) -> Self {
+        Self::new(HttpMethod::Get, url)
+    }
+
+    pub fn post(url: impl Into<String>) -> Self {
+        Self::new(HttpMethod::Post, url)
+    }
+
+    pub fn delete(url: impl Into<String>) -> Self {
+        Self::new(HttpMethod::Delete, url)
+    }
+
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn body(mut self, body: Vec<u8>) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    pub fn json<T: Serialize>(self, body: &T) -> Result<Self, NexaError> {
+        Ok(self
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_vec(body)?))
+    }
+}
+
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: BoxStream<'static, Result<Bytes, NexaError>>,
+}
+
+impl HttpResponse {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    pub async fn bytes(mut self) -> Result<Vec<u8>, NexaError> {
+        let mut bytes = vec![];
+        while let Some(chunk) = self.body.next().await {
+            bytes.extend_from_slice(&chunk?);
+        }
+        Ok(bytes)
+    }
+
+    pub async fn text(self) -> Result<String, NexaError> {
+        Ok(String::from_utf8_lossy(&self.bytes().await?).to_string())
+    }
+
+    pub async fn json<T: DeserializeOwned>(self) -> Result<T, NexaError> {
+        Ok(serde_json::from_slice(&self.bytes().await?)?)
+    }
+
+    // Turns a non 2xx response into an error carrying the response body, which
+    // is where both Gemini and Ollama put the actual reason
+    pub async fn error_for_status(
+        self,
+        to_error: impl FnOnce(String) -> NexaError,
+    ) -> Result<Self, NexaError> {
+        if self.is_success() {
+            return Ok(self);
+        }
+
+        let status = self.status;
+        let body = self.text().await.unwrap_or_default();
+        Err(to_error(format!("HTTP {}: {}", status, body)))
+    }
+}
+
+#[async_trait]
+pub trait HttpTransport: Send + Sync {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, NexaError>;
+}
+
+// The client can be swapped when the HTTP settings change. Requests take a
+// clone (reqwest clients share their pool), so one in flight isn't affected.
+pub struct ReqwestTransport {
+    client: RwLock<reqwest::Client>,
+}
+
+impl ReqwestTransport {
+    pub fn new(client: reqwest::Client) -> Self {
+        ReqwestTransport {
+            client: RwLock::new(client),
+        }
+    }
+
+    pub fn set_client(&self, client: reqwest::Client) {
+        *self.client.write().unwrap() = client;
+    }
+}
+
+#[async_trait]
+impl HttpTransport for ReqwestTransport {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, NexaError> {
+        let client = self.client.read().unwrap().clone();
+        let mut builder = match request.method {
+            HttpMethod::Get => client.get(&request.url),
+            HttpMethod::Post => client.post(&request.url),
+            HttpMethod::Delete => client.delete(&request.url),
+        };
+
+        for (name, value) in request.headers.iter() {
+            builder = builder.header(name, value);
+        }
+        if let Some(body) = request.body {
+            builder = builder.body(body);
+        }
+
+        let response = builder.send().await?;
+        let headers = response
+            .headers()
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.to_string(),
+                    String::from_utf8_lossy(value.as_bytes()).to_string(),
+                )
+            })
+            .collect();
+
+        Ok(HttpResponse {
+            status: response.status().as_u16(),
+            headers,
+            body: response
+                .bytes_stream()
+                .map(|chunk| chunk.map_err(NexaError::Reqwest))
+                .boxed(),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RecordedRequest {
+    pub method: HttpMethod,
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RecordedResponse {
+    pub status: u16,
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    pub chunks: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+    pub fn load(path: &PathBuf) -> Result<Self, NexaError> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    fn save(&self, path: &PathBuf) -> Result<(), NexaError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Ok(fs::write(path, serde_json::to_vec_pretty(self)?)?)
+    }
+}
+
+// Request headers are never written to the cassette since they carry the API keys
+fn get_recorded_request(request: &HttpRequest) -> RecordedRequest {
+    RecordedRequest {
+        method: request.method.clone(),
+        url: request.url.clone(),
+        body: request.body.as_ref().map(|body| {
+            serde_json::from_slice(body)
+                .unwrap_or_else(|_| Value::String(format!("<{} bytes>", body.len())))
+        }),
+    }
+}
+
+// Passes everything through to the inner transport and appends each finished
+// request/response pair to a cassette file
+pub struct RecordingTransport {
+    inner: Arc<dyn HttpTransport>,
+    path: PathBuf,
+    cassette: Arc<Mutex<Cassette>>,
+}
+
+impl RecordingTransport {
+    pub fn new(inner: Arc<dyn HttpTransport>, path: PathBuf) -> Self {
+        RecordingTransport {
+            inner,
+            path,
+            cassette: Arc::new(Mutex::new(Cassette::default())),
+        }
+    }
+}
+
+#[async_trait]
+impl HttpTransport for RecordingTransport {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, NexaError> {
+        let recorded_request = get_recorded_request(&request);
+        let response = self.inner.send(request).await?;
+
+        let recorded_response = RecordedResponse {
+            status: response.status,
+            headers: response.headers.clone(),
+            chunks: vec![],
+        };
+        let state = (
+            response.body.fuse(),
+            Some(Interaction {
+                request: recorded_request,
+                response: recorded_response,
+            }),
+            self.cassette.clone(),
+            self.path.clone(),
+        );
+
+        let body = stream::unfold(
+            state,
+            |(mut body, mut interaction, cassette, path)| async move {
+                match body.next().await {
+                    Some(chunk) => {
+                        if let (Ok(bytes), Some(interaction)) = (&chunk, interaction.as_mut()) {
+                            interaction
+                                .response
+                                .chunks
+                                .push(String::from_utf8_lossy(bytes).to_string());
+                        }
+                        Some((chunk, (body, interaction, cassette, path)))
+                    }
+                    None => {
+                        let interaction = interaction.take()?;
+                        let saved = {
+                            let mut cassette = cassette.lock().unwrap();
+                            cassette.interactions.push(interaction);
+                            cassette.save(&path)
+                        };
+                        // The body is fused, the stream ends after the error
+                        match saved {
+                            Ok(()) => None,
+                            Err(e) => Some((Err(e), (body, None, cassette, path))),
+                        }
+                    }
+                }
+            },
+        );
+
+        Ok(HttpResponse {
+            status: response.status,
+            headers: response.headers,
+            body: body.boxed(),
+        })
+    }
+}
+
+// Serves the interactions of a cassette in order, checking that every request
+// hits the same method and URL as when it was recorded
+pub struct ReplayTransport {
+    interactions: Mutex<Vec<Interaction>>,
+}
+
+impl ReplayTransport {
+    pub fn new(cassette: Cassette) -> Self {
+        let mut interactions = cassette.interactions;
+        interactions.reverse();
+
+        ReplayTransport {
+            interactions: Mutex::new(interactions),
+        }
+    }
+
+    pub fn load(path: &PathBuf) -> Result<Self, NexaError> {
+        Ok(Self::new(Cassette::load(path)?))
+    }
+}
+
+#[async_trait]
+impl HttpTransport for ReplayTransport {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, NexaError> {
+        let interaction = self
+            .interactions
+            .lock()
+            .unwrap()
+            .pop()
+            .ok_or(NexaError::Transport(format!(
+                "No recorded response left for {:?} {}",
+                request.method, request.url
+            )))?;
+
+        if interaction.request.method != request.method || interaction.request.url != request.url {
+            return Err(NexaError::Transport(format!(
+                "Expected {:?} {} but got {:?} {}",
+                interaction.request.method, interaction.request.url, request.method, request.url
+            )));
+        }
+
+        let chunks: Vec<Result<Bytes, NexaError>> = interaction
+            .response
+            .chunks
+            .into_iter()
+            .map(|chunk| Ok(Bytes::from(chunk)))
+            .collect();
+
+        Ok(HttpResponse {
+            status: interaction.response.status,
+            headers: interaction.response.headers,
+            body: stream::iter(chunks).boxed(),
+        })
+    }
+}
+
+// Network chunks don't line up with NDJSON objects or SSE events, so the
+// bytes are re-split on newlines before anything gets parsed
+pub(crate) fn split_lines<S>(byte_stream: S) -> impl Stream<Item = Result<String, NexaError>>
+where
+    S: Stream<Item = Result<Bytes, NexaError>> + Unpin,
+{
+    stream::unfold(
+        (byte_stream, Vec::<u8>::new(), false),
+        |(mut byte_stream, mut buffer, mut finished)| async move {
+            loop {
+                if let Some(pos) = buffer.iter().position(|b| *b == b'\n') {
+                    let line: Vec<u8> = buffer.drain(..=pos).collect();
+                    let line = String::from_utf8_lossy(&line).trim().to_string();
+                    if line.is_empty() {
+                        continue;
+                    }
+                    return Some((Ok(line), (byte_stream, buffer, finished)));
+                }
+
+                if finished {
+                    let rest = String::from_utf8_lossy(&buffer).trim().to_string();
+                    buffer.clear();
+                    if rest.is_empty() {
+                        return None;
+                    }
+                    return Some((Ok(rest), (byte_stream, buffer, finished)));
+                }
+
+                match byte_stream.next().await {
+                    Some(Ok(bytes)) => buffer.extend_from_slice(&bytes),
+                    Some(Err(e)) => {
+                        buffer.clear();
+                        return Some((Err(e), (byte_stream, buffer, true)));
+                    }
+                    None => finished = true,
+                }
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use serde_json::json;
+
+    pub(crate) fn get_fixture_path(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join("http")
+            .join(name)
+    }
+
+    #[tokio::test]
+    async fn record_and_replay_test() {
+        let cassette = Cassette {
+            interactions: vec![Interaction {
+                request: RecordedRequest {
+                    method: HttpMethod::Post,
+                    url: String::from("http://localhost:11434/api/chat"),
+                    body: None,
+                },
+                response: RecordedResponse {
+                    status: 200,
+                    headers: vec![(
+                        String::from("content-type"),
+                        String::from("application/x-ndjson"),
+                    )],
+                    chunks: vec![String::from("{\"a\":"), String::from("1}\n")],
+                },
+            }],
+        };
+
+        // Record the replayed response through a recorder and compare the cassettes
+        let path = std::env::temp_dir().join(format!(
+            "nexa-record-and-replay-{}.json",
+            std::process::id()
+        ));
+        let recorder = RecordingTransport::new(
+            Arc::new(ReplayTransport::new(cassette.clone())),
+            path.clone(),
+        );
+
+        let request = HttpRequest::post("http://localhost:11434/api/chat")
+            .header("Authorization", "Bearer secret")
+            .json(&json!({"model": "llama3.2"}))
+            .unwrap();
+        let response = recorder.send(request).await.unwrap();
+        assert_eq!(
+            response.header("Content-Type"),
+            Some("application/x-ndjson")
+        );
+        assert_eq!(response.text().await.unwrap(), "{\"a\":1}\n");
+
+        let recorded = Cassette::load(&path).unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(recorded.interactions.len(), 1);
+        assert_eq!(
+            recorded.interactions[0].request.body,
+            Some(json!({"model": "llama3.2"}))
+        );
+        assert_eq!(
+            recorded.interactions[0].response,
+            cassette.interactions[0].response
+        );
+        assert!(!serde_json::to_string(&recorded).unwrap().contains("secret"));
+
+        // A cassette that can't be written fails the response at its end
+        let recorder = RecordingTransport::new(
+            Arc::new(ReplayTransport::new(cassette.clone())),
+            std::env::temp_dir(),
+        );
+        let response = recorder
+            .send(HttpRequest::post("http://localhost:11434/api/chat"))
+            .await
+            .unwrap();
+        assert!(response.text().await.is_err());
+
+        // Replaying past the end of the cassette or to another URL fails
+        let replay = ReplayTransport::new(cassette);
+        assert!(replay
+            .send(HttpRequest::get("http://localhost:11434/api/tags"))
+            .await
+            .is_err());
+        assert!(replay
+            .send(HttpRequest::post("http://localhost:11434/api/chat"))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn split_lines_test() {
+        // One object split across chunks, two objects in one chunk, no trailing newline
+        let chunks: Vec<Result<Bytes, NexaError>> = vec![
+            Ok(Bytes::from(r#"{"message": "hel"#)),
+            Ok(Bytes::from("lo\"}\n{\"message\": \"a\"}\r\n\n{\"message\"")),
+            Ok(Bytes::from(r#": "b"}"#)),
+        ];
+
+        let lines: Vec<String> = split_lines(stream::iter(chunks))
+            .map(|line| line.unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(
+            lines,
+            vec![
+                r#"{"message": "hello"}"#.to_string(),
+                r#"{"message": "a"}"#.to_string(),
+                r#"{"message": "b"}"#.to_string(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,289 @@
+use crate::conversation::export::{get_export, ExportFormat};
+use crate::conversation::search::{SearchFilters, SearchResult};
+use crate::conversation::settings::ConversationSettings;
+use crate::conversation::store::{Conversation, ConversationSummary};
+use crate::conversation::tree::{BranchMessage, ConversationTree, TreeMessage};
+use crate::error::NexaError;
+use crate::llm::base::{ChatMessageContent, ChatMessageWithId};
+use crate::llm::cache::now_secs;
+use crate::llm::pricing::{self, ConversationCost};
+use crate::AppData;
+use serde_json::{Map, Value};
+use tauri::State;
+
+#[tauri::command]
+pub async fn list_conversations(
+    state: State<'_, AppData>,
+) -> Result<Vec<ConversationSummary>, NexaError> {
+    state.conversation_store.lock().await.list_conversations()
+}
+
+#[tauri::command]
+pub async fn create_conversation(
+    state: State<'_, AppData>,
+    title: Option<String>,
+    settings: Option<ConversationSettings>,
+) -> Result<ConversationSummary, NexaError> {
+    let store = state.conversation_store.lock().await;
+    let summary = store.create_conversation(title.as_deref().unwrap_or("New chat"))?;
+    if let Some(settings) = settings {
+        store.set_settings(&summary.id, &settings)?;
+    }
+
+    Ok(summary)
+}
+
+#[tauri::command]
+pub async fn rename_conversation(
+    state: State<'_, AppData>,
+    conversation_id: String,
+    title: String,
+) -> Result<(), NexaError> {
+    state
+        .conversation_store
+        .lock()
+        .await
+        .rename_conversation(&conversation_id, &title)
+}
+
+#[tauri::command]
+pub async fn delete_conversation(
+    state: State<'_, AppData>,
+    conversation_id: String,
+) -> Result<(), NexaError> {
+    state
+        .conversation_store
+        .lock()
+        .await
+        .delete_conversation(&conversation_id)
+}
+
+#[tauri::command]
+pub async fn load_conversation(
+    state: State<'_, AppData>,
+    conversation_id: String,
+) -> Result<Conversation, NexaError> {
+    state
+        .conversation_store
+        .lock()
+        .await
+        .load_conversation(&conversation_id)
+}
+
+// Returns the file contents, saving them is up to the frontend
+#[tauri::command]
+pub async fn export_conversation(
+    state: State<'_, AppData>,
+    conversation_id: String,
+    format: ExportFormat,
+) -> Result<String, NexaError> {
+    let conversation = state
+        .conversation_store
+        .lock()
+        .await
+        .load_conversation(&conversation_id)?;
+
+    get_export(&conversation, &format)
+}
+
+#[tauri::command]
+pub async fn import_conversations(
+    state: State<'_, AppData>,
+    contents: String,
+) -> Result<Vec<ConversationSummary>, NexaError> {
+    state
+        .conversation_store
+        .lock()
+        .await
+        .import_conversations(&contents)
+}
+
+// The chat older versions kept in the frontend store, as a conversation. Only
+// the first call imports it, later ones return nothing.
+#[tauri::command]
+pub async fn import_chat_history_store(
+    state: State<'_, AppData>,
+) -> Result<Option<ConversationSummary>, NexaError> {
+    state
+        .conversation_store
+        .lock()
+        .await
+        .import_chat_history_store(&state.chat_history_store_path)
+}
+
+#[tauri::command]
+pub async fn search_conversations(
+    state: State<'_, AppData>,
+    query: String,
+    filters: Option<SearchFilters>,
+    limit: Option<u32>,
+) -> Result<Vec<SearchResult>, NexaError> {
+    state.conversation_store.lock().await.search(
+        &query,
+        &filters.unwrap_or_default(),
+        limit.unwrap_or(50),
+    )
+}
+
+#[tauri::command]
+pub async fn get_conversation_metadata(
+    state: State<'_, AppData>,
+    conversation_id: String,
+) -> Result<Map<String, Value>, NexaError> {
+    state
+        .conversation_store
+        .lock()
+        .await
+        .get_metadata(&conversation_id)
+}
+
+#[tauri::command]
+pub async fn set_conversation_metadata(
+    state: State<'_, AppData>,
+    conversation_id: String,
+    metadata: Map<String, Value>,
+) -> Result<(), NexaError> {
+    state
+        .conversation_store
+        .lock()
+        .await
+        .set_metadata(&conversation_id, &metadata)
+}
+
+#[tauri::command]
+pub async fn get_conversation_settings(
+    state: State<'_, AppData>,
+    conversation_id: String,
+) -> Result<ConversationSettings, NexaError> {
+    state
+        .conversation_store
+        .lock()
+        .await
+        .get_settings(&conversation_id)
+}
+
+#[tauri::command]
+pub async fn set_conversation_settings(
+    state: State<'_, AppData>,
+    conversation_id: String,
+    settings: ConversationSettings,
+) -> Result<(), NexaError> {
+    state
+        .conversation_store
+        .lock()
+        .await
+        .set_settings(&conversation_id, &settings)
+}
+
+// What the conversation has cost so far, and the replies leading up to its
+// active leaf since the user last wrote
+#[tauri::command]
+pub async fn get_conversation_cost(
+    state: State<'_, AppData>,
+    conversation_id: String,
+) -> Result<ConversationCost, NexaError> {
+    let tree = state
+        .conversation_store
+        .lock()
+        .await
+        .load_tree(&conversation_id)?;
+
+    pricing::get_conversation_cost(&tree, tree.active_leaf_id.as_deref())
+}
+
+// Appends a message below `parent_id` (or as a new root when there is none) and
+// makes it the active leaf
+#[tauri::command]
+pub async fn add_chat_message(
+    state: State<'_, AppData>,
+    conversation_id: String,
+    parent_id: Option<String>,
+    message: ChatMessageWithId,
+) -> Result<(), NexaError> {
+    update_conversation_tree(&state, &conversation_id, |tree| {
+        tree.add_message(TreeMessage {
+            id: message.id,
+            parent_id,
+            role: message.role,
+            content: message.content,
+            images: message.images,
+            created_at: now_secs(),
+            provider: None,
+            model: None,
+            usage: None,
+            cost: None,
+        })
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn regenerate_message(
+    state: State<'_, AppData>,
+    conversation_id: String,
+    message_id: String,
+) -> Result<String, NexaError> {
+    update_conversation_tree(&state, &conversation_id, |tree| {
+        tree.regenerate(&message_id)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn edit_message(
+    state: State<'_, AppData>,
+    conversation_id: String,
+    message_id: String,
+    content: ChatMessageContent,
+) -> Result<String, NexaError> {
+    update_conversation_tree(&state, &conversation_id, |tree| {
+        tree.edit(&message_id, content)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn switch_branch(
+    state: State<'_, AppData>,
+    conversation_id: String,
+    message_id: String,
+) -> Result<Vec<BranchMessage>, NexaError> {
+    update_conversation_tree(&state, &conversation_id, |tree| {
+        let leaf_id = tree.switch_branch(&message_id)?;
+        tree.get_branch(&leaf_id)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_active_branch(
+    state: State<'_, AppData>,
+    conversation_id: String,
+) -> Result<Vec<BranchMessage>, NexaError> {
+    let tree = state
+        .conversation_store
+        .lock()
+        .await
+        .load_tree(&conversation_id)?;
+
+    match &tree.active_leaf_id {
+        Some(leaf_id) => tree.get_branch(leaf_id),
+        None => Ok(vec![]),
+    }
+}
+
+// Loads the tree, applies `update` and writes the result back while holding
+// the store lock, so two commands can't interleave on the same conversation
+pub(crate) async fn update_conversation_tree<T>(
+    state: &AppData,
+    conversation_id: &str,
+    update: impl FnOnce(&mut ConversationTree) -> Result<T, NexaError>,
+) -> Result<T, NexaError> {
+    let mut store = state.conversation_store.lock().await;
+    let mut tree = store.load_tree(conversation_id)?;
+
+    let result = update(&mut tree)?;
+    store.save_tree(conversation_id, &tree)?;
+
+    Ok(result)
+}
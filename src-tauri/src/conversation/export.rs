@@ -0,0 +1,371 @@
+use crate::conversation::store::Conversation;
+use crate::conversation::tree::TreeMessage;
+use crate::error::NexaError;
+use crate::llm::base::{ChatMessageContent, Role};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub(crate) static NEXA_EXPORT_FORMAT: &str = "nexa-conversation";
+pub(crate) static NEXA_EXPORT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Markdown,
+    Json,
+    Html,
+}
+
+// The JSON export is the whole conversation as stored, every branch included,
+// so importing it again gives back the same tree
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationExport {
+    pub format: String,
+    pub version: u32,
+    pub conversation: Conversation,
+}
+
+// Markdown and HTML are for reading, so they only contain the active branch
+pub fn get_export(conversation: &Conversation, format: &ExportFormat) -> Result<String, NexaError> {
+    let messages = match &conversation.tree.active_leaf_id {
+        Some(leaf_id) => conversation.tree.get_path(leaf_id)?,
+        None => vec![],
+    };
+
+    match format {
+        ExportFormat::Markdown => get_markdown(&conversation.summary.title, &messages),
+        ExportFormat::Html => get_html(&conversation.summary.title, &messages),
+        ExportFormat::Json => Ok(serde_json::to_string_pretty(&ConversationExport {
+            format: NEXA_EXPORT_FORMAT.to_string(),
+            version: NEXA_EXPORT_VERSION,
+            conversation: conversation.clone(),
+        })?),
+    }
+}
+
+fn get_markdown(title: &str, messages: &[&TreeMessage]) -> Result<String, NexaError> {
+    let mut markdown = format!("# {}\n", title);
+
+    for message in messages {
+        markdown.push_str(&format!("\n## {}\n\n", get_heading(message)));
+
+        let meta = match &message.content {
+            ChatMessageContent::Text { text, _meta } => {
+                markdown.push_str(text);
+                markdown.push('\n');
+                _meta
+            }
+            ChatMessageContent::FunctionCallRequest {
+                name, args, _meta, ..
+            } => {
+                markdown.push_str(&format!("**Function call** `{}`\n\n", name));
+                markdown.push_str(&get_json_block(args.as_ref().unwrap_or(&Value::Null))?);
+                _meta
+            }
+            ChatMessageContent::FunctionCallResponse {
+                name,
+                response,
+                _meta,
+                ..
+            } => {
+                markdown.push_str(&format!("**Function response** `{}`\n\n", name));
+                markdown.push_str(&get_json_block(response)?);
+                _meta
+            }
+            ChatMessageContent::File {
+                path,
+                mime_type,
+                display_name,
+                _meta,
+            } => {
+                markdown.push_str(&format!(
+                    "**Attachment** {} (`{}`, `{}`)\n",
+                    display_name.as_deref().unwrap_or(path),
+                    mime_type,
+                    path
+                ));
+                _meta
+            }
+        };
+
+        if let Some(images) = &message.images {
+            markdown.push_str(&format!("\n![image](data:image/png;base64,{})\n", images));
+        }
+
+        if let Some(meta) = meta {
+            markdown.push_str("\n<details>\n<summary>Metadata</summary>\n\n");
+            markdown.push_str(&get_json_block(meta)?);
+            markdown.push_str("\n</details>\n");
+        }
+    }
+
+    Ok(markdown)
+}
+
+fn get_html(title: &str, messages: &[&TreeMessage]) -> Result<String, NexaError> {
+    let mut body = String::new();
+
+    for message in messages {
+        body.push_str(&format!(
+            "<section class=\"message {}\">\n<h2>{}</h2>\n",
+            get_role_class(&message.role),
+            escape_html(&get_heading(message))
+        ));
+
+        let meta = match &message.content {
+            ChatMessageContent::Text { text, _meta } => {
+                body.push_str(&format!(
+                    "<div class=\"text\">{}</div>\n",
+                    escape_html(text)
+                ));
+                _meta
+            }
+            ChatMessageContent::FunctionCallRequest {
+                name, args, _meta, ..
+            } => {
+                body.push_str(&get_html_details(
+                    &format!("Function call <code>{}</code>", escape_html(name)),
+                    args.as_ref().unwrap_or(&Value::Null),
+                    true,
+                )?);
+                _meta
+            }
+            ChatMessageContent::FunctionCallResponse {
+                name,
+                response,
+                _meta,
+                ..
+            } => {
+                body.push_str(&get_html_details(
+                    &format!("Function response <code>{}</code>", escape_html(name)),
+                    response,
+                    true,
+                )?);
+                _meta
+            }
+            ChatMessageContent::File {
+                path,
+                mime_type,
+                display_name,
+                _meta,
+            } => {
+                body.push_str(&format!(
+                    "<p class=\"attachment\">Attachment {} <code>{}</code> <code>{}</code></p>\n",
+                    escape_html(display_name.as_deref().unwrap_or(path)),
+                    escape_html(mime_type),
+                    escape_html(path)
+                ));
+                _meta
+            }
+        };
+
+        if let Some(images) = &message.images {
+            body.push_str(&format!(
+                "<img src=\"data:image/png;base64,{}\" alt=\"image\">\n",
+                escape_html(images)
+            ));
+        }
+
+        if let Some(meta) = meta {
+            body.push_str(&get_html_details("Metadata", meta, false)?);
+        }
+
+        body.push_str("</section>\n");
+    }
+
+    Ok(format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body {{ font-family: system-ui, sans-serif; max-width: 48rem; margin: 2rem auto; padding: 0 1rem; color: #1f2328; }}
+h2 {{ font-size: 0.9rem; margin: 0 0 0.5rem; color: #59636e; }}
+.message {{ border: 1px solid #d1d9e0; border-radius: 0.5rem; padding: 1rem; margin: 1rem 0; }}
+.message.user {{ background: #f6f8fa; }}
+.text {{ white-space: pre-wrap; }}
+pre {{ background: #f6f8fa; padding: 0.75rem; overflow-x: auto; }}
+img {{ max-width: 100%; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+{body}</body>
+</html>
+"#,
+        title = escape_html(title),
+        body = body
+    ))
+}
+
+fn get_heading(message: &TreeMessage) -> String {
+    let role = match message.role {
+        Role::User => "User",
+        Role::Assistant => "Assistant",
+        Role::System => "System",
+        Role::Function => "Function",
+    };
+
+    match &message.model {
+        Some(model) => format!("{} · {}", role, model),
+        None => role.to_string(),
+    }
+}
+
+fn get_role_class(role: &Role) -> &'static str {
+    match role {
+        Role::User => "user",
+        Role::Assistant => "assistant",
+        Role::System => "system",
+        Role::Function => "function",
+    }
+}
+
+// The fence has to be longer than any run of backticks inside the block
+fn get_json_block(value: &Value) -> Result<String, NexaError> {
+    let json = serde_json::to_string_pretty(value)?;
+
+    let mut longest_run = 0;
+    let mut run = 0;
+    for c in json.chars() {
+        run = if c == '`' { run + 1 } else { 0 };
+        longest_run = longest_run.max(run);
+    }
+    let fence = "`".repeat(longest_run.max(2) + 1);
+
+    Ok(format!("{}json\n{}\n{}\n", fence, json, fence))
+}
+
+fn get_html_details(summary: &str, value: &Value, open: bool) -> Result<String, NexaError> {
+    Ok(format!(
+        "<details{}>\n<summary>{}</summary>\n<pre>{}</pre>\n</details>\n",
+        if open { " open" } else { "" },
+        summary,
+        escape_html(&serde_json::to_string_pretty(value)?)
+    ))
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversation::settings::ConversationSettings;
+    use crate::conversation::store::ConversationSummary;
+    use crate::conversation::tree::ConversationTree;
+    use serde_json::{json, Map};
+
+    fn get_conversation() -> Conversation {
+        let mut tree = ConversationTree::default();
+        let messages = vec![
+            (
+                "u1",
+                None,
+                Role::User,
+                ChatMessageContent::Text {
+                    text: String::from("What's the weather in <Boston>?"),
+                    _meta: None,
+                },
+            ),
+            (
+                "a1",
+                Some("u1"),
+                Role::Assistant,
+                ChatMessageContent::FunctionCallRequest {
+                    id: None,
+                    name: String::from("weather-_-get_forecast"),
+                    args: Some(json!({"city": "Boston"})),
+                    _meta: Some(json!({"thoughtSignature": "c2lnbmF0dXJl"})),
+                },
+            ),
+            (
+                "f1",
+                Some("a1"),
+                Role::Function,
+                ChatMessageContent::FunctionCallResponse {
+                    id: None,
+                    name: String::from("weather-_-get_forecast"),
+                    response: json!({"forecast": "```sunny```"}),
+                    _meta: None,
+                },
+            ),
+        ];
+        for (id, parent_id, role, content) in messages {
+            tree.add_message(TreeMessage {
+                id: id.to_string(),
+                parent_id: parent_id.map(|id: &str| id.to_string()),
+                role,
+                content,
+                images: None,
+                created_at: 1_000,
+                provider: None,
+                model: None,
+                usage: None,
+                cost: None,
+            })
+            .unwrap();
+        }
+
+        Conversation {
+            summary: ConversationSummary {
+                id: String::from("c1"),
+                title: String::from("Weather"),
+                created_at: 1_000,
+                updated_at: 1_000,
+                message_count: 3,
+            },
+            metadata: Map::new(),
+            settings: ConversationSettings::default(),
+            tree,
+        }
+    }
+
+    #[test]
+    fn export_markdown_test() {
+        let markdown = get_export(&get_conversation(), &ExportFormat::Markdown).unwrap();
+
+        assert!(markdown.starts_with("# Weather\n"));
+        assert!(markdown.contains("**Function call** `weather-_-get_forecast`"));
+        assert!(markdown.contains("\"thoughtSignature\": \"c2lnbmF0dXJl\""));
+        // Backticks in the response don't close the fence early
+        assert!(markdown.contains("````json\n{\n  \"forecast\": \"```sunny```\"\n}\n````"));
+    }
+
+    #[test]
+    fn export_html_test() {
+        let html = get_export(&get_conversation(), &ExportFormat::Html).unwrap();
+
+        assert!(html.contains("What&#39;s the weather in &lt;Boston&gt;?"));
+        assert!(html.contains("<section class=\"message function\">"));
+        assert!(!html.contains("<Boston>"));
+    }
+
+    #[test]
+    fn export_json_test() {
+        let conversation = get_conversation();
+        let json = get_export(&conversation, &ExportFormat::Json).unwrap();
+
+        let export: ConversationExport = serde_json::from_str(&json).unwrap();
+        assert_eq!(export.format, NEXA_EXPORT_FORMAT);
+        assert_eq!(
+            serde_json::to_value(&export.conversation).unwrap(),
+            serde_json::to_value(&conversation).unwrap()
+        );
+    }
+}
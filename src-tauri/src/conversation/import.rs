@@ -0,0 +1,482 @@
+use crate::conversation::export::{ConversationExport, NEXA_EXPORT_FORMAT, NEXA_EXPORT_VERSION};
+use crate::conversation::store::{ConversationStore, ConversationSummary};
+use crate::conversation::tree::ConversationTree;
+use crate::error::NexaError;
+use crate::llm::base::{ChatMessageContent, ChatMessageWithId, Role};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+// A chat from another app, flattened to the branch that was active there
+#[derive(Clone, Debug)]
+pub struct ImportedChat {
+    pub title: String,
+    pub messages: Vec<ChatMessageWithId>,
+}
+
+#[derive(Deserialize)]
+struct ChatGptConversation {
+    title: Option<String>,
+    mapping: HashMap<String, ChatGptNode>,
+    current_node: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChatGptNode {
+    message: Option<ChatGptMessage>,
+    parent: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChatGptMessage {
+    author: ChatGptAuthor,
+    content: ChatGptContent,
+    recipient: Option<String>,
+    metadata: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct ChatGptAuthor {
+    role: String,
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChatGptContent {
+    #[serde(default)]
+    parts: Vec<Value>,
+    text: Option<String>,
+    result: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OpenWebUiChat {
+    title: Option<String>,
+    chat: OpenWebUiChatData,
+}
+
+#[derive(Deserialize)]
+struct OpenWebUiChatData {
+    title: Option<String>,
+    history: Option<OpenWebUiHistory>,
+    #[serde(default)]
+    messages: Vec<OpenWebUiMessage>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OpenWebUiHistory {
+    messages: HashMap<String, OpenWebUiMessage>,
+    current_id: Option<String>,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct OpenWebUiMessage {
+    parent_id: Option<String>,
+    role: String,
+    #[serde(default)]
+    content: String,
+}
+
+impl ConversationStore {
+    // Takes the contents of a Nexa JSON export, a ChatGPT conversations.json or
+    // an Open WebUI chat export and figures out which one it is
+    pub fn import_conversations(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<ConversationSummary>, NexaError> {
+        let value: Value = serde_json::from_str(contents)?;
+
+        if value.get("format").and_then(Value::as_str) == Some(NEXA_EXPORT_FORMAT) {
+            let export: ConversationExport = serde_json::from_value(value)?;
+            return Ok(vec![
+                self.write_all(|store| store.import_conversation_export(export))?
+            ]);
+        }
+
+        let chats = match value.as_array().and_then(|chats| chats.first()) {
+            Some(chat) if chat.get("mapping").is_some() => parse_chatgpt_export(value)?,
+            Some(chat) if chat.get("chat").is_some() => parse_open_webui_export(value)?,
+            // Open WebUI also exports a single chat as a bare object
+            None if value.get("chat").is_some() => {
+                parse_open_webui_export(Value::Array(vec![value]))?
+            }
+            _ => {
+                return Err(NexaError::Conversation(String::from(
+                    "Unrecognized conversation export file",
+                )))
+            }
+        };
+
+        // A file is imported as a whole or not at all
+        self.write_all(|store| {
+            chats
+                .into_iter()
+                .map(|chat| store.import_messages(&chat.title, chat.messages))
+                .collect()
+        })
+    }
+
+    // Message ids are global, so the tree gets fresh ones in case the same file
+    // is imported twice
+    fn import_conversation_export(
+        &mut self,
+        export: ConversationExport,
+    ) -> Result<ConversationSummary, NexaError> {
+        if export.version > NEXA_EXPORT_VERSION {
+            return Err(NexaError::Conversation(format!(
+                "Unsupported export version {}",
+                export.version
+            )));
+        }
+
+        let conversation = export.conversation;
+        let ids: HashMap<String, String> = conversation
+            .tree
+            .messages
+            .iter()
+            .map(|message| (message.id.clone(), Uuid::new_v4().to_string()))
+            .collect();
+        let get_new_id = |id: &String| {
+            ids.get(id).cloned().ok_or(NexaError::Conversation(format!(
+                "Can't find message {} in the export",
+                id
+            )))
+        };
+
+        let mut tree = ConversationTree::default();
+        for mut message in conversation.tree.messages {
+            message.id = get_new_id(&message.id)?;
+            message.parent_id = message.parent_id.as_ref().map(get_new_id).transpose()?;
+            tree.add_message(message)?;
+        }
+        tree.active_leaf_id = conversation
+            .tree
+            .active_leaf_id
+            .as_ref()
+            .map(get_new_id)
+            .transpose()?;
+
+        let summary = self.create_conversation(&conversation.summary.title)?;
+        self.save_tree(&summary.id, &tree)?;
+        self.set_metadata(&summary.id, &conversation.metadata)?;
+        self.set_settings(&summary.id, &conversation.settings)?;
+
+        Ok(ConversationSummary {
+            message_count: tree.messages.len() as u64,
+            ..summary
+        })
+    }
+}
+
+pub fn parse_chatgpt_export(value: Value) -> Result<Vec<ImportedChat>, NexaError> {
+    let conversations: Vec<ChatGptConversation> = serde_json::from_value(value)?;
+    let mut chats = vec![];
+
+    for conversation in conversations {
+        // Walk up from the node that was shown last, then flip to root first
+        let mut nodes = vec![];
+        let mut node_id = conversation.current_node;
+        while let Some(node) = node_id.and_then(|id| conversation.mapping.get(&id)) {
+            if nodes.len() > conversation.mapping.len() {
+                break;
+            }
+            nodes.push(node);
+            node_id = node.parent.clone();
+        }
+        nodes.reverse();
+
+        let messages = nodes
+            .into_iter()
+            .filter_map(|node| node.message.as_ref())
+            .filter_map(get_chatgpt_message)
+            .collect();
+
+        chats.push(ImportedChat {
+            title: conversation
+                .title
+                .unwrap_or(String::from("Imported ChatGPT chat")),
+            messages,
+        });
+    }
+
+    Ok(chats)
+}
+
+fn get_chatgpt_message(message: &ChatGptMessage) -> Option<ChatMessageWithId> {
+    let hidden = message
+        .metadata
+        .as_ref()
+        .and_then(|metadata| metadata.get("is_visually_hidden_from_conversation"))
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+    if hidden {
+        return None;
+    }
+
+    let parts: Vec<&str> = message
+        .content
+        .parts
+        .iter()
+        .filter_map(Value::as_str)
+        .collect();
+    let text = match (&message.content.text, &message.content.result) {
+        _ if !parts.is_empty() => parts.join("\n"),
+        (Some(text), _) => text.clone(),
+        (None, Some(result)) => result.clone(),
+        (None, None) => String::new(),
+    };
+    if text.trim().is_empty() {
+        return None;
+    }
+
+    let recipient = message.recipient.as_deref().unwrap_or("all");
+    let (role, content) = match message.author.role.as_str() {
+        "user" => (Role::User, get_text_content(text)),
+        "system" => (Role::System, get_text_content(text)),
+        // Messages addressed to a tool are ChatGPT's function calls
+        "assistant" if recipient != "all" => (
+            Role::Assistant,
+            ChatMessageContent::FunctionCallRequest {
+                id: None,
+                name: recipient.to_string(),
+                args: Some(serde_json::from_str(&text).unwrap_or(json!({ "input": text }))),
+                _meta: None,
+            },
+        ),
+        "assistant" => (Role::Assistant, get_text_content(text)),
+        "tool" => (
+            Role::User,
+            ChatMessageContent::FunctionCallResponse {
+                id: None,
+                name: message.author.name.clone().unwrap_or(String::from("tool")),
+                response: json!({ "output": text }),
+                _meta: None,
+            },
+        ),
+        _ => return None,
+    };
+
+    Some(ChatMessageWithId {
+        id: Uuid::new_v4().to_string(),
+        role,
+        content,
+        images: None,
+    })
+}
+
+pub fn parse_open_webui_export(value: Value) -> Result<Vec<ImportedChat>, NexaError> {
+    let exported: Vec<OpenWebUiChat> = serde_json::from_value(value)?;
+    let mut chats = vec![];
+
+    for exported_chat in exported {
+        let chat = exported_chat.chat;
+
+        // The history tree knows about regenerated answers, the flat message
+        // list is only there in older exports
+        let messages = match chat.history {
+            Some(history) => {
+                let mut messages = vec![];
+                let mut message_id = history.current_id;
+                while let Some(message) = message_id.and_then(|id| history.messages.get(&id)) {
+                    if messages.len() > history.messages.len() {
+                        break;
+                    }
+                    messages.push(message.clone());
+                    message_id = message.parent_id.clone();
+                }
+                messages.reverse();
+                messages
+            }
+            None => chat.messages,
+        };
+
+        chats.push(ImportedChat {
+            title: exported_chat
+                .title
+                .or(chat.title)
+                .unwrap_or(String::from("Imported Open WebUI chat")),
+            messages: messages
+                .into_iter()
+                .filter_map(|message| {
+                    let role = match message.role.as_str() {
+                        "user" => Role::User,
+                        "assistant" => Role::Assistant,
+                        "system" => Role::System,
+                        _ => return None,
+                    };
+
+                    Some(ChatMessageWithId {
+                        id: Uuid::new_v4().to_string(),
+                        role,
+                        content: get_text_content(message.content),
+                        images: None,
+                    })
+                })
+                .collect(),
+        });
+    }
+
+    Ok(chats)
+}
+
+fn get_text_content(text: String) -> ChatMessageContent {
+    ChatMessageContent::Text { text, _meta: None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversation::export::{get_export, ExportFormat};
+    use crate::conversation::tree::TreeMessage;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn get_fixture(name: &str) -> String {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join("import")
+            .join(name);
+
+        fs::read_to_string(path).unwrap()
+    }
+
+    fn get_texts(messages: &[ChatMessageWithId]) -> Vec<String> {
+        messages
+            .iter()
+            .map(|message| match &message.content {
+                ChatMessageContent::Text { text, .. } => text.clone(),
+                ChatMessageContent::FunctionCallRequest { name, .. } => format!("call {}", name),
+                ChatMessageContent::FunctionCallResponse { name, .. } => {
+                    format!("response {}", name)
+                }
+                ChatMessageContent::File { path, .. } => path.clone(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parse_chatgpt_export_test() {
+        let value = serde_json::from_str(&get_fixture("chatgpt.json")).unwrap();
+        let chats = parse_chatgpt_export(value).unwrap();
+
+        assert_eq!(chats.len(), 1);
+        assert_eq!(chats[0].title, "Boston weather");
+        // The hidden system prompt and the abandoned branch are left out
+        assert_eq!(
+            get_texts(&chats[0].messages),
+            vec![
+                "What's the weather in Boston?",
+                "call browser",
+                "response browser",
+                "It's sunny and 24°C in Boston.",
+            ]
+        );
+        assert!(matches!(
+            &chats[0].messages[1].content,
+            ChatMessageContent::FunctionCallRequest { args: Some(args), .. } if args["q"] == "Boston weather"
+        ));
+    }
+
+    #[test]
+    fn parse_open_webui_export_test() {
+        let value = serde_json::from_str(&get_fixture("open_webui.json")).unwrap();
+        let chats = parse_open_webui_export(value).unwrap();
+
+        assert_eq!(chats.len(), 1);
+        assert_eq!(chats[0].title, "Sourdough");
+        assert_eq!(
+            get_texts(&chats[0].messages),
+            vec![
+                "How long should I bake sourdough bread?",
+                "Bake it for about 40 minutes.",
+            ]
+        );
+        assert_eq!(chats[0].messages[1].role, Role::Assistant);
+    }
+
+    #[test]
+    fn import_conversations_test() {
+        let mut store = ConversationStore::open_in_memory().unwrap();
+
+        let imported = store
+            .import_conversations(&get_fixture("chatgpt.json"))
+            .unwrap();
+        assert_eq!(imported[0].message_count, 4);
+        let imported = store
+            .import_conversations(&get_fixture("open_webui.json"))
+            .unwrap();
+        assert_eq!(imported[0].message_count, 2);
+
+        assert!(store.import_conversations("{\"foo\": 1}").is_err());
+    }
+
+    #[test]
+    fn json_export_roundtrip_test() {
+        let mut store = ConversationStore::open_in_memory().unwrap();
+        let summary = store.create_conversation("Roundtrip").unwrap();
+
+        let mut tree = ConversationTree::default();
+        tree.add_message(TreeMessage {
+            id: String::from("u1"),
+            parent_id: None,
+            role: Role::User,
+            content: get_text_content(String::from("Weather?")),
+            images: None,
+            created_at: 1_000,
+            provider: None,
+            model: None,
+            usage: None,
+            cost: None,
+        })
+        .unwrap();
+        tree.add_message(TreeMessage {
+            id: String::from("a1"),
+            parent_id: Some(String::from("u1")),
+            role: Role::Assistant,
+            content: ChatMessageContent::FunctionCallRequest {
+                id: Some(String::from("call-1")),
+                name: String::from("weather-_-get_forecast"),
+                args: Some(json!({"city": "Boston"})),
+                _meta: Some(json!({"thoughtSignature": "c2lnbmF0dXJl"})),
+            },
+            images: None,
+            created_at: 1_001,
+            provider: None,
+            model: None,
+            usage: None,
+            cost: None,
+        })
+        .unwrap();
+        tree.regenerate("a1").unwrap();
+        store.save_tree(&summary.id, &tree).unwrap();
+
+        let original = store.load_conversation(&summary.id).unwrap();
+        let json = get_export(&original, &ExportFormat::Json).unwrap();
+
+        // Importing twice doesn't collide on message ids
+        store.import_conversations(&json).unwrap();
+        let imported = store.import_conversations(&json).unwrap();
+        let copy = store.load_conversation(&imported[0].id).unwrap();
+
+        assert_eq!(copy.summary.title, "Roundtrip");
+        assert_eq!(copy.tree.messages.len(), 3);
+        let strip_ids = |tree: &ConversationTree| {
+            tree.messages
+                .iter()
+                .map(|message| {
+                    (
+                        serde_json::to_value(&message.content).unwrap(),
+                        message.created_at,
+                        message.parent_id.is_some(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(strip_ids(&copy.tree), strip_ids(&original.tree));
+        let leaf = copy.tree.active_leaf_id.as_ref().unwrap();
+        assert_eq!(copy.tree.get_path(leaf).unwrap().len(), 2);
+    }
+}
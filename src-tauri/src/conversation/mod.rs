@@ -0,0 +1,7 @@
+pub mod commands;
+pub mod export;
+pub mod import;
+pub mod search;
+pub mod settings;
+pub mod store;
+pub mod tree;
@@ -0,0 +1,344 @@
+use crate::conversation::store::{get_enum_str, ConversationStore};
+use crate::error::NexaError;
+use crate::llm::base::{Provider, Role};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// FTS5 marks matches with these in the snippet; private use code points can't
+// show up in real chat text
+static HIGHLIGHT_START: char = '\u{E000}';
+static HIGHLIGHT_END: char = '\u{E001}';
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchFilters {
+    pub provider: Option<Provider>,
+    pub model: Option<String>,
+    // Unix seconds, both inclusive
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    pub has_tool_calls: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SnippetPart {
+    pub text: String,
+    pub highlighted: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResult {
+    pub conversation_id: String,
+    pub conversation_title: String,
+    pub message_id: String,
+    pub role: Role,
+    pub provider: Option<Provider>,
+    pub model: Option<String>,
+    pub created_at: u64,
+    pub snippet: Vec<SnippetPart>,
+}
+
+impl ConversationStore {
+    // Ranked by bm25, best match first. Provider, model and tool call filters
+    // apply to the conversation the message belongs to, the date range to the
+    // message itself.
+    pub fn search(
+        &self,
+        query: &str,
+        filters: &SearchFilters,
+        limit: u32,
+    ) -> Result<Vec<SearchResult>, NexaError> {
+        let Some(match_query) = get_match_query(query) else {
+            return Ok(vec![]);
+        };
+
+        let mut statement = self.conn.prepare(
+            "SELECT m.conversation_id, c.title, m.id, m.role, m.provider, m.model, m.created_at,
+                    snippet(messages_fts, 0, ?2, ?3, '…', 16)
+             FROM messages_fts
+             JOIN messages m ON m.rowid = messages_fts.rowid
+             JOIN conversations c ON c.id = m.conversation_id
+             WHERE messages_fts MATCH ?1
+               AND (?4 IS NULL OR EXISTS (
+                    SELECT 1 FROM messages p
+                    WHERE p.conversation_id = m.conversation_id AND p.provider = ?4))
+               AND (?5 IS NULL OR EXISTS (
+                    SELECT 1 FROM messages p
+                    WHERE p.conversation_id = m.conversation_id AND p.model = ?5))
+               AND (?6 IS NULL OR m.created_at >= ?6)
+               AND (?7 IS NULL OR m.created_at <= ?7)
+               AND (?8 IS NULL OR ?8 = EXISTS (
+                    SELECT 1 FROM messages t
+                    WHERE t.conversation_id = m.conversation_id
+                      AND json_extract(t.content, '$.type')
+                          IN ('functionCallRequest', 'functionCallResponse')))
+             ORDER BY bm25(messages_fts)
+             LIMIT ?9",
+        )?;
+
+        let rows = statement
+            .query_map(
+                params![
+                    match_query,
+                    HIGHLIGHT_START.to_string(),
+                    HIGHLIGHT_END.to_string(),
+                    filters.provider.as_ref().map(get_enum_str).transpose()?,
+                    filters.model,
+                    filters.from,
+                    filters.to,
+                    filters.has_tool_calls,
+                    limit,
+                ],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, String>(3)?,
+                        row.get::<_, Option<String>>(4)?,
+                        row.get::<_, Option<String>>(5)?,
+                        row.get::<_, u64>(6)?,
+                        row.get::<_, String>(7)?,
+                    ))
+                },
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut results = vec![];
+        for (conversation_id, title, message_id, role, provider, model, created_at, snippet) in rows
+        {
+            results.push(SearchResult {
+                conversation_id,
+                conversation_title: title,
+                message_id,
+                role: serde_json::from_value(Value::String(role))?,
+                provider: provider
+                    .map(|provider| serde_json::from_value(Value::String(provider)))
+                    .transpose()?,
+                model,
+                created_at,
+                snippet: get_snippet_parts(&snippet),
+            });
+        }
+
+        Ok(results)
+    }
+}
+
+// User input is not FTS5 syntax. Every word becomes a quoted phrase so quotes,
+// dashes and operators are searched literally, and the last word also matches
+// as a prefix for search-as-you-type.
+fn get_match_query(query: &str) -> Option<String> {
+    let words: Vec<String> = query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect();
+
+    let mut match_query = words.join(" ");
+    if match_query.is_empty() {
+        return None;
+    }
+    match_query.push('*');
+
+    Some(match_query)
+}
+
+fn get_snippet_parts(snippet: &str) -> Vec<SnippetPart> {
+    let mut parts: Vec<SnippetPart> = vec![];
+    let mut current = String::new();
+    let mut highlighted = false;
+
+    for c in snippet.chars() {
+        if c == HIGHLIGHT_START || c == HIGHLIGHT_END {
+            if !current.is_empty() {
+                parts.push(SnippetPart {
+                    text: std::mem::take(&mut current),
+                    highlighted,
+                });
+            }
+            highlighted = c == HIGHLIGHT_START;
+        } else {
+            current.push(c);
+        }
+    }
+    if !current.is_empty() {
+        parts.push(SnippetPart {
+            text: current,
+            highlighted,
+        });
+    }
+
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversation::tree::{ConversationTree, TreeMessage};
+    use crate::llm::base::ChatMessageContent;
+    use serde_json::json;
+
+    fn get_message(id: &str, parent_id: Option<&str>, content: ChatMessageContent) -> TreeMessage {
+        TreeMessage {
+            id: id.to_string(),
+            parent_id: parent_id.map(|id| id.to_string()),
+            role: Role::User,
+            content,
+            images: None,
+            created_at: 1_000,
+            provider: None,
+            model: None,
+            usage: None,
+            cost: None,
+        }
+    }
+
+    fn get_store() -> (ConversationStore, String, String) {
+        let mut store = ConversationStore::open_in_memory().unwrap();
+
+        let recipes = store.create_conversation("Recipes").unwrap();
+        let mut tree = ConversationTree::default();
+        tree.add_message(get_message(
+            "r1",
+            None,
+            ChatMessageContent::Text {
+                text: String::from("How long should I bake sourdough bread?"),
+                _meta: None,
+            },
+        ))
+        .unwrap();
+        tree.add_message(get_message(
+            "r2",
+            Some("r1"),
+            ChatMessageContent::Text {
+                text: String::new(),
+                _meta: None,
+            },
+        ))
+        .unwrap();
+        tree.set_reply(
+            "r2",
+            ChatMessageContent::Text {
+                text: String::from("Bake the sourdough for about 40 minutes."),
+                _meta: None,
+            },
+            Provider::Ollama,
+            String::from("llama3.2"),
+            None,
+            None,
+        )
+        .unwrap();
+        store.save_tree(&recipes.id, &tree).unwrap();
+
+        let weather = store.create_conversation("Weather").unwrap();
+        let mut tree = ConversationTree::default();
+        tree.add_message(get_message(
+            "w1",
+            None,
+            ChatMessageContent::FunctionCallRequest {
+                id: None,
+                name: String::from("weather-_-get_forecast"),
+                args: Some(json!({"city": "Boston"})),
+                _meta: None,
+            },
+        ))
+        .unwrap();
+        tree.add_message(get_message(
+            "w2",
+            Some("w1"),
+            ChatMessageContent::FunctionCallResponse {
+                id: None,
+                name: String::from("weather-_-get_forecast"),
+                response: json!({"forecast": "Sunny, good day to bake"}),
+                _meta: None,
+            },
+        ))
+        .unwrap();
+        store.save_tree(&weather.id, &tree).unwrap();
+
+        (store, recipes.id, weather.id)
+    }
+
+    #[test]
+    fn search_conversations_test() {
+        let (store, recipes_id, weather_id) = get_store();
+        let no_filters = SearchFilters::default();
+
+        // Ranked hits with highlighted snippets, prefix match on the last word
+        let results = store.search("sourdo", &no_filters, 10).unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| r.conversation_id == recipes_id));
+        assert!(results[0]
+            .snippet
+            .iter()
+            .any(|part| part.highlighted && part.text.eq_ignore_ascii_case("sourdough")));
+
+        // Tool call names, arguments and results are searchable
+        let results = store.search("Boston", &no_filters, 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].message_id, "w1");
+        assert_eq!(
+            store.search("get_forecast", &no_filters, 10).unwrap().len(),
+            2
+        );
+
+        // Filters
+        let bake = |filters: SearchFilters| {
+            store
+                .search("bake", &filters, 10)
+                .unwrap()
+                .into_iter()
+                .map(|result| result.conversation_id)
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(bake(no_filters.clone()).len(), 3);
+        assert!(bake(SearchFilters {
+            has_tool_calls: Some(true),
+            ..Default::default()
+        })
+        .iter()
+        .all(|id| *id == weather_id));
+        assert!(bake(SearchFilters {
+            provider: Some(Provider::Ollama),
+            model: Some(String::from("llama3.2")),
+            ..Default::default()
+        })
+        .iter()
+        .all(|id| *id == recipes_id));
+        assert!(bake(SearchFilters {
+            from: Some(2_000),
+            ..Default::default()
+        })
+        .is_empty());
+
+        // FTS syntax in the input is searched literally
+        assert!(store.search("\"bake AND (", &no_filters, 10).is_ok());
+        assert!(store.search("   ", &no_filters, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn snippet_parts_test() {
+        let snippet = format!("…the {}sourdough{} bread", HIGHLIGHT_START, HIGHLIGHT_END);
+
+        assert_eq!(
+            get_snippet_parts(&snippet),
+            vec![
+                SnippetPart {
+                    text: String::from("…the "),
+                    highlighted: false,
+                },
+                SnippetPart {
+                    text: String::from("sourdough"),
+                    highlighted: true,
+                },
+                SnippetPart {
+                    text: String::from(" bread"),
+                    highlighted: false,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use crate::api::gemini::SafetySetting;
+use crate::conversation::store::{get_not_found_error, ConversationStore};
+use crate::error::NexaError;
+use crate::llm::base::{GenerationConfig, Provider, ToolCallingMode};
+use crate::llm::ollama::OllamaKeepAlive;
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+// Everything needed to answer in a conversation besides the messages, so
+// switching chats brings back the model and tools that chat was set up with.
+// Options a provider doesn't support are ignored for it.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<Provider>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    // Which stored key to use, the default profile if unset. With rotation on,
+    // a key that ran out of quota hands over to the provider's other profiles.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credential_profile: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotate_credentials: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    #[serde(default)]
+    pub generation: GenerationConfig,
+    // MCP server names and "server-_-tool" function names. Unset enables all
+    // of them, including servers connected later.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mcp_servers: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calling_mode: Option<ToolCallingMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_alive: Option<OllamaKeepAlive>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub safety_settings: Option<Vec<SafetySetting>>,
+    // How many times a reply cut off by the token limit is continued
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_continue: Option<u32>,
+    // USD. Once reached, no more requests are sent for the conversation, or
+    // for the replies since the user's last message (a model calling tools
+    // in a loop).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_conversation_cost: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_agent_loop_cost: Option<f64>,
+}
+
+impl ConversationSettings {
+    pub fn is_server_enabled(&self, server_name: &str) -> bool {
+        self.mcp_servers
+            .as_ref()
+            .is_none_or(|servers| servers.iter().any(|name| name == server_name))
+    }
+
+    pub fn is_tool_enabled(&self, function_name: &str) -> bool {
+        self.tools
+            .as_ref()
+            .is_none_or(|tools| tools.iter().any(|name| name == function_name))
+    }
+}
+
+impl ConversationStore {
+    pub fn get_settings(&self, id: &str) -> Result<ConversationSettings, NexaError> {
+        let settings: String = self
+            .conn
+            .query_row(
+                "SELECT settings FROM conversations WHERE id = ?1",
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()?
+            .ok_or(get_not_found_error(id))?;
+
+        Ok(serde_json::from_str(&settings)?)
+    }
+
+    pub fn set_settings(&self, id: &str, settings: &ConversationSettings) -> Result<(), NexaError> {
+        let updated = self.conn.execute(
+            "UPDATE conversations SET settings = ?2 WHERE id = ?1",
+            params![id, serde_json::to_string(settings)?],
+        )?;
+
+        match updated {
+            0 => Err(get_not_found_error(id)),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn conversation_settings_test() {
+        let store = ConversationStore::open_in_memory().unwrap();
+        let conversation = store.create_conversation("Settings").unwrap();
+
+        // Nothing stored yet
+        assert_eq!(
+            store.get_settings(&conversation.id).unwrap(),
+            ConversationSettings::default()
+        );
+
+        let settings: ConversationSettings = serde_json::from_value(json!({
+            "provider": "ollama",
+            "model": "llama3.2",
+            "systemPrompt": "Answer in French.",
+            "generation": {"temperature": 0.4, "seed": 7},
+            "mcpServers": ["weather"],
+            "toolCallingMode": "auto",
+            "keepAlive": "10m"
+        }))
+        .unwrap();
+        store.set_settings(&conversation.id, &settings).unwrap();
+        assert_eq!(store.get_settings(&conversation.id).unwrap(), settings);
+
+        assert!(settings.is_server_enabled("weather"));
+        assert!(!settings.is_server_enabled("files"));
+        assert!(settings.is_tool_enabled("weather-_-get_forecast"));
+
+        assert!(store.set_settings("missing", &settings).is_err());
+    }
+}
@@ -0,0 +1,713 @@
+use crate::conversation::settings::ConversationSettings;
+use crate::conversation::tree::{ConversationTree, TreeMessage};
+use crate::error::NexaError;
+use crate::llm::base::{ChatMessageContent, ChatMessageWithId};
+use crate::llm::cache::now_secs;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::Path;
+use uuid::Uuid;
+
+pub(crate) static CONVERSATION_DB_FILENAME: &str = "nexa.db";
+pub(crate) static CHAT_HISTORY_STORE_FILENAME: &str = "chat-history.json";
+static CHAT_HISTORY_IMPORTED_KEY: &str = "chat_history_store_imported";
+
+// Applied in order, the index of the last applied one is kept in
+// PRAGMA user_version. Never edit a migration that shipped, add a new one.
+static MIGRATIONS: &[&str] = &[
+    r#"
+    CREATE TABLE conversations (
+        id TEXT PRIMARY KEY,
+        title TEXT NOT NULL,
+        active_leaf_id TEXT,
+        metadata TEXT NOT NULL DEFAULT '{}',
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL
+    );
+
+    CREATE TABLE messages (
+        id TEXT PRIMARY KEY,
+        conversation_id TEXT NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+        parent_id TEXT,
+        role TEXT NOT NULL,
+        content TEXT NOT NULL,
+        images TEXT,
+        created_at INTEGER NOT NULL
+    );
+    CREATE INDEX messages_conversation_id ON messages(conversation_id);
+
+    CREATE TABLE attachments (
+        message_id TEXT PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+        path TEXT NOT NULL,
+        mime_type TEXT NOT NULL,
+        display_name TEXT
+    );
+
+    CREATE TABLE app_metadata (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+"#,
+    // Full-text search. The searchable text of a message is derived from its
+    // JSON content by a view, and triggers keep the index in sync with it.
+    r#"
+    ALTER TABLE messages ADD COLUMN provider TEXT;
+    ALTER TABLE messages ADD COLUMN model TEXT;
+
+    CREATE VIEW message_search_text AS
+    SELECT
+        rowid AS message_rowid,
+        CASE json_extract(content, '$.type')
+            WHEN 'text' THEN json_extract(content, '$.content.text')
+            WHEN 'functionCallRequest' THEN json_extract(content, '$.content.name')
+                || ' ' || coalesce(json_extract(content, '$.content.args'), '')
+            WHEN 'functionCallResponse' THEN json_extract(content, '$.content.name')
+                || ' ' || coalesce(json_extract(content, '$.content.response'), '')
+            WHEN 'file' THEN coalesce(
+                json_extract(content, '$.content.displayName'),
+                json_extract(content, '$.content.path')
+            )
+        END AS text
+    FROM messages;
+
+    CREATE VIRTUAL TABLE messages_fts USING fts5(
+        text,
+        tokenize = 'unicode61 remove_diacritics 2'
+    );
+
+    CREATE TRIGGER messages_fts_insert AFTER INSERT ON messages BEGIN
+        INSERT INTO messages_fts (rowid, text)
+        SELECT message_rowid, text FROM message_search_text WHERE message_rowid = new.rowid;
+    END;
+
+    CREATE TRIGGER messages_fts_update AFTER UPDATE OF content ON messages BEGIN
+        DELETE FROM messages_fts WHERE rowid = old.rowid;
+        INSERT INTO messages_fts (rowid, text)
+        SELECT message_rowid, text FROM message_search_text WHERE message_rowid = new.rowid;
+    END;
+
+    CREATE TRIGGER messages_fts_delete AFTER DELETE ON messages BEGIN
+        DELETE FROM messages_fts WHERE rowid = old.rowid;
+    END;
+
+    INSERT INTO messages_fts (rowid, text) SELECT message_rowid, text FROM message_search_text;
+"#,
+    r#"
+    ALTER TABLE conversations ADD COLUMN settings TEXT NOT NULL DEFAULT '{}';
+"#,
+    r#"
+    ALTER TABLE messages ADD COLUMN usage TEXT;
+    ALTER TABLE messages ADD COLUMN cost REAL;
+"#,
+];
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationSummary {
+    pub id: String,
+    pub title: String,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub message_count: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Conversation {
+    pub summary: ConversationSummary,
+    pub metadata: Map<String, Value>,
+    // Exports written before settings existed don't have them
+    #[serde(default)]
+    pub settings: ConversationSettings,
+    pub tree: ConversationTree,
+}
+
+pub(crate) struct ConversationStore {
+    pub(super) conn: Connection,
+}
+
+impl ConversationStore {
+    pub fn open(path: &Path) -> Result<Self, NexaError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        Self::init(Connection::open(path)?)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self, NexaError> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, NexaError> {
+        conn.execute_batch("PRAGMA foreign_keys = ON; PRAGMA journal_mode = WAL;")?;
+
+        let mut store = ConversationStore { conn };
+        store.migrate()?;
+
+        Ok(store)
+    }
+
+    fn migrate(&mut self) -> Result<(), NexaError> {
+        let version: usize = self
+            .conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            let tx = self.conn.transaction()?;
+            tx.execute_batch(migration)?;
+            tx.pragma_update(None, "user_version", index + 1)?;
+            tx.commit()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn list_conversations(&self) -> Result<Vec<ConversationSummary>, NexaError> {
+        let mut statement = self.conn.prepare(
+            "SELECT c.id, c.title, c.created_at, c.updated_at,
+                    (SELECT COUNT(*) FROM messages m WHERE m.conversation_id = c.id)
+             FROM conversations c
+             ORDER BY c.updated_at DESC, c.rowid DESC",
+        )?;
+
+        let summaries = statement
+            .query_map([], |row| {
+                Ok(ConversationSummary {
+                    id: row.get(0)?,
+                    title: row.get(1)?,
+                    created_at: row.get(2)?,
+                    updated_at: row.get(3)?,
+                    message_count: row.get(4)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(summaries)
+    }
+
+    pub fn create_conversation(&self, title: &str) -> Result<ConversationSummary, NexaError> {
+        let now = now_secs();
+        let summary = ConversationSummary {
+            id: Uuid::new_v4().to_string(),
+            title: title.to_string(),
+            created_at: now,
+            updated_at: now,
+            message_count: 0,
+        };
+
+        self.conn.execute(
+            "INSERT INTO conversations (id, title, created_at, updated_at) VALUES (?1, ?2, ?3, ?4)",
+            params![summary.id, summary.title, now, now],
+        )?;
+
+        Ok(summary)
+    }
+
+    pub fn rename_conversation(&self, id: &str, title: &str) -> Result<(), NexaError> {
+        let updated = self.conn.execute(
+            "UPDATE conversations SET title = ?2, updated_at = ?3 WHERE id = ?1",
+            params![id, title, now_secs()],
+        )?;
+
+        match updated {
+            0 => Err(get_not_found_error(id)),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn delete_conversation(&self, id: &str) -> Result<(), NexaError> {
+        let deleted = self
+            .conn
+            .execute("DELETE FROM conversations WHERE id = ?1", params![id])?;
+
+        match deleted {
+            0 => Err(get_not_found_error(id)),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn load_conversation(&self, id: &str) -> Result<Conversation, NexaError> {
+        let (title, active_leaf_id, metadata, settings, created_at, updated_at) = self
+            .conn
+            .query_row(
+                "SELECT title, active_leaf_id, metadata, settings, created_at, updated_at
+                 FROM conversations WHERE id = ?1",
+                params![id],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, Option<String>>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, String>(3)?,
+                        row.get::<_, u64>(4)?,
+                        row.get::<_, u64>(5)?,
+                    ))
+                },
+            )
+            .optional()?
+            .ok_or(get_not_found_error(id))?;
+
+        let mut statement = self.conn.prepare(
+            "SELECT id, parent_id, role, content, images, created_at, provider, model, usage, cost
+             FROM messages WHERE conversation_id = ?1 ORDER BY rowid",
+        )?;
+        let rows = statement
+            .query_map(params![id], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, u64>(5)?,
+                    row.get::<_, Option<String>>(6)?,
+                    row.get::<_, Option<String>>(7)?,
+                    row.get::<_, Option<String>>(8)?,
+                    row.get::<_, Option<f64>>(9)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut messages = vec![];
+        for (
+            message_id,
+            parent_id,
+            role,
+            content,
+            images,
+            message_created_at,
+            provider,
+            model,
+            usage,
+            cost,
+        ) in rows
+        {
+            messages.push(TreeMessage {
+                id: message_id,
+                parent_id,
+                role: serde_json::from_value(Value::String(role))?,
+                content: serde_json::from_str(&content)?,
+                images,
+                created_at: message_created_at,
+                provider: provider
+                    .map(|provider| serde_json::from_value(Value::String(provider)))
+                    .transpose()?,
+                model,
+                usage: usage
+                    .map(|usage| serde_json::from_str(&usage))
+                    .transpose()?,
+                cost,
+            });
+        }
+
+        Ok(Conversation {
+            summary: ConversationSummary {
+                id: id.to_string(),
+                title,
+                created_at,
+                updated_at,
+                message_count: messages.len() as u64,
+            },
+            metadata: serde_json::from_str(&metadata)?,
+            settings: serde_json::from_str(&settings)?,
+            tree: ConversationTree {
+                messages,
+                active_leaf_id,
+            },
+        })
+    }
+
+    pub fn load_tree(&self, id: &str) -> Result<ConversationTree, NexaError> {
+        Ok(self.load_conversation(id)?.tree)
+    }
+
+    // Writes new messages, updated contents and the active leaf in one go.
+    // Messages are never removed from a tree, so upserting all of them is enough.
+    pub fn save_tree(&mut self, id: &str, tree: &ConversationTree) -> Result<(), NexaError> {
+        // A savepoint rather than a transaction, so an import can wrap it
+        let tx = self.conn.savepoint()?;
+
+        let updated = tx.execute(
+            "UPDATE conversations SET active_leaf_id = ?2, updated_at = ?3 WHERE id = ?1",
+            params![id, tree.active_leaf_id, now_secs()],
+        )?;
+        if updated == 0 {
+            return Err(get_not_found_error(id));
+        }
+
+        for message in tree.messages.iter() {
+            let changed = tx.execute(
+                "INSERT INTO messages
+                    (id, conversation_id, parent_id, role, content, images, created_at, provider, model, usage, cost)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+                 ON CONFLICT(id) DO UPDATE SET
+                    content = excluded.content,
+                    provider = excluded.provider,
+                    model = excluded.model,
+                    usage = excluded.usage,
+                    cost = excluded.cost
+                 WHERE messages.conversation_id = excluded.conversation_id
+                    AND (messages.content IS NOT excluded.content
+                        OR messages.provider IS NOT excluded.provider
+                        OR messages.model IS NOT excluded.model
+                        OR messages.usage IS NOT excluded.usage
+                        OR messages.cost IS NOT excluded.cost)",
+                params![
+                    message.id,
+                    id,
+                    message.parent_id,
+                    get_enum_str(&message.role)?,
+                    serde_json::to_string(&message.content)?,
+                    message.images,
+                    message.created_at,
+                    message.provider.as_ref().map(get_enum_str).transpose()?,
+                    message.model,
+                    message.usage.as_ref().map(serde_json::to_string).transpose()?,
+                    message.cost,
+                ],
+            )?;
+            // Message ids come from the frontend, one that's taken by another
+            // conversation must not overwrite it
+            if changed == 0 {
+                let owner: String = tx.query_row(
+                    "SELECT conversation_id FROM messages WHERE id = ?1",
+                    params![message.id],
+                    |row| row.get(0),
+                )?;
+                if owner != id {
+                    return Err(NexaError::Conversation(format!(
+                        "Message id {} is already used by another conversation",
+                        message.id
+                    )));
+                }
+            }
+
+            if let ChatMessageContent::File {
+                path,
+                mime_type,
+                display_name,
+                ..
+            } = &message.content
+            {
+                tx.execute(
+                    "INSERT OR REPLACE INTO attachments (message_id, path, mime_type, display_name)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![message.id, path, mime_type, display_name],
+                )?;
+            }
+        }
+
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    // Runs `write` as a unit, whatever it wrote is rolled back when it fails
+    pub(crate) fn write_all<T>(
+        &mut self,
+        write: impl FnOnce(&mut Self) -> Result<T, NexaError>,
+    ) -> Result<T, NexaError> {
+        self.conn.execute_batch("SAVEPOINT write_all")?;
+        match write(self) {
+            Ok(value) => {
+                self.conn.execute_batch("RELEASE write_all")?;
+                Ok(value)
+            }
+            Err(e) => {
+                self.conn
+                    .execute_batch("ROLLBACK TO write_all; RELEASE write_all")?;
+                Err(e)
+            }
+        }
+    }
+
+    pub fn get_metadata(&self, id: &str) -> Result<Map<String, Value>, NexaError> {
+        let metadata: String = self
+            .conn
+            .query_row(
+                "SELECT metadata FROM conversations WHERE id = ?1",
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()?
+            .ok_or(get_not_found_error(id))?;
+
+        Ok(serde_json::from_str(&metadata)?)
+    }
+
+    pub fn set_metadata(&self, id: &str, metadata: &Map<String, Value>) -> Result<(), NexaError> {
+        let updated = self.conn.execute(
+            "UPDATE conversations SET metadata = ?2 WHERE id = ?1",
+            params![id, serde_json::to_string(metadata)?],
+        )?;
+
+        match updated {
+            0 => Err(get_not_found_error(id)),
+            _ => Ok(()),
+        }
+    }
+
+    // The frontend used to keep a single chat in the "chatHistory" key of the
+    // chat-history.json plugin store. It becomes a regular conversation the
+    // first time the store is opened, and is never imported again after that.
+    pub fn import_chat_history_store(
+        &mut self,
+        path: &Path,
+    ) -> Result<Option<ConversationSummary>, NexaError> {
+        let imported: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT value FROM app_metadata WHERE key = ?1",
+                params![CHAT_HISTORY_IMPORTED_KEY],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if imported.is_some() {
+            return Ok(None);
+        }
+
+        let messages: Vec<ChatMessageWithId> = match fs::read(path) {
+            Ok(bytes) => {
+                let store: Value = serde_json::from_slice(&bytes)?;
+                match store.get("chatHistory") {
+                    Some(chat_history) => serde_json::from_value(chat_history.clone())?,
+                    None => vec![],
+                }
+            }
+            Err(_) => vec![],
+        };
+
+        self.write_all(|store| {
+            let mut summary = None;
+            if !messages.is_empty() {
+                summary = Some(store.import_messages("Imported chat", messages)?);
+            }
+
+            store.conn.execute(
+                "INSERT INTO app_metadata (key, value) VALUES (?1, ?2)",
+                params![CHAT_HISTORY_IMPORTED_KEY, now_secs().to_string()],
+            )?;
+
+            Ok(summary)
+        })
+    }
+
+    // Stores a flat chat as a new conversation with a single branch. Message ids
+    // are kept, so they have to be unique across all conversations.
+    pub fn import_messages(
+        &mut self,
+        title: &str,
+        messages: Vec<ChatMessageWithId>,
+    ) -> Result<ConversationSummary, NexaError> {
+        let conversation = self.create_conversation(title)?;
+
+        let mut tree = ConversationTree::default();
+        let mut parent_id = None;
+        for message in messages {
+            tree.add_message(TreeMessage {
+                id: message.id.clone(),
+                parent_id,
+                role: message.role,
+                content: message.content,
+                images: message.images,
+                created_at: conversation.created_at,
+                provider: None,
+                model: None,
+                usage: None,
+                cost: None,
+            })?;
+            parent_id = Some(message.id);
+        }
+        self.save_tree(&conversation.id, &tree)?;
+
+        Ok(ConversationSummary {
+            message_count: tree.messages.len() as u64,
+            ..conversation
+        })
+    }
+}
+
+// Roles and providers are stored as their serde names
+pub(crate) fn get_enum_str<T: Serialize>(value: &T) -> Result<String, NexaError> {
+    match serde_json::to_value(value)? {
+        Value::String(value) => Ok(value),
+        _ => Err(NexaError::Conversation(String::from(
+            "Expected a unit enum variant",
+        ))),
+    }
+}
+
+pub(crate) fn get_not_found_error(id: &str) -> NexaError {
+    NexaError::Conversation(format!("Can't find conversation {}", id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::base::{Provider, Role, TokenUsage};
+    use serde_json::json;
+
+    fn get_text_message(id: &str, parent_id: Option<&str>, role: Role, text: &str) -> TreeMessage {
+        TreeMessage {
+            id: id.to_string(),
+            parent_id: parent_id.map(|id| id.to_string()),
+            role,
+            content: ChatMessageContent::Text {
+                text: text.to_string(),
+                _meta: None,
+            },
+            images: None,
+            created_at: 0,
+            provider: None,
+            model: None,
+            usage: None,
+            cost: None,
+        }
+    }
+
+    #[test]
+    fn conversation_store_test() {
+        let mut store = ConversationStore::open_in_memory().unwrap();
+
+        let first = store.create_conversation("First").unwrap();
+        let second = store.create_conversation("Second").unwrap();
+        store.rename_conversation(&first.id, "Renamed").unwrap();
+
+        let mut tree = ConversationTree::default();
+        tree.add_message(get_text_message("u1", None, Role::User, "Hi"))
+            .unwrap();
+        tree.add_message(get_text_message("a1", Some("u1"), Role::Assistant, ""))
+            .unwrap();
+        store.save_tree(&first.id, &tree).unwrap();
+
+        // Saving again updates contents and adds the new branch
+        let usage = TokenUsage {
+            input_tokens: 12,
+            output_tokens: 3,
+            ..Default::default()
+        };
+        tree.set_reply(
+            "a1",
+            ChatMessageContent::Text {
+                text: String::from("Hello"),
+                _meta: None,
+            },
+            Provider::Gemini,
+            String::from("gemini-2.5-flash"),
+            Some(usage.clone()),
+            Some(0.0001),
+        )
+        .unwrap();
+        let leaf_id = tree.regenerate("a1").unwrap();
+        store.save_tree(&first.id, &tree).unwrap();
+
+        let conversation = store.load_conversation(&first.id).unwrap();
+        assert_eq!(conversation.summary.title, "Renamed");
+        assert_eq!(conversation.summary.message_count, 3);
+        assert_eq!(conversation.tree.active_leaf_id, Some(leaf_id));
+        assert!(matches!(
+            &conversation.tree.get("a1").unwrap().content,
+            ChatMessageContent::Text { text, .. } if text == "Hello"
+        ));
+        assert_eq!(conversation.tree.get("a1").unwrap().usage, Some(usage));
+        assert_eq!(conversation.tree.get("a1").unwrap().cost, Some(0.0001));
+
+        // The ids are global, another conversation can't take one over
+        let mut other_tree = ConversationTree::default();
+        other_tree
+            .add_message(get_text_message("u1", None, Role::User, "Overwritten"))
+            .unwrap();
+        assert!(store.save_tree(&second.id, &other_tree).is_err());
+        assert!(matches!(
+            &store.load_tree(&first.id).unwrap().get("u1").unwrap().content,
+            ChatMessageContent::Text { text, .. } if text == "Hi"
+        ));
+        assert!(store.load_tree(&second.id).unwrap().messages.is_empty());
+
+        let mut metadata = Map::new();
+        metadata.insert(String::from("pinned"), json!(true));
+        store.set_metadata(&first.id, &metadata).unwrap();
+        assert_eq!(store.get_metadata(&first.id).unwrap(), metadata);
+
+        // Most recently updated first
+        let ids: Vec<String> = store
+            .list_conversations()
+            .unwrap()
+            .into_iter()
+            .map(|summary| summary.id)
+            .collect();
+        assert_eq!(ids.len(), 2);
+        assert!(ids.contains(&second.id));
+
+        // Deleting cascades to the messages
+        store.delete_conversation(&first.id).unwrap();
+        assert!(store.load_conversation(&first.id).is_err());
+        let message_count: u64 = store
+            .conn
+            .query_row("SELECT COUNT(*) FROM messages", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(message_count, 0);
+        assert!(store.delete_conversation(&first.id).is_err());
+    }
+
+    #[test]
+    fn import_chat_history_store_test() {
+        let path =
+            std::env::temp_dir().join(format!("nexa-chat-history-{}.json", std::process::id()));
+        fs::write(
+            &path,
+            serde_json::to_vec(&json!({
+                "chatHistory": [
+                    {"id": "1", "role": "user", "content": {"type": "text", "content": {"text": "Hi"}}, "done": true},
+                    {"id": "2", "role": "assistant", "content": {"type": "text", "content": {"text": "Hello"}}, "done": true}
+                ]
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+
+        let mut store = ConversationStore::open_in_memory().unwrap();
+        let imported = store.import_chat_history_store(&path).unwrap().unwrap();
+        assert_eq!(imported.message_count, 2);
+
+        let tree = store.load_tree(&imported.id).unwrap();
+        assert_eq!(tree.get("2").unwrap().parent_id.as_deref(), Some("1"));
+        assert_eq!(tree.active_leaf_id.as_deref(), Some("2"));
+
+        // Only imported once
+        assert!(store.import_chat_history_store(&path).unwrap().is_none());
+        let _ = fs::remove_file(&path);
+        assert_eq!(store.list_conversations().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn write_all_test() {
+        let mut store = ConversationStore::open_in_memory().unwrap();
+        let get_message = |id: &str| ChatMessageWithId {
+            id: id.to_string(),
+            role: Role::User,
+            content: ChatMessageContent::Text {
+                text: String::from("Hi"),
+                _meta: None,
+            },
+            images: None,
+        };
+
+        // The second chat repeats a message id, the first one goes too
+        let result = store.write_all(|store| {
+            store.import_messages("First", vec![get_message("1")])?;
+            store.import_messages("Second", vec![get_message("2"), get_message("2")])
+        });
+        assert!(result.is_err());
+        assert!(store.list_conversations().unwrap().is_empty());
+
+        store
+            .write_all(|store| store.import_messages("First", vec![get_message("1")]))
+            .unwrap();
+        assert_eq!(store.list_conversations().unwrap().len(), 1);
+    }
+}
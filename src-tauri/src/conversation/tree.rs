@@ -0,0 +1,379 @@
+use crate::error::NexaError;
+use crate::llm::base::{
+    ChatHistory, ChatMessageContent, ChatMessageWithId, Provider, Role, TokenUsage,
+};
+use crate::llm::cache::now_secs;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TreeMessage {
+    pub id: String,
+    pub parent_id: Option<String>,
+    pub role: Role,
+    pub content: ChatMessageContent,
+    pub images: Option<String>,
+    pub created_at: u64,
+    // Which model produced an assistant reply
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<Provider>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    // What generating the reply took, cost is left out for models without a
+    // price
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost: Option<f64>,
+}
+
+// One message of the active branch together with all of its siblings, which
+// is what the UI needs to render the "< 2/3 >" branch switcher
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BranchMessage {
+    pub message: TreeMessage,
+    pub sibling_ids: Vec<String>,
+}
+
+// Every message points at its parent, so regenerating or editing a turn adds a
+// sibling instead of overwriting history. Messages are kept in insertion order,
+// which is also the order siblings are shown in.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationTree {
+    pub messages: Vec<TreeMessage>,
+    pub active_leaf_id: Option<String>,
+}
+
+impl ConversationTree {
+    pub fn get(&self, id: &str) -> Option<&TreeMessage> {
+        self.messages.iter().find(|message| message.id == id)
+    }
+
+    fn get_or_err(&self, id: &str) -> Result<&TreeMessage, NexaError> {
+        self.get(id).ok_or(NexaError::Conversation(format!(
+            "Can't find message {} in the conversation",
+            id
+        )))
+    }
+
+    pub fn get_children(&self, parent_id: Option<&str>) -> Vec<&TreeMessage> {
+        self.messages
+            .iter()
+            .filter(|message| message.parent_id.as_deref() == parent_id)
+            .collect()
+    }
+
+    pub fn add_message(&mut self, message: TreeMessage) -> Result<(), NexaError> {
+        if self.get(&message.id).is_some() {
+            return Err(NexaError::Conversation(format!(
+                "Message {} already exists",
+                message.id
+            )));
+        }
+        if let Some(parent_id) = &message.parent_id {
+            self.get_or_err(parent_id)?;
+        }
+
+        self.active_leaf_id = Some(message.id.clone());
+        self.messages.push(message);
+
+        Ok(())
+    }
+
+    pub fn set_content(&mut self, id: &str, content: ChatMessageContent) -> Result<(), NexaError> {
+        let message = self
+            .messages
+            .iter_mut()
+            .find(|message| message.id == id)
+            .ok_or(NexaError::Conversation(format!(
+                "Can't find message {} in the conversation",
+                id
+            )))?;
+        message.content = content;
+
+        Ok(())
+    }
+
+    pub fn set_reply(
+        &mut self,
+        id: &str,
+        content: ChatMessageContent,
+        provider: Provider,
+        model: String,
+        usage: Option<TokenUsage>,
+        cost: Option<f64>,
+    ) -> Result<(), NexaError> {
+        self.set_content(id, content)?;
+        self.set_reply_usage(id, provider, model, usage, cost)
+    }
+
+    // For a reply without text of its own, a function call for instance, which
+    // still has to count towards what the conversation cost
+    pub fn set_reply_usage(
+        &mut self,
+        id: &str,
+        provider: Provider,
+        model: String,
+        usage: Option<TokenUsage>,
+        cost: Option<f64>,
+    ) -> Result<(), NexaError> {
+        let message = self
+            .messages
+            .iter_mut()
+            .find(|message| message.id == id)
+            .ok_or(NexaError::Conversation(format!(
+                "Can't find message {} in the conversation",
+                id
+            )))?;
+        message.provider = Some(provider);
+        message.model = Some(model);
+        message.usage = usage;
+        message.cost = cost;
+
+        Ok(())
+    }
+
+    // Root first, leaf last
+    pub fn get_path(&self, leaf_id: &str) -> Result<Vec<&TreeMessage>, NexaError> {
+        let mut path = vec![self.get_or_err(leaf_id)?];
+
+        while let Some(parent_id) = &path.last().unwrap().parent_id {
+            if path.len() > self.messages.len() {
+                return Err(NexaError::Conversation(String::from(
+                    "Conversation tree contains a cycle",
+                )));
+            }
+            path.push(self.get_or_err(parent_id)?);
+        }
+        path.reverse();
+
+        Ok(path)
+    }
+
+    pub fn get_history(&self, leaf_id: &str) -> Result<ChatHistory, NexaError> {
+        let messages = self
+            .get_path(leaf_id)?
+            .into_iter()
+            .map(|message| ChatMessageWithId {
+                id: message.id.clone(),
+                role: message.role.clone(),
+                content: message.content.clone(),
+                images: message.images.clone(),
+            })
+            .collect();
+
+        Ok(ChatHistory { messages })
+    }
+
+    pub fn get_branch(&self, leaf_id: &str) -> Result<Vec<BranchMessage>, NexaError> {
+        Ok(self
+            .get_path(leaf_id)?
+            .into_iter()
+            .map(|message| BranchMessage {
+                message: message.clone(),
+                sibling_ids: self
+                    .get_children(message.parent_id.as_deref())
+                    .into_iter()
+                    .map(|sibling| sibling.id.clone())
+                    .collect(),
+            })
+            .collect())
+    }
+
+    // Follows the newest child down to a leaf
+    pub fn get_leaf(&self, from_id: &str) -> Result<String, NexaError> {
+        let mut leaf = self.get_or_err(from_id)?;
+        while let Some(child) = self.get_children(Some(&leaf.id)).last() {
+            leaf = child;
+        }
+
+        Ok(leaf.id.clone())
+    }
+
+    pub fn switch_branch(&mut self, message_id: &str) -> Result<String, NexaError> {
+        let leaf_id = self.get_leaf(message_id)?;
+        self.active_leaf_id = Some(leaf_id.clone());
+
+        Ok(leaf_id)
+    }
+
+    // Adds an empty assistant sibling next to an existing reply. The returned id
+    // is the leaf to stream the new reply into.
+    pub fn regenerate(&mut self, message_id: &str) -> Result<String, NexaError> {
+        let message = self.get_or_err(message_id)?;
+        if message.role != Role::Assistant {
+            return Err(NexaError::Conversation(String::from(
+                "Only assistant messages can be regenerated",
+            )));
+        }
+
+        let placeholder = get_assistant_placeholder(message.parent_id.clone());
+        let placeholder_id = placeholder.id.clone();
+        self.add_message(placeholder)?;
+
+        Ok(placeholder_id)
+    }
+
+    // Adds the edited user turn as a sibling of the original one, followed by an
+    // empty assistant reply. The returned id is the leaf to stream into.
+    pub fn edit(
+        &mut self,
+        message_id: &str,
+        content: ChatMessageContent,
+    ) -> Result<String, NexaError> {
+        let message = self.get_or_err(message_id)?;
+        if message.role != Role::User {
+            return Err(NexaError::Conversation(String::from(
+                "Only user messages can be edited",
+            )));
+        }
+
+        let edited = TreeMessage {
+            id: Uuid::new_v4().to_string(),
+            parent_id: message.parent_id.clone(),
+            role: Role::User,
+            content,
+            images: message.images.clone(),
+            created_at: now_secs(),
+            provider: None,
+            model: None,
+            usage: None,
+            cost: None,
+        };
+        let placeholder = get_assistant_placeholder(Some(edited.id.clone()));
+        let placeholder_id = placeholder.id.clone();
+
+        self.add_message(edited)?;
+        self.add_message(placeholder)?;
+
+        Ok(placeholder_id)
+    }
+}
+
+fn get_assistant_placeholder(parent_id: Option<String>) -> TreeMessage {
+    TreeMessage {
+        id: Uuid::new_v4().to_string(),
+        parent_id,
+        role: Role::Assistant,
+        content: ChatMessageContent::Text {
+            text: String::new(),
+            _meta: None,
+        },
+        images: None,
+        created_at: now_secs(),
+        provider: None,
+        model: None,
+        usage: None,
+        cost: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_text_message(id: &str, parent_id: Option<&str>, role: Role, text: &str) -> TreeMessage {
+        TreeMessage {
+            id: id.to_string(),
+            parent_id: parent_id.map(|id| id.to_string()),
+            role,
+            content: ChatMessageContent::Text {
+                text: text.to_string(),
+                _meta: None,
+            },
+            images: None,
+            created_at: 0,
+            provider: None,
+            model: None,
+            usage: None,
+            cost: None,
+        }
+    }
+
+    fn get_text(message: &ChatMessageWithId) -> &str {
+        match &message.content {
+            ChatMessageContent::Text { text, .. } => text,
+            _ => "",
+        }
+    }
+
+    fn get_tree() -> ConversationTree {
+        let mut tree = ConversationTree::default();
+        tree.add_message(get_text_message("u1", None, Role::User, "Hi"))
+            .unwrap();
+        tree.add_message(get_text_message("a1", Some("u1"), Role::Assistant, "Hello"))
+            .unwrap();
+        tree.add_message(get_text_message(
+            "u2",
+            Some("a1"),
+            Role::User,
+            "Tell a joke",
+        ))
+        .unwrap();
+        tree.add_message(get_text_message("a2", Some("u2"), Role::Assistant, "No"))
+            .unwrap();
+        tree
+    }
+
+    #[test]
+    fn regenerate_and_switch_branch_test() {
+        let mut tree = get_tree();
+
+        let leaf_id = tree.regenerate("a2").unwrap();
+        assert_eq!(tree.active_leaf_id.as_deref(), Some(leaf_id.as_str()));
+
+        let history = tree.get_history(&leaf_id).unwrap();
+        let ids: Vec<&str> = history.messages.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, vec!["u1", "a1", "u2", leaf_id.as_str()]);
+        assert_eq!(get_text(history.messages.last().unwrap()), "");
+
+        let branch = tree.get_branch(&leaf_id).unwrap();
+        assert_eq!(
+            branch[3].sibling_ids,
+            vec![String::from("a2"), leaf_id.clone()]
+        );
+        assert_eq!(branch[0].sibling_ids, vec![String::from("u1")]);
+
+        // Switching back lands on the old reply
+        assert_eq!(tree.switch_branch("a2").unwrap(), "a2");
+        assert_eq!(tree.active_leaf_id.as_deref(), Some("a2"));
+
+        // User messages can't be regenerated
+        assert!(tree.regenerate("u2").is_err());
+    }
+
+    #[test]
+    fn edit_message_test() {
+        let mut tree = get_tree();
+
+        let leaf_id = tree
+            .edit(
+                "u2",
+                ChatMessageContent::Text {
+                    text: String::from("Tell a short joke"),
+                    _meta: None,
+                },
+            )
+            .unwrap();
+
+        let history = tree.get_history(&leaf_id).unwrap();
+        assert_eq!(history.messages.len(), 4);
+        assert_eq!(history.messages[1].id, "a1");
+        assert_ne!(history.messages[2].id, "u2");
+        assert_eq!(get_text(&history.messages[2]), "Tell a short joke");
+
+        // Switching to the original turn follows it down to its old reply
+        assert_eq!(tree.switch_branch("u2").unwrap(), "a2");
+        assert_eq!(tree.get_leaf("u1").unwrap(), leaf_id);
+
+        assert!(tree
+            .edit("a1", history.messages[2].content.clone())
+            .is_err());
+        assert!(tree
+            .add_message(get_text_message("x", Some("missing"), Role::User, "?"))
+            .is_err());
+    }
+}
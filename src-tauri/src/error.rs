@@ -9,13 +9,31 @@ pub enum NexaError {
     Reqwest(#[from] tauri_plugin_http::reqwest::Error),
     #[error("Serde Json Error: {0}")]
     SerdeJson(#[from] serde_json::Error),
+    #[error("SQLite Error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("Transport Error: {0}")]
+    Transport(String),
 
     #[error("Gemini Error: {0}")]
     Gemini(String),
+    #[error("Ollama Error: {0}")]
+    Ollama(String),
+    #[error("Mock Error: {0}")]
+    Mock(String),
     #[error("MCP Connection Error: {0}")]
     MCPConnection(String),
     #[error("MCP Tool Call Error: {0}")]
     MCPToolCall(String),
+    #[error("Conversation Error: {0}")]
+    Conversation(String),
+    #[error("Prompt Template Error: {0}")]
+    Prompt(String),
+    #[error("Secret Store Error: {0}")]
+    Secret(String),
+    #[error("Rate Limit Error: {0}")]
+    RateLimit(String),
+    #[error("Spend Limit Error: {0}")]
+    SpendLimit(String),
     #[error("Command Error: {0}")]
     Command(String),
 }
@@ -1,39 +1,268 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 mod api;
+mod conversation;
 mod error;
 mod llm;
 mod mcp;
-use std::{collections::HashMap, sync::Arc};
+mod prompt;
+mod secrets;
+mod tools;
+use std::{collections::HashMap, env, path::PathBuf, sync::Arc};
 
-use llm::commands::{get_all_ollama_chat_models, stream_chat};
+use api::commands::{get_http_client_config, set_http_client_config};
+use api::http_client::{HttpClientSettings, HTTP_CLIENT_CONFIG_FILENAME};
+use api::transport::{HttpTransport, RecordingTransport, ReplayTransport};
+use conversation::commands::{
+    add_chat_message, create_conversation, delete_conversation, edit_message, export_conversation,
+    get_active_branch, get_conversation_cost, get_conversation_metadata, get_conversation_settings,
+    import_chat_history_store, import_conversations, list_conversations, load_conversation,
+    regenerate_message, rename_conversation, search_conversations, set_conversation_metadata,
+    set_conversation_settings, switch_branch,
+};
+use conversation::store::{
+    ConversationStore, CHAT_HISTORY_STORE_FILENAME, CONVERSATION_DB_FILENAME,
+};
+use llm::cache::{ResponseCache, RESPONSE_CACHE_DIRNAME};
+use llm::commands::{
+    clear_response_cache, compare_chat, get_all_ollama_chat_models, get_gemini_file_info,
+    get_gemini_files, get_ollama_running_models, get_pricing, get_quota_status, get_rate_limits,
+    get_response_cache_config, preload_ollama_model, remove_gemini_file, select_compare_winner,
+    set_pricing, set_rate_limits, set_response_cache_config, stream_chat, unload_ollama_model,
+    upload_gemini_file,
+};
+use llm::compare::CompareResult;
+use llm::gemini_files::{GeminiFileRegistry, GEMINI_FILES_FILENAME};
+use llm::limits::{RateLimiter, RATE_LIMITS_FILENAME};
+use llm::pricing::{PricingTable, PRICING_FILENAME};
 use mcp::client::MCPClient;
-use mcp::commands::{call_tool, initialize_mcp_client};
-use tauri::Manager;
-use tauri_plugin_secure_storage;
-use tokio::sync::RwLock;
+use mcp::commands::{call_tool, get_mcp_config_path, get_mcp_server_statuses, reload_mcp_servers};
+use mcp::config::MCP_CONFIG_FILENAME;
+use mcp::manager::{start_mcp_servers, MCPServerStatus};
+use prompt::commands::{
+    delete_prompt_template, get_prompt_template_dir, list_prompt_templates, render_prompt,
+    save_prompt_template,
+};
+use prompt::library::{PromptLibrary, PROMPTS_DIRNAME};
+use secrets::commands::{
+    add_credential_profile, delete_secret, get_secret_status, list_credential_profiles,
+    remove_credential_profile, set_secret, test_secret,
+};
+use secrets::profiles::{CredentialProfiles, CREDENTIAL_PROFILES_FILENAME};
+use secrets::store::{
+    EncryptedFileSecretStore, EnvSecretStore, KeyringSecretStore, SecretStore, SecretStores,
+    SECRETS_FILENAME,
+};
+use tauri::{Emitter, Manager};
+use tokio::sync::{Mutex, RwLock};
+use tools::commands::{get_native_tools_config, set_native_tools_config};
+use tools::native::{NativeTools, NATIVE_TOOLS_FILENAME};
 
 struct AppData {
     mcp_clients: RwLock<HashMap<String, Arc<MCPClient>>>,
+    mcp_config_path: PathBuf,
+    mcp_server_statuses: RwLock<Vec<MCPServerStatus>>,
+    // Held for the whole of a reload
+    mcp_reload: Mutex<()>,
+    native_tools: Mutex<NativeTools>,
+    compare_run: RwLock<Option<(String, Vec<CompareResult>)>>,
+    conversation_store: Mutex<ConversationStore>,
+    chat_history_store_path: PathBuf,
+    response_cache: Mutex<ResponseCache>,
+    rate_limiter: Arc<Mutex<RateLimiter>>,
+    pricing: Mutex<PricingTable>,
+    gemini_files: Arc<Mutex<GeminiFileRegistry>>,
+    http_transport: Arc<dyn HttpTransport>,
+    mcp_http_transport: Arc<dyn HttpTransport>,
+    http_client: Mutex<HttpClientSettings>,
+    prompt_library: PromptLibrary,
+    secret_store: SecretStores,
+    credential_profiles: Mutex<CredentialProfiles>,
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .setup(|app| {
+            let response_cache_path = app
+                .path()
+                .app_cache_dir()
+                .ok()
+                .map(|dir| dir.join(RESPONSE_CACHE_DIRNAME));
+            let gemini_files_path = app
+                .path()
+                .app_data_dir()
+                .ok()
+                .map(|dir| dir.join(GEMINI_FILES_FILENAME));
+
+            let app_data_dir = app.path().app_data_dir()?;
+            let conversation_store =
+                ConversationStore::open(&app_data_dir.join(CONVERSATION_DB_FILENAME))?;
+
+            // Lets a team point Nexa at a shared, version controlled folder
+            let prompts_dir = env::var("NEXA_PROMPTS_DIR")
+                .map(PathBuf::from)
+                .unwrap_or(app_data_dir.join(PROMPTS_DIRNAME));
+
+            let product_name = app
+                .config()
+                .product_name
+                .clone()
+                .unwrap_or(app.config().identifier.clone());
+            let mut secret_stores: Vec<Box<dyn SecretStore>> = vec![
+                Box::new(EnvSecretStore),
+                Box::new(KeyringSecretStore::new(product_name)),
+            ];
+            // Headless Linux usually has no Secret Service to back the keyring
+            if let Ok(passphrase) = env::var("NEXA_SECRETS_PASSPHRASE") {
+                secret_stores.push(Box::new(EncryptedFileSecretStore::new(
+                    app_data_dir.join(SECRETS_FILENAME),
+                    passphrase,
+                )));
+            }
+
+            let secret_store = SecretStores::new(secret_stores);
+
+            // One client shared by every provider, so proxy, CA and timeout
+            // settings apply everywhere
+            let (http_client, http_client_error) = HttpClientSettings::load(
+                Some(app_data_dir.join(HTTP_CLIENT_CONFIG_FILENAME)),
+                &secret_store,
+            );
+            if let Some(e) = http_client_error {
+                let _ = app.emit("http_client_error", e.to_string());
+            }
+            // A broken pricing file is left alone for the user to fix, the
+            // prices just stay unknown until then
+            let pricing = PricingTable::load(Some(app_data_dir.join(PRICING_FILENAME)))
+                .unwrap_or_else(|e| {
+                    let _ = app.emit("pricing_error", e.to_string());
+                    PricingTable::default()
+                });
+
+            // Without its file the file tools stay off until the user fixes it
+            let native_tools = NativeTools::load(Some(app_data_dir.join(NATIVE_TOOLS_FILENAME)))
+                .unwrap_or_else(|e| {
+                    let _ = app.emit("native_tools_error", e.to_string());
+                    NativeTools::default()
+                });
+
+            let mut http_transport: Arc<dyn HttpTransport> = http_client.get_transport();
+            let mut mcp_http_transport: Arc<dyn HttpTransport> = http_client.get_stream_transport();
+
+            // Point these at a cassette file to capture provider traffic as a
+            // test fixture, or to run a recorded session again without network.
+            // MCP streams don't end, so they stay out of a recording.
+            if let Ok(record_path) = env::var("NEXA_HTTP_RECORD_PATH") {
+                http_transport = Arc::new(RecordingTransport::new(
+                    http_transport,
+                    PathBuf::from(record_path),
+                ));
+            } else if let Ok(replay_path) = env::var("NEXA_HTTP_REPLAY_PATH") {
+                http_transport = Arc::new(ReplayTransport::load(&PathBuf::from(replay_path))?);
+                mcp_http_transport = http_transport.clone();
+            }
+
             app.manage(AppData {
                 mcp_clients: RwLock::new(HashMap::new()),
+                mcp_config_path: app.path().app_config_dir()?.join(MCP_CONFIG_FILENAME),
+                mcp_server_statuses: RwLock::new(vec![]),
+                mcp_reload: Mutex::new(()),
+                native_tools: Mutex::new(native_tools),
+                compare_run: RwLock::new(None),
+                conversation_store: Mutex::new(conversation_store),
+                chat_history_store_path: app_data_dir.join(CHAT_HISTORY_STORE_FILENAME),
+                response_cache: Mutex::new(ResponseCache::load(response_cache_path)),
+                rate_limiter: Arc::new(Mutex::new(RateLimiter::load(Some(
+                    app_data_dir.join(RATE_LIMITS_FILENAME),
+                )))),
+                pricing: Mutex::new(pricing),
+                gemini_files: Arc::new(Mutex::new(GeminiFileRegistry::load(gemini_files_path))),
+                http_transport,
+                mcp_http_transport,
+                http_client: Mutex::new(http_client),
+                prompt_library: PromptLibrary::new(prompts_dir),
+                secret_store,
+                credential_profiles: Mutex::new(CredentialProfiles::load(Some(
+                    app_data_dir.join(CREDENTIAL_PROFILES_FILENAME),
+                ))),
+            });
+
+            // Servers can take a while to come up, the window doesn't wait
+            // for them
+            let app_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                match start_mcp_servers(&app_handle).await {
+                    Ok(statuses) => {
+                        let _ = app_handle.emit("mcp_servers_started", statuses);
+                    }
+                    Err(e) => {
+                        let _ = app_handle.emit("mcp_servers_error", e.to_string());
+                    }
+                }
             });
 
             Ok(())
         })
-        .plugin(tauri_plugin_secure_storage::init())
         .plugin(tauri_plugin_store::Builder::new().build())
         .plugin(tauri_plugin_opener::init())
-        .plugin(tauri_plugin_secure_storage::init())
         .invoke_handler(tauri::generate_handler![
             get_all_ollama_chat_models,
+            get_ollama_running_models,
+            preload_ollama_model,
+            unload_ollama_model,
             stream_chat,
-            initialize_mcp_client,
+            compare_chat,
+            select_compare_winner,
+            list_conversations,
+            create_conversation,
+            rename_conversation,
+            delete_conversation,
+            load_conversation,
+            export_conversation,
+            import_conversations,
+            import_chat_history_store,
+            search_conversations,
+            get_conversation_metadata,
+            set_conversation_metadata,
+            get_conversation_settings,
+            set_conversation_settings,
+            get_conversation_cost,
+            add_chat_message,
+            regenerate_message,
+            edit_message,
+            switch_branch,
+            get_active_branch,
+            get_response_cache_config,
+            set_response_cache_config,
+            clear_response_cache,
+            get_rate_limits,
+            set_rate_limits,
+            get_quota_status,
+            get_pricing,
+            set_pricing,
+            upload_gemini_file,
+            get_gemini_file_info,
+            get_gemini_files,
+            remove_gemini_file,
+            list_prompt_templates,
+            get_prompt_template_dir,
+            save_prompt_template,
+            delete_prompt_template,
+            render_prompt,
+            set_secret,
+            delete_secret,
+            get_secret_status,
+            test_secret,
+            list_credential_profiles,
+            add_credential_profile,
+            remove_credential_profile,
+            get_http_client_config,
+            set_http_client_config,
+            reload_mcp_servers,
+            get_mcp_server_statuses,
+            get_mcp_config_path,
+            get_native_tools_config,
+            set_native_tools_config,
             call_tool,
         ])
         .run(tauri::generate_context!())
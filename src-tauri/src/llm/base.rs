@@ -3,11 +3,43 @@ use futures_util::stream::Stream;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum Provider {
     Ollama,
     Gemini,
+    Mock,
+}
+
+// Sampling parameters understood by every provider. Anything left unset is up
+// to the provider default. Field names follow Gemini's generationConfig.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerationConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_sequences: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolCallingMode {
+    // The model decides whether to call a tool
+    Auto,
+    // The model has to call one of the enabled tools
+    Any,
+    // The model can't call any tool. They are still declared, earlier turns
+    // of the conversation may have called them.
+    None,
 }
 
 pub trait LLM {
@@ -17,7 +49,7 @@ pub trait LLM {
     ) -> Result<impl Stream<Item = Result<EmittedChatMessage, NexaError>>, NexaError>;
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Clone, Debug)]
 pub struct ChatHistory {
     pub messages: Vec<ChatMessageWithId>,
 }
@@ -31,7 +63,7 @@ pub enum Role {
     Function,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ChatMessage {
     pub role: Role,
     pub content: ChatMessageContent,
@@ -67,6 +99,17 @@ pub enum ChatMessageContent {
         #[serde(rename = "_meta")]
         _meta: Option<Value>,
     },
+    // A local file (video, audio, PDF, ...) attached to the message
+    #[serde(rename_all = "camelCase")]
+    File {
+        path: String,
+        mime_type: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        display_name: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[serde(rename = "_meta")]
+        _meta: Option<Value>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -87,9 +130,99 @@ impl ChatMessageWithId {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+// Folds streamed chunks into whole messages: consecutive text from the same role
+// is concatenated, everything else is kept as its own message.
+pub fn merge_chat_messages(merged: &mut Vec<ChatMessage>, chunk: Vec<ChatMessage>) {
+    for message in chunk {
+        if let (
+            Some(ChatMessage {
+                role,
+                content: ChatMessageContent::Text { text, _meta },
+                ..
+            }),
+            ChatMessageContent::Text {
+                text: new_text,
+                _meta: new_meta,
+            },
+        ) = (merged.last_mut(), &message.content)
+        {
+            if *role == message.role {
+                text.push_str(new_text);
+                if let Some(new_meta) = new_meta {
+                    merge_meta(_meta.get_or_insert(Value::Null), new_meta);
+                }
+                continue;
+            }
+        }
+
+        merged.push(message);
+    }
+}
+
+// Providers may send part of the meta on a later chunk, Gemini's thought
+// signature for one, so objects are merged and later values win
+fn merge_meta(meta: &mut Value, new_meta: &Value) {
+    match (meta, new_meta) {
+        (Value::Object(meta), Value::Object(new_meta)) => {
+            for (key, new_value) in new_meta {
+                match meta.get_mut(key) {
+                    Some(value) => merge_meta(value, new_value),
+                    None => {
+                        meta.insert(key.clone(), new_value.clone());
+                    }
+                }
+            }
+        }
+        (_, Value::Null) => {}
+        (meta, new_meta) => *meta = new_meta.clone(),
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct EmittedChatMessage {
     pub id: String,
     pub message: Vec<ChatMessage>,
     pub done: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocked: Option<BlockedOutcome>,
+    // Why generation stopped, as the provider reports it ("STOP", "MAX_TOKENS",
+    // "stop", "length", ...). Only set on the final chunk.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<String>,
+    // USD, on the chunk carrying the usage when the model has a price
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost: Option<f64>,
+    // Why the reply broke off. Only set on a closing chunk of its own, nothing
+    // follows it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// Gemini's MAX_TOKENS and Ollama's length mean the reply was cut off by the
+// output token limit
+pub fn is_max_tokens_finish(finish_reason: &str) -> bool {
+    matches!(finish_reason, "MAX_TOKENS" | "length")
+}
+
+// Set when the provider refused the prompt or cut the reply short for policy
+// reasons. `message` is meant to be shown to the user as is.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockedOutcome {
+    pub reason: String,
+    pub message: String,
+    pub categories: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    #[serde(default)]
+    pub cached_tokens: u64,
+    #[serde(default)]
+    pub thinking_tokens: u64,
 }
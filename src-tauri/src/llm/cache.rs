@@ -0,0 +1,598 @@
+use crate::error::NexaError;
+use crate::llm::base::{
+    ChatHistory, ChatMessage, ChatMessageContent, EmittedChatMessage, Provider, LLM,
+};
+use futures::future::Either;
+use futures::stream::{self, StreamExt};
+use futures_util::Stream;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+pub(crate) static RESPONSE_CACHE_DIRNAME: &str = "response-cache";
+static CACHE_CONFIG_FILENAME: &str = "config.json";
+static CACHE_ENTRIES_DIRNAME: &str = "entries";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ResponseCacheConfig {
+    pub enabled: bool,
+    pub ttl_secs: u64,
+    pub max_entries: usize,
+    pub max_bytes: usize,
+}
+
+impl Default for ResponseCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl_secs: 60 * 60 * 24 * 7,
+            max_entries: 500,
+            max_bytes: 50 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+struct CacheEntry {
+    created_at: u64,
+    last_used_at: u64,
+    size: usize,
+    chunks: Vec<EmittedChatMessage>,
+}
+
+// Every entry is a file of its own, named after its key, so storing a reply
+// writes just that reply and never the whole cache
+pub(crate) struct ResponseCache {
+    config: ResponseCacheConfig,
+    entries: HashMap<String, CacheEntry>,
+    dir: Option<PathBuf>,
+}
+
+impl ResponseCache {
+    pub fn load(dir: Option<PathBuf>) -> Self {
+        let config = dir
+            .as_ref()
+            .and_then(|dir| fs::read(dir.join(CACHE_CONFIG_FILENAME)).ok())
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+
+        let mut entries = HashMap::new();
+        let entry_files = dir
+            .as_ref()
+            .and_then(|dir| fs::read_dir(dir.join(CACHE_ENTRIES_DIRNAME)).ok());
+        for file in entry_files.into_iter().flatten().flatten() {
+            let path = file.path();
+            let key = path.file_stem().and_then(|stem| stem.to_str());
+            let entry = fs::read(&path)
+                .ok()
+                .and_then(|bytes| serde_json::from_slice::<CacheEntry>(&bytes).ok());
+            if let (Some(key), Some(entry)) = (key, entry) {
+                entries.insert(key.to_string(), entry);
+            }
+        }
+
+        let mut cache = ResponseCache {
+            config,
+            entries,
+            dir,
+        };
+        cache.evict(now_secs());
+
+        cache
+    }
+
+    pub fn get_config(&self) -> ResponseCacheConfig {
+        self.config.clone()
+    }
+
+    pub fn set_config(&mut self, config: ResponseCacheConfig) -> Result<(), NexaError> {
+        self.config = config;
+        self.evict(now_secs());
+        self.save_config()
+    }
+
+    pub fn clear(&mut self) -> Result<(), NexaError> {
+        self.entries.clear();
+
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+        match fs::remove_dir_all(dir.join(CACHE_ENTRIES_DIRNAME)) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn get(&mut self, key: &str) -> Result<Option<Vec<EmittedChatMessage>>, NexaError> {
+        if !self.config.enabled {
+            return Ok(None);
+        }
+
+        let now = now_secs();
+        let ttl_secs = self.config.ttl_secs;
+        let Some(entry) = self.entries.get_mut(key) else {
+            return Ok(None);
+        };
+
+        if now.saturating_sub(entry.created_at) > ttl_secs {
+            self.remove(key);
+            return Ok(None);
+        }
+
+        // Written back, so the eviction order holds across restarts
+        entry.last_used_at = now;
+        let chunks = entry.chunks.clone();
+        self.save_entry(key, &self.entries[key])?;
+
+        Ok(Some(chunks))
+    }
+
+    // The entry is kept in memory even when writing it out fails
+    pub fn insert(
+        &mut self,
+        key: String,
+        chunks: Vec<EmittedChatMessage>,
+    ) -> Result<(), NexaError> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+
+        let now = now_secs();
+        let size = serde_json::to_vec(&chunks).map(|v| v.len()).unwrap_or(0);
+        let entry = CacheEntry {
+            created_at: now,
+            last_used_at: now,
+            size,
+            chunks,
+        };
+        let saved = self.save_entry(&key, &entry);
+        self.entries.insert(key, entry);
+        self.evict(now);
+
+        saved
+    }
+
+    // Drops expired entries first, then the least recently used ones until
+    // the cache fits into both limits again
+    fn evict(&mut self, now: u64) {
+        let ttl_secs = self.config.ttl_secs;
+        let expired_keys: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| now.saturating_sub(entry.created_at) > ttl_secs)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in expired_keys {
+            self.remove(&key);
+        }
+
+        let mut total_bytes: usize = self.entries.values().map(|entry| entry.size).sum();
+        while self.entries.len() > self.config.max_entries || total_bytes > self.config.max_bytes {
+            let oldest_key = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used_at)
+                .map(|(key, _)| key.clone());
+
+            match oldest_key.and_then(|key| self.remove(&key)) {
+                Some(entry) => total_bytes -= entry.size,
+                None => break,
+            }
+        }
+    }
+
+    fn get_entry_path(&self, key: &str) -> Option<PathBuf> {
+        self.dir.as_ref().map(|dir| {
+            dir.join(CACHE_ENTRIES_DIRNAME)
+                .join(format!("{}.json", key))
+        })
+    }
+
+    fn remove(&mut self, key: &str) -> Option<CacheEntry> {
+        if let Some(path) = self.get_entry_path(key) {
+            let _ = fs::remove_file(path);
+        }
+
+        self.entries.remove(key)
+    }
+
+    fn save_entry(&self, key: &str, entry: &CacheEntry) -> Result<(), NexaError> {
+        let Some(path) = self.get_entry_path(key) else {
+            return Ok(());
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_vec(entry)?)?;
+
+        Ok(())
+    }
+
+    fn save_config(&self) -> Result<(), NexaError> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+
+        fs::create_dir_all(dir)?;
+        fs::write(
+            dir.join(CACHE_CONFIG_FILENAME),
+            serde_json::to_vec_pretty(&self.config)?,
+        )?;
+
+        Ok(())
+    }
+}
+
+pub(crate) struct CachedLLM<'a, L: LLM> {
+    pub(crate) inner: L,
+    pub(crate) cache: &'a Mutex<ResponseCache>,
+    pub(crate) provider: Provider,
+    pub(crate) model: String,
+    pub(crate) tools: Value,
+    pub(crate) generation_config: Value,
+}
+
+impl<'a, L: LLM> LLM for CachedLLM<'a, L> {
+    async fn stream_chat(
+        &self,
+        history: ChatHistory,
+    ) -> Result<impl Stream<Item = Result<EmittedChatMessage, NexaError>>, NexaError> {
+        let id = history
+            .messages
+            .last()
+            .map(|message| message.id.clone())
+            .unwrap_or_default();
+        let key = get_cache_key(
+            &self.provider,
+            &self.model,
+            &history,
+            &self.tools,
+            &self.generation_config,
+        )?;
+
+        let cached_chunks = self.cache.lock().await.get(&key)?;
+        if let Some(chunks) = cached_chunks {
+            // Replay under the id of the message that is waiting for this
+            // response. Nothing was billed for it this time.
+            let replay = chunks.into_iter().map(move |mut chunk| {
+                chunk.id = id.clone();
+                if chunk.usage.is_some() {
+                    chunk.cost = Some(0.0);
+                }
+                Ok(chunk)
+            });
+            return Ok(Either::Left(stream::iter(replay)));
+        }
+
+        let inner_stream = Box::pin(self.inner.stream_chat(history).await?);
+        let cache = self.cache;
+
+        Ok(Either::Right(stream::unfold(
+            (inner_stream, Vec::new(), Some(key), None),
+            move |(mut inner_stream, mut chunks, mut key, mut save_error)| async move {
+                // The reply itself went out, the failed save comes after it
+                if let Some(e) = save_error.take() {
+                    return Some((Err(e), (inner_stream, chunks, key, None)));
+                }
+                let item = inner_stream.next().await?;
+
+                match &item {
+                    // Blocked replies carry no content worth replaying
+                    Ok(message) if message.blocked.is_some() => key = None,
+                    Ok(message) => {
+                        chunks.push(message.clone());
+                        if message.done {
+                            if let Some(key) = key.take() {
+                                save_error = cache
+                                    .lock()
+                                    .await
+                                    .insert(key, std::mem::take(&mut chunks))
+                                    .err();
+                            }
+                        }
+                    }
+                    // Never cache a response that failed halfway
+                    Err(_) => key = None,
+                }
+
+                Some((item, (inner_stream, chunks, key, save_error)))
+            },
+        )))
+    }
+}
+
+pub(crate) fn get_cache_key(
+    provider: &Provider,
+    model: &str,
+    history: &ChatHistory,
+    tools: &Value,
+    generation_config: &Value,
+) -> Result<String, NexaError> {
+    // Message ids are random per turn, so only the content takes part in the key
+    let normalized_history: Vec<ChatMessage> = history
+        .messages
+        .iter()
+        .map(|message| message.strip_id())
+        .collect();
+    // Attachments are sent by path, the file behind it may have been edited
+    // since the reply was cached
+    let files: Vec<Value> = history
+        .messages
+        .iter()
+        .filter_map(|message| match &message.content {
+            ChatMessageContent::File { path, .. } => {
+                Some(json!({ "path": path, "stamp": get_file_stamp(path).ok() }))
+            }
+            _ => None,
+        })
+        .collect();
+
+    let key_source = json!({
+        "provider": provider,
+        "model": model,
+        "history": normalized_history,
+        "files": files,
+        "tools": tools,
+        "generationConfig": generation_config,
+    });
+    let canonical = serde_json::to_vec(&canonicalize_json(&key_source))?;
+
+    Ok(format!("{:x}", Sha256::digest(canonical)))
+}
+
+fn canonicalize_json(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+
+            let mut sorted = Map::new();
+            for key in keys {
+                sorted.insert(key.clone(), canonicalize_json(&map[key]));
+            }
+            Value::Object(sorted)
+        }
+        Value::Array(values) => Value::Array(values.iter().map(canonicalize_json).collect()),
+        _ => value.clone(),
+    }
+}
+
+// Size and modification time of a local file, enough to tell it changed
+// without reading it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FileStamp {
+    pub size: u64,
+    pub modified_at_nanos: u64,
+}
+
+pub(crate) fn get_file_stamp(path: &str) -> Result<FileStamp, NexaError> {
+    let metadata = fs::metadata(path)?;
+    let modified_at_nanos = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or_default();
+
+    Ok(FileStamp {
+        size: metadata.len(),
+        modified_at_nanos,
+    })
+}
+
+pub(crate) fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::base::{ChatMessageContent, ChatMessageWithId, Role};
+    use crate::llm::test_support::get_history;
+
+    fn get_chunk(text: &str, done: bool) -> EmittedChatMessage {
+        EmittedChatMessage {
+            id: String::from("1"),
+            message: vec![ChatMessage {
+                role: Role::Assistant,
+                content: ChatMessageContent::Text {
+                    text: text.to_string(),
+                    _meta: None,
+                },
+                images: None,
+            }],
+            done,
+            usage: None,
+            blocked: None,
+            finish_reason: None,
+            cost: None,
+            error: None,
+        }
+    }
+
+    fn get_enabled_cache(max_entries: usize) -> ResponseCache {
+        ResponseCache {
+            config: ResponseCacheConfig {
+                enabled: true,
+                max_entries,
+                ..Default::default()
+            },
+            entries: HashMap::new(),
+            dir: None,
+        }
+    }
+
+    #[test]
+    fn cache_key_test() {
+        let tools = json!({"b": 1, "a": [{"y": 2, "x": 1}]});
+        let reordered_tools = json!({"a": [{"x": 1, "y": 2}], "b": 1});
+
+        // Message ids and key order don't change the key
+        let mut other_ids = get_history("Hello");
+        other_ids.messages[0].id = String::from("3");
+        let key_1 = get_cache_key(
+            &Provider::Gemini,
+            "gemini-2.5-pro",
+            &get_history("Hello"),
+            &tools,
+            &Value::Null,
+        )
+        .unwrap();
+        let key_2 = get_cache_key(
+            &Provider::Gemini,
+            "gemini-2.5-pro",
+            &other_ids,
+            &reordered_tools,
+            &Value::Null,
+        )
+        .unwrap();
+        assert_eq!(key_1, key_2);
+
+        // Content, model and generation config do
+        let key_3 = get_cache_key(
+            &Provider::Gemini,
+            "gemini-2.5-pro",
+            &get_history("Hello!"),
+            &tools,
+            &Value::Null,
+        )
+        .unwrap();
+        let key_4 = get_cache_key(
+            &Provider::Gemini,
+            "gemini-2.5-flash",
+            &get_history("Hello"),
+            &tools,
+            &Value::Null,
+        )
+        .unwrap();
+        let key_5 = get_cache_key(
+            &Provider::Gemini,
+            "gemini-2.5-pro",
+            &get_history("Hello"),
+            &tools,
+            &json!({"temperature": 0.2}),
+        )
+        .unwrap();
+        assert_ne!(key_1, key_3);
+        assert_ne!(key_1, key_4);
+        assert_ne!(key_1, key_5);
+
+        // So does a change to an attached file
+        let path = std::env::temp_dir().join(format!("nexa-cache-key-{}.txt", std::process::id()));
+        let history = ChatHistory {
+            messages: vec![ChatMessageWithId {
+                id: String::from("abc"),
+                role: Role::User,
+                content: ChatMessageContent::File {
+                    path: path.to_string_lossy().to_string(),
+                    mime_type: String::from("text/plain"),
+                    display_name: None,
+                    _meta: None,
+                },
+                images: None,
+            }],
+        };
+        let get_file_key = || {
+            get_cache_key(
+                &Provider::Gemini,
+                "gemini-2.5-pro",
+                &history,
+                &tools,
+                &Value::Null,
+            )
+            .unwrap()
+        };
+        fs::write(&path, "first").unwrap();
+        let key_6 = get_file_key();
+        assert_eq!(key_6, get_file_key());
+        fs::write(&path, "second").unwrap();
+        let key_7 = get_file_key();
+        let _ = fs::remove_file(&path);
+        assert_ne!(key_6, key_7);
+    }
+
+    #[test]
+    fn cache_ttl_and_eviction_test() {
+        let mut cache = get_enabled_cache(2);
+
+        cache
+            .insert(String::from("a"), vec![get_chunk("A", true)])
+            .unwrap();
+        cache
+            .insert(String::from("b"), vec![get_chunk("B", true)])
+            .unwrap();
+        assert!(cache.get("a").unwrap().is_some());
+
+        // "b" is the least recently used one now
+        cache.entries.get_mut("b").unwrap().last_used_at = 0;
+        cache
+            .insert(String::from("c"), vec![get_chunk("C", true)])
+            .unwrap();
+        assert!(cache.get("a").unwrap().is_some());
+        assert!(cache.get("b").unwrap().is_none());
+        assert!(cache.get("c").unwrap().is_some());
+
+        // Expired entries are never replayed
+        cache.entries.get_mut("a").unwrap().created_at = 0;
+        assert!(cache.get("a").unwrap().is_none());
+        assert!(!cache.entries.contains_key("a"));
+
+        // Disabled cache neither reads nor writes
+        cache.config.enabled = false;
+        assert!(cache.get("c").unwrap().is_none());
+        cache
+            .insert(String::from("d"), vec![get_chunk("D", true)])
+            .unwrap();
+        assert!(!cache.entries.contains_key("d"));
+    }
+
+    #[test]
+    fn cache_files_test() {
+        let dir = std::env::temp_dir().join(format!("nexa-response-cache-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut cache = ResponseCache::load(Some(dir.clone()));
+        cache
+            .set_config(ResponseCacheConfig {
+                enabled: true,
+                max_entries: 1,
+                ..Default::default()
+            })
+            .unwrap();
+        cache
+            .insert(String::from("a"), vec![get_chunk("A", true)])
+            .unwrap();
+        cache.entries.get_mut("a").unwrap().last_used_at = 0;
+        cache
+            .insert(String::from("b"), vec![get_chunk("B", true)])
+            .unwrap();
+
+        // The evicted entry's file is gone with it
+        let mut cache = ResponseCache::load(Some(dir.clone()));
+        assert!(cache.get("a").unwrap().is_none());
+        cache.entries.get_mut("b").unwrap().last_used_at = 0;
+        assert!(cache.get("b").unwrap().is_some());
+
+        // And using an entry is remembered
+        let mut cache = ResponseCache::load(Some(dir.clone()));
+        assert_ne!(cache.entries["b"].last_used_at, 0);
+
+        cache.clear().unwrap();
+        let mut cache = ResponseCache::load(Some(dir.clone()));
+        let _ = fs::remove_dir_all(&dir);
+        assert!(cache.get_config().enabled);
+        assert!(cache.get("b").unwrap().is_none());
+    }
+}
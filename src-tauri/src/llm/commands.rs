@@ -1,33 +1,68 @@
-use crate::api::gemini::{FunctionDeclaration, Tool};
+use crate::api::gemini::{FunctionCallingMode, FunctionDeclaration, Tool, ToolConfig};
+use crate::api::gemini_files::{
+    delete_gemini_file, get_gemini_file, list_gemini_files, GeminiFile, GeminiListFilesResponse,
+};
+use crate::conversation::commands::update_conversation_tree;
+use crate::conversation::settings::ConversationSettings;
 use crate::error::NexaError;
 use crate::llm::base::{
-    ChatHistory, ChatMessage, ChatMessageContent, EmittedChatMessage, Provider, LLM,
+    merge_chat_messages, ChatHistory, ChatMessage, ChatMessageContent, EmittedChatMessage,
+    Provider, Role, TokenUsage, ToolCallingMode, LLM,
 };
-use crate::llm::constants::GEMINI_KETRING_KEY;
+use crate::llm::cache::{CachedLLM, ResponseCacheConfig};
+use crate::llm::compare::{run_compare_target, CompareResult, CompareTarget};
+use crate::llm::continuation::AutoContinue;
 use crate::llm::gemini::Gemini;
+use crate::llm::gemini_files::{resolve_gemini_file, GeminiFileEntry};
+use crate::llm::limits::{QuotaReport, RateLimit, RateLimited};
+use crate::llm::mock::Mock;
 use crate::llm::ollama::{
-    OllamaChatMessage, OllamaChatRequest, OllamaChatResponse, OllamaModelInfo, OllamaModelTag,
-    OllamaTagsResponse,
+    get_ollama_model_info, get_ollama_ps, get_ollama_tags, send_ollama_generate_request, Ollama,
+    OllamaGenerateRequest, OllamaKeepAlive, OllamaRunningModel,
 };
+use crate::llm::pricing::{get_conversation_cost, ModelPricing, Priced, SpendLimit};
+use crate::llm::rotation::CredentialRotation;
+use crate::secrets::profiles::{get_profile_secret_key, DEFAULT_PROFILE};
+use crate::tools::native::NATIVE_TOOLS_SERVER_NAME;
 use crate::AppData;
+use futures::future::join_all;
 use futures::pin_mut;
-use futures_util::StreamExt;
-use keyring::Entry;
-use serde_json::json;
-use std::collections::HashMap;
-use std::str::from_utf8;
+use futures_util::{Stream, StreamExt};
+use serde_json::{json, Value};
+use std::time::Instant;
 use tauri::{AppHandle, Emitter, State};
-use tauri_plugin_http::reqwest;
 
+// Answers the active leaf (or `leaf_id`) of a conversation with the provider,
+// model and options stored in its settings
 #[tauri::command]
 pub async fn stream_chat(
     app: AppHandle,
     state: State<'_, AppData>,
-    history: ChatHistory,
-    model: String,
-    provider: Provider,
+    conversation_id: String,
+    leaf_id: Option<String>,
 ) -> Result<(), NexaError> {
-    let client = reqwest::Client::new();
+    let (history, settings, spent) = {
+        let store = state.conversation_store.lock().await;
+        let tree = store.load_tree(&conversation_id)?;
+        let leaf_id = leaf_id
+            .or(tree.active_leaf_id.clone())
+            .ok_or(NexaError::Command(String::from(
+                "Stream chat command on an empty conversation",
+            )))?;
+
+        (
+            tree.get_history(&leaf_id)?,
+            store.get_settings(&conversation_id)?,
+            get_conversation_cost(&tree, Some(&leaf_id))?,
+        )
+    };
+
+    let (Some(provider), Some(model)) = (settings.provider.clone(), settings.model.clone()) else {
+        return Err(NexaError::Command(String::from(
+            "Stream chat command without a provider and model",
+        )));
+    };
+    let max_continuations = settings.auto_continue.unwrap_or(0);
 
     dbg!(&history);
 
@@ -36,53 +71,71 @@ pub async fn stream_chat(
             "Stream chat command without chat history",
         )));
     }
+    let leaf_id = history.messages.last().unwrap().id.clone();
+    let (reply_provider, reply_model) = (provider.clone(), model.clone());
 
-    let user_input = history.messages.last();
-    if user_input.is_none() {
-        return Err(NexaError::Command(String::from(
-            "Stream chat command without user input",
-        )));
-    }
+    let spend_limit = SpendLimit {
+        spent,
+        settings: settings.clone(),
+        price: state.pricing.lock().await.get(&provider, &model),
+    };
+    spend_limit.check(None, &history)?;
 
-    match provider {
+    let mut reply = match provider {
         Provider::Gemini => {
-            let product_name = app.config().product_name.clone();
-            if product_name.is_none() {
-                return Err(NexaError::Command(String::from("Product name is none")));
-            }
-
-            // Need to move this to setup maybe
-            let product_name = product_name.unwrap();
-            let entry = Entry::new(&product_name, GEMINI_KETRING_KEY).expect("Keyring Error");
-            let api_key = entry.get_password().expect("Keychain Error");
-
-            let mcp_clients = state.mcp_clients.read().await;
-            let mut tools: Vec<Tool> = vec![];
-
-            for (server_name, mcp_client) in mcp_clients.iter() {
-                let tool_list = mcp_client.get_tool_list().await;
-                let function_decorations: Vec<FunctionDeclaration> = tool_list
-                    .iter()
-                    .map(|(name, tool)| FunctionDeclaration {
-                        name: format!("{}-_-{}", server_name.clone(), name.clone()),
-                        description: tool.description.clone().unwrap_or_default(),
-                        parameters: Some(serde_json::to_value(&tool.input_schema).unwrap()),
-                        extra_fields: json!({}),
-                    })
-                    .collect();
-
-                tools.push(Tool {
-                    function_declarations: Some(function_decorations),
-
-                    extra_fields: json!({}),
-                });
-            }
+            let tools = get_gemini_tools(&state, &settings).await;
+            // Gemini refuses a tool config without any function declarations
+            let tool_config = match tools.is_empty() {
+                true => None,
+                false => get_gemini_tool_config(settings.tool_calling_mode.as_ref()),
+            };
+            let api_keys = get_gemini_api_keys(
+                &state,
+                settings.credential_profile.as_deref(),
+                settings.rotate_credentials.unwrap_or(false),
+            )
+            .await?;
+            let candidates = api_keys
+                .into_iter()
+                .map(|api_key| Gemini {
+                    model_id: model.clone(),
+                    tools: tools.clone(),
+                    api_key,
+                    tool_config: tool_config.clone(),
+                    safety_settings: settings.safety_settings.clone(),
+                    system_prompt: settings.system_prompt.clone(),
+                    generation_config: settings.generation.clone(),
+                    transport: state.http_transport.clone(),
+                    file_registry: state.gemini_files.clone(),
+                })
+                .collect();
 
-            let gemini = Gemini {
-                model_id: model,
-                tools: tools,
-                api_key: api_key,
-                tool_config: None,
+            let gemini = Priced {
+                inner: AutoContinue {
+                    max_continuations,
+                    spend_limit: Some(spend_limit.clone()),
+                    inner: CachedLLM {
+                        cache: &state.response_cache,
+                        provider: provider.clone(),
+                        model: model.clone(),
+                        tools: serde_json::to_value(&tools)?,
+                        generation_config: json!({
+                            "systemPrompt": settings.system_prompt,
+                            "generation": settings.generation,
+                            "toolConfig": tool_config,
+                            "safetySettings": settings.safety_settings,
+                        }),
+                        inner: RateLimited {
+                            inner: CredentialRotation { candidates },
+                            limiter: state.rate_limiter.clone(),
+                            provider: provider.clone(),
+                            model: model.clone(),
+                        },
+                    },
+                },
+                pricing: &state.pricing,
+                provider,
+                model,
             };
 
             let stream = gemini.stream_chat(history).await;
@@ -93,104 +146,450 @@ pub async fn stream_chat(
                 ))));
             }
 
-            let stream = stream.unwrap();
-            pin_mut!(stream);
-
-            while let Some(item) = stream.next().await {
-                match item {
-                    Ok(message) => {
-                        _ = app.emit("stream_chat", message);
-                    }
-                    Err(e) => {
-                        dbg!(e);
-                    }
-                }
-            }
-
-            Ok(())
+            emit_chat_stream(&app, "stream_chat", &leaf_id, stream.unwrap()).await
         }
         Provider::Ollama => {
-            // Ollama section
-            let id = user_input.unwrap().id.clone();
-            let mut messages: Vec<OllamaChatMessage> = vec![];
-
-            for msg in history.messages.into_iter() {
-                if let ChatMessageContent::Text { text, _meta } = msg.content {
-                    messages.push(OllamaChatMessage {
-                        role: msg.role,
-                        content: text,
-                        images: msg.images,
-                    });
-                }
-            }
+            let ollama = Priced {
+                inner: AutoContinue {
+                    max_continuations,
+                    spend_limit: Some(spend_limit.clone()),
+                    inner: CachedLLM {
+                        cache: &state.response_cache,
+                        provider: provider.clone(),
+                        model: model.clone(),
+                        tools: Value::Null,
+                        generation_config: json!({
+                            "systemPrompt": settings.system_prompt,
+                            "generation": settings.generation,
+                        }),
+                        inner: RateLimited {
+                            inner: Ollama {
+                                model: model.clone(),
+                                keep_alive: settings.keep_alive,
+                                system_prompt: settings.system_prompt,
+                                generation_config: settings.generation,
+                                transport: state.http_transport.clone(),
+                            },
+                            limiter: state.rate_limiter.clone(),
+                            provider: provider.clone(),
+                            model: model.clone(),
+                        },
+                    },
+                },
+                pricing: &state.pricing,
+                provider,
+                model,
+            };
 
-            let req = OllamaChatRequest {
+            let stream = ollama.stream_chat(history).await?;
+            emit_chat_stream(&app, "stream_chat", &leaf_id, stream).await
+        }
+        // Scripted responses are deterministic already, so they skip the cache
+        Provider::Mock => {
+            let mock = Priced {
+                inner: AutoContinue {
+                    max_continuations,
+                    spend_limit: Some(spend_limit.clone()),
+                    inner: Mock::from_model(&model)?,
+                },
+                pricing: &state.pricing,
+                provider,
                 model,
-                messages: messages,
             };
-            let res = client
-                .post("http://localhost:11434/api/chat")
-                .body(serde_json::to_string(&req).unwrap())
-                .send()
-                .await
-                .unwrap();
-
-            let mut stream = res.bytes_stream();
-
-            while let Some(item) = stream.next().await {
-                match item {
-                    Ok(byte) => {
-                        let msg = from_utf8(&byte).unwrap();
-                        let stream_response: OllamaChatResponse =
-                            serde_json::from_str(msg).unwrap();
-                        let emitted_message = EmittedChatMessage {
-                            id: id.clone(),
-                            message: vec![ChatMessage {
-                                role: stream_response.message.role,
-                                images: stream_response.message.images,
-                                content: ChatMessageContent::Text {
-                                    text: stream_response.message.content,
-                                    _meta: None,
-                                },
-                            }],
-                            done: stream_response.done,
-                        };
-                        _ = app.emit("stream_chat", emitted_message);
-                    }
-                    Err(e) => {
-                        dbg!(e);
-                        break;
-                    }
+
+            let stream = mock.stream_chat(history).await?;
+            emit_chat_stream(&app, "stream_chat", &leaf_id, stream).await
+        }
+    };
+
+    // The reply text goes into the placeholder leaf. Function calls are added
+    // by the frontend as their own turns once they ran, the placeholder still
+    // carries what the reply cost so the spend limits see tool loops too.
+    // Whatever came before a broken off stream is kept as well.
+    let error = reply.error.take();
+    let reply_text = reply.messages.into_iter().find(|message| {
+        message.role == Role::Assistant
+            && matches!(message.content, ChatMessageContent::Text { .. })
+    });
+
+    update_conversation_tree(&state, &conversation_id, |tree| match reply_text {
+        Some(reply_text) => tree.set_reply(
+            &leaf_id,
+            reply_text.content,
+            reply_provider,
+            reply_model,
+            reply.usage,
+            reply.cost,
+        ),
+        None if reply.usage.is_some() => tree.set_reply_usage(
+            &leaf_id,
+            reply_provider,
+            reply_model,
+            reply.usage,
+            reply.cost,
+        ),
+        None => Ok(()),
+    })
+    .await?;
+
+    match error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+#[tauri::command]
+pub async fn compare_chat(
+    app: AppHandle,
+    state: State<'_, AppData>,
+    run_id: String,
+    history: ChatHistory,
+    targets: Vec<CompareTarget>,
+) -> Result<Vec<CompareResult>, NexaError> {
+    if history.messages.is_empty() {
+        return Err(NexaError::Command(String::from(
+            "Compare chat command without chat history",
+        )));
+    }
+
+    if targets.is_empty() {
+        return Err(NexaError::Command(String::from(
+            "Compare chat command without targets",
+        )));
+    }
+
+    let gemini_tools = get_gemini_tools(&state, &ConversationSettings::default()).await;
+    let runs = targets.into_iter().map(|target| {
+        run_compare_target(
+            &app,
+            &run_id,
+            target,
+            history.clone(),
+            gemini_tools.clone(),
+            state.http_transport.clone(),
+            state.gemini_files.clone(),
+        )
+    });
+    let results = join_all(runs).await;
+
+    // Only the run on screen can get a winner, an older one is dropped
+    *state.compare_run.write().await = Some((run_id, results.clone()));
+
+    Ok(results)
+}
+
+#[tauri::command]
+pub async fn select_compare_winner(
+    state: State<'_, AppData>,
+    run_id: String,
+    target_id: String,
+) -> Result<CompareResult, NexaError> {
+    let mut compare_run = state.compare_run.write().await;
+    let results = match compare_run.take() {
+        Some((id, results)) if id == run_id => results,
+        other => {
+            *compare_run = other;
+            return Err(NexaError::Command(String::from(
+                "Can't find the compare run with the given id",
+            )));
+        }
+    };
+
+    results
+        .into_iter()
+        .find(|result| result.target_id == target_id)
+        .ok_or(NexaError::Command(String::from(
+            "Can't find the compare target with the given id",
+        )))
+}
+
+#[tauri::command]
+pub async fn get_response_cache_config(
+    state: State<'_, AppData>,
+) -> Result<ResponseCacheConfig, NexaError> {
+    Ok(state.response_cache.lock().await.get_config())
+}
+
+#[tauri::command]
+pub async fn set_response_cache_config(
+    state: State<'_, AppData>,
+    config: ResponseCacheConfig,
+) -> Result<(), NexaError> {
+    state.response_cache.lock().await.set_config(config)
+}
+
+#[tauri::command]
+pub async fn clear_response_cache(state: State<'_, AppData>) -> Result<(), NexaError> {
+    state.response_cache.lock().await.clear()
+}
+
+#[tauri::command]
+pub async fn get_rate_limits(state: State<'_, AppData>) -> Result<Vec<RateLimit>, NexaError> {
+    Ok(state.rate_limiter.lock().await.get_limits())
+}
+
+#[tauri::command]
+pub async fn set_rate_limits(
+    state: State<'_, AppData>,
+    limits: Vec<RateLimit>,
+) -> Result<(), NexaError> {
+    state.rate_limiter.lock().await.set_limits(limits)
+}
+
+#[tauri::command]
+pub async fn get_pricing(state: State<'_, AppData>) -> Result<Vec<ModelPricing>, NexaError> {
+    Ok(state.pricing.lock().await.get_prices())
+}
+
+#[tauri::command]
+pub async fn set_pricing(
+    state: State<'_, AppData>,
+    prices: Vec<ModelPricing>,
+) -> Result<(), NexaError> {
+    state.pricing.lock().await.set_prices(prices)
+}
+
+// What's left of every configured limit, along with today's usage per model
+#[tauri::command]
+pub async fn get_quota_status(state: State<'_, AppData>) -> Result<QuotaReport, NexaError> {
+    Ok(state
+        .rate_limiter
+        .lock()
+        .await
+        .get_quota_report(Instant::now()))
+}
+
+// Uploads ahead of time so the first chat turn with a big attachment doesn't
+// have to wait for it. Files belong to the key they were uploaded with, so
+// these take the credential profile of the conversation (the default one
+// without).
+#[tauri::command]
+pub async fn upload_gemini_file(
+    state: State<'_, AppData>,
+    profile: Option<String>,
+    path: String,
+    mime_type: String,
+    display_name: Option<String>,
+) -> Result<GeminiFileEntry, NexaError> {
+    resolve_gemini_file(
+        state.http_transport.as_ref(),
+        &get_gemini_api_key(&state, profile.as_deref())?,
+        &state.gemini_files,
+        &path,
+        &mime_type,
+        display_name,
+    )
+    .await
+}
+
+#[tauri::command]
+pub async fn get_gemini_file_info(
+    state: State<'_, AppData>,
+    profile: Option<String>,
+    name: String,
+) -> Result<GeminiFile, NexaError> {
+    get_gemini_file(
+        state.http_transport.as_ref(),
+        &get_gemini_api_key(&state, profile.as_deref())?,
+        &name,
+    )
+    .await
+}
+
+#[tauri::command]
+pub async fn get_gemini_files(
+    state: State<'_, AppData>,
+    profile: Option<String>,
+    page_token: Option<String>,
+) -> Result<GeminiListFilesResponse, NexaError> {
+    list_gemini_files(
+        state.http_transport.as_ref(),
+        &get_gemini_api_key(&state, profile.as_deref())?,
+        page_token,
+    )
+    .await
+}
+
+#[tauri::command]
+pub async fn remove_gemini_file(
+    state: State<'_, AppData>,
+    profile: Option<String>,
+    name: String,
+) -> Result<(), NexaError> {
+    let api_key = get_gemini_api_key(&state, profile.as_deref())?;
+    delete_gemini_file(state.http_transport.as_ref(), &api_key, &name).await?;
+    state
+        .gemini_files
+        .lock()
+        .await
+        .remove_by_name(&api_key, &name)
+}
+
+pub(crate) struct ChatStreamReply {
+    pub messages: Vec<ChatMessage>,
+    pub usage: Option<TokenUsage>,
+    pub cost: Option<f64>,
+    // The error the stream broke off with
+    pub error: Option<NexaError>,
+}
+
+// Forwards every chunk to the frontend and returns the whole reply, with the
+// last usage and cost reported for it. A stream error ends the reply, the
+// frontend gets it as a closing chunk for the message `id`.
+pub(crate) async fn emit_chat_stream(
+    app: &AppHandle,
+    event: &str,
+    id: &str,
+    stream: impl Stream<Item = Result<EmittedChatMessage, NexaError>>,
+) -> ChatStreamReply {
+    pin_mut!(stream);
+    let mut reply = ChatStreamReply {
+        messages: vec![],
+        usage: None,
+        cost: None,
+        error: None,
+    };
+
+    while let Some(item) = stream.next().await {
+        match item {
+            Ok(message) => {
+                merge_chat_messages(&mut reply.messages, message.message.clone());
+                if message.usage.is_some() {
+                    reply.usage = message.usage.clone();
                 }
+                if message.cost.is_some() {
+                    reply.cost = message.cost;
+                }
+                _ = app.emit(event, message);
+            }
+            Err(e) => {
+                _ = app.emit(event, get_error_chunk(id, &e));
+                reply.error = Some(e);
+                break;
             }
+        }
+    }
 
-            Ok(())
+    reply
+}
+
+pub(crate) fn get_error_chunk(id: &str, e: &NexaError) -> EmittedChatMessage {
+    EmittedChatMessage {
+        id: id.to_string(),
+        message: vec![],
+        done: true,
+        usage: None,
+        blocked: None,
+        finish_reason: None,
+        cost: None,
+        error: Some(e.to_string()),
+    }
+}
+
+pub(crate) fn get_gemini_api_key(
+    state: &AppData,
+    profile: Option<&str>,
+) -> Result<String, NexaError> {
+    let key = get_profile_secret_key(&Provider::Gemini, profile.unwrap_or(DEFAULT_PROFILE))?;
+
+    state.secret_store.require(&key)
+}
 
-            // dbg!(res);
+// Keys in the order they should be tried. The chosen profile has to have one,
+// profiles further down the rotation without a key are skipped.
+pub(crate) async fn get_gemini_api_keys(
+    state: &AppData,
+    profile: Option<&str>,
+    rotate: bool,
+) -> Result<Vec<String>, NexaError> {
+    let profiles =
+        state
+            .credential_profiles
+            .lock()
+            .await
+            .get_rotation(&Provider::Gemini, profile, rotate);
+
+    let mut api_keys = vec![];
+    for (index, name) in profiles.iter().enumerate() {
+        let key = get_profile_secret_key(&Provider::Gemini, name)?;
+        match index {
+            0 => api_keys.push(state.secret_store.require(&key)?),
+            _ => {
+                if let Ok(Some((api_key, _))) = state.secret_store.get(&key) {
+                    api_keys.push(api_key);
+                }
+            }
         }
     }
+
+    Ok(api_keys)
+}
+
+// Only the servers and tools the settings enable, servers left without any
+// tool are skipped. The built-in tools come first, as if they were a server.
+pub(crate) async fn get_gemini_tools(
+    state: &AppData,
+    settings: &ConversationSettings,
+) -> Vec<Tool> {
+    let mut servers = vec![(
+        NATIVE_TOOLS_SERVER_NAME.to_string(),
+        state.native_tools.lock().await.get_tool_list(),
+    )];
+    for (server_name, mcp_client) in state.mcp_clients.read().await.iter() {
+        servers.push((server_name.clone(), mcp_client.get_tool_list().await));
+    }
+
+    let mut tools: Vec<Tool> = vec![];
+    for (server_name, tool_list) in servers {
+        if !settings.is_server_enabled(&server_name) {
+            continue;
+        }
+
+        let function_decorations: Vec<FunctionDeclaration> = tool_list
+            .iter()
+            .map(|(name, tool)| FunctionDeclaration {
+                name: format!("{}-_-{}", server_name.clone(), name.clone()),
+                description: tool.description.clone().unwrap_or_default(),
+                parameters: Some(serde_json::to_value(&tool.input_schema).unwrap()),
+                extra_fields: json!({}),
+            })
+            .filter(|declaration| settings.is_tool_enabled(&declaration.name))
+            .collect();
+        if function_decorations.is_empty() {
+            continue;
+        }
+
+        tools.push(Tool {
+            function_declarations: Some(function_decorations),
+
+            extra_fields: json!({}),
+        });
+    }
+
+    tools
+}
+
+fn get_gemini_tool_config(mode: Option<&ToolCallingMode>) -> Option<ToolConfig> {
+    let mode = match mode? {
+        ToolCallingMode::Auto => FunctionCallingMode::Auto,
+        ToolCallingMode::Any => FunctionCallingMode::Any,
+        ToolCallingMode::None => FunctionCallingMode::None,
+    };
+
+    Some(ToolConfig::new(mode, None))
 }
 
 #[tauri::command]
-pub async fn get_all_ollama_chat_models() -> Vec<String> {
-    let client = reqwest::Client::new();
+pub async fn get_all_ollama_chat_models(
+    state: State<'_, AppData>,
+) -> Result<Vec<String>, NexaError> {
+    let transport = state.http_transport.as_ref();
 
-    let tags = get_all_ollama_models().await;
+    let tags = get_ollama_tags(transport).await?;
     let mut chat_models = vec![];
 
     for tag in tags {
-        let json_obj = json!({
-            "model": tag.name
-        });
-        let model_info_res = client
-            .post("http://localhost:11434/api/show")
-            .body(json_obj.to_string())
-            .send()
-            .await
-            .unwrap();
-
-        let bytes = model_info_res.bytes().await.unwrap();
-        let model_info: OllamaModelInfo = serde_json::from_slice(&bytes).unwrap();
+        let model_info = get_ollama_model_info(transport, &tag.name).await?;
         if model_info.capabilities.iter().any(|ca| ca == "completion") {
             chat_models.push(tag.name);
         }
@@ -198,18 +597,40 @@ pub async fn get_all_ollama_chat_models() -> Vec<String> {
 
     dbg!(&chat_models);
 
-    chat_models
+    Ok(chat_models)
 }
 
-async fn get_all_ollama_models() -> Vec<OllamaModelTag> {
-    let client = reqwest::Client::new();
-    let res = client
-        .get("http://localhost:11434/api/tags")
-        .send()
-        .await
-        .unwrap();
+#[tauri::command]
+pub async fn get_ollama_running_models(
+    state: State<'_, AppData>,
+) -> Result<Vec<OllamaRunningModel>, NexaError> {
+    get_ollama_ps(state.http_transport.as_ref()).await
+}
 
-    let bytes = res.bytes().await.unwrap();
-    let ollama_tags: OllamaTagsResponse = serde_json::from_slice(&bytes).unwrap();
-    ollama_tags.models
+#[tauri::command]
+pub async fn preload_ollama_model(
+    state: State<'_, AppData>,
+    model: String,
+    keep_alive: Option<OllamaKeepAlive>,
+) -> Result<(), NexaError> {
+    send_ollama_generate_request(
+        state.http_transport.as_ref(),
+        OllamaGenerateRequest { model, keep_alive },
+    )
+    .await
+}
+
+#[tauri::command]
+pub async fn unload_ollama_model(
+    state: State<'_, AppData>,
+    model: String,
+) -> Result<(), NexaError> {
+    send_ollama_generate_request(
+        state.http_transport.as_ref(),
+        OllamaGenerateRequest {
+            model,
+            keep_alive: Some(OllamaKeepAlive::Seconds(0)),
+        },
+    )
+    .await
 }
@@ -0,0 +1,223 @@
+use crate::api::gemini::Tool;
+use crate::api::transport::HttpTransport;
+use crate::error::NexaError;
+use crate::llm::base::{
+    merge_chat_messages, ChatHistory, ChatMessage, EmittedChatMessage, GenerationConfig, Provider,
+    TokenUsage, LLM,
+};
+use crate::llm::commands::{get_error_chunk, get_gemini_api_key};
+use crate::llm::gemini::Gemini;
+use crate::llm::gemini_files::GeminiFileRegistry;
+use crate::llm::limits::RateLimited;
+use crate::llm::mock::Mock;
+use crate::llm::ollama::Ollama;
+use crate::AppData;
+use futures::pin_mut;
+use futures_util::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Instant;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::Mutex;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CompareTarget {
+    pub id: String,
+    pub provider: Provider,
+    pub model: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credential_profile: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CompareResult {
+    pub run_id: String,
+    pub target_id: String,
+    pub provider: Provider,
+    pub model: String,
+    pub messages: Vec<ChatMessage>,
+    pub time_to_first_token_ms: Option<u64>,
+    pub total_duration_ms: u64,
+    pub usage: Option<TokenUsage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost: Option<f64>,
+    pub error: Option<String>,
+}
+
+// Every target streams to its own channel so the UI can render the runs side by side
+pub(crate) fn get_compare_event_name(run_id: &str, target_id: &str) -> String {
+    format!("compare_chat/{}/{}", run_id, target_id)
+}
+
+pub(crate) async fn run_compare_target(
+    app: &AppHandle,
+    run_id: &str,
+    target: CompareTarget,
+    history: ChatHistory,
+    gemini_tools: Vec<Tool>,
+    transport: Arc<dyn HttpTransport>,
+    gemini_files: Arc<Mutex<GeminiFileRegistry>>,
+) -> CompareResult {
+    let started_at = Instant::now();
+    let event = get_compare_event_name(run_id, &target.id);
+    // Chunks carry the id of the last message, like the ones of stream_chat
+    let reply_id = history
+        .messages
+        .last()
+        .map(|message| message.id.clone())
+        .unwrap_or_default();
+
+    let mut result = CompareResult {
+        run_id: run_id.to_string(),
+        target_id: target.id.clone(),
+        provider: target.provider.clone(),
+        model: target.model.clone(),
+        messages: vec![],
+        time_to_first_token_ms: None,
+        total_duration_ms: 0,
+        usage: None,
+        cost: None,
+        error: None,
+    };
+
+    let state = app.state::<AppData>();
+    let outcome = match target.provider {
+        Provider::Gemini => {
+            match get_gemini_api_key(&state, target.credential_profile.as_deref()) {
+                Ok(api_key) => {
+                    let gemini = RateLimited {
+                        inner: Gemini {
+                            model_id: target.model.clone(),
+                            tools: gemini_tools,
+                            api_key,
+                            tool_config: None,
+                            safety_settings: None,
+                            system_prompt: None,
+                            generation_config: GenerationConfig::default(),
+                            transport,
+                            file_registry: gemini_files,
+                        },
+                        limiter: state.rate_limiter.clone(),
+                        provider: Provider::Gemini,
+                        model: target.model,
+                    };
+
+                    let outcome = match gemini.stream_chat(history).await {
+                        Ok(stream) => {
+                            collect_compare_stream(
+                                app,
+                                &event,
+                                &reply_id,
+                                stream,
+                                started_at,
+                                &mut result,
+                            )
+                            .await
+                        }
+                        Err(e) => Err(e),
+                    };
+                    outcome
+                }
+                Err(e) => Err(e),
+            }
+        }
+        Provider::Ollama => {
+            let ollama = RateLimited {
+                inner: Ollama {
+                    model: target.model.clone(),
+                    keep_alive: None,
+                    system_prompt: None,
+                    generation_config: GenerationConfig::default(),
+                    transport,
+                },
+                limiter: state.rate_limiter.clone(),
+                provider: Provider::Ollama,
+                model: target.model,
+            };
+
+            let outcome = match ollama.stream_chat(history).await {
+                Ok(stream) => {
+                    collect_compare_stream(app, &event, &reply_id, stream, started_at, &mut result)
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            outcome
+        }
+        Provider::Mock => match Mock::from_model(&target.model) {
+            Ok(mock) => {
+                let outcome = match mock.stream_chat(history).await {
+                    Ok(stream) => {
+                        collect_compare_stream(
+                            app,
+                            &event,
+                            &reply_id,
+                            stream,
+                            started_at,
+                            &mut result,
+                        )
+                        .await
+                    }
+                    Err(e) => Err(e),
+                };
+                outcome
+            }
+            Err(e) => Err(e),
+        },
+    };
+
+    if let Err(e) = outcome {
+        result.error = Some(e.to_string());
+    }
+    result.total_duration_ms = started_at.elapsed().as_millis() as u64;
+    let price = state
+        .pricing
+        .lock()
+        .await
+        .get(&result.provider, &result.model);
+    if let (Some(usage), Some(price)) = (&result.usage, price) {
+        result.cost = Some(price.get_cost(usage));
+    }
+
+    _ = app.emit("compare_chat_result", result.clone());
+
+    result
+}
+
+async fn collect_compare_stream(
+    app: &AppHandle,
+    event: &str,
+    id: &str,
+    stream: impl Stream<Item = Result<EmittedChatMessage, NexaError>>,
+    started_at: Instant,
+    result: &mut CompareResult,
+) -> Result<(), NexaError> {
+    pin_mut!(stream);
+
+    while let Some(item) = stream.next().await {
+        let message = match item {
+            Ok(message) => message,
+            Err(e) => {
+                _ = app.emit(event, get_error_chunk(id, &e));
+                return Err(e);
+            }
+        };
+
+        if result.time_to_first_token_ms.is_none() && !message.message.is_empty() {
+            result.time_to_first_token_ms = Some(started_at.elapsed().as_millis() as u64);
+        }
+        if message.usage.is_some() {
+            result.usage = message.usage.clone();
+        }
+        if let Some(blocked) = &message.blocked {
+            result.error = Some(blocked.message.clone());
+        }
+        merge_chat_messages(&mut result.messages, message.message.clone());
+
+        _ = app.emit(event, message);
+    }
+
+    Ok(())
+}
@@ -1 +1,2 @@
 pub static GEMINI_KETRING_KEY: &str = "gemini-api-key";
+pub static OLLAMA_BASE_URL: &str = "http://localhost:11434";
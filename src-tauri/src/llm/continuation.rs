@@ -0,0 +1,268 @@
+use crate::error::NexaError;
+use crate::llm::base::{
+    is_max_tokens_finish, ChatHistory, ChatMessageContent, ChatMessageWithId, EmittedChatMessage,
+    Role, TokenUsage, LLM,
+};
+use crate::llm::pricing::{SpendLimit, SPEND_LIMIT_FINISH};
+use async_stream::stream;
+use futures_util::{Stream, StreamExt};
+use uuid::Uuid;
+
+pub(crate) static CONTINUE_PROMPT: &str =
+    "Continue exactly where your last message stopped. Don't repeat anything you already wrote and don't add an introduction.";
+
+// When a reply stops at the output token limit, sends what came so far back
+// with a request to keep going, up to `max_continuations` times. Every round
+// streams under the same placeholder id, so the UI sees a single message that
+// keeps growing and only the very last chunk is marked done. A round that
+// would go over `spend_limit` isn't started, the reply ends there instead.
+pub(crate) struct AutoContinue<L: LLM> {
+    pub(crate) inner: L,
+    pub(crate) max_continuations: u32,
+    pub(crate) spend_limit: Option<SpendLimit>,
+}
+
+impl<L: LLM> LLM for AutoContinue<L> {
+    async fn stream_chat(
+        &self,
+        history: ChatHistory,
+    ) -> Result<impl Stream<Item = Result<EmittedChatMessage, NexaError>>, NexaError> {
+        let first_round = self.inner.stream_chat(history.clone()).await?;
+
+        Ok(stream! {
+            let mut history = history;
+            let mut round = Box::pin(first_round);
+            let mut continuations = 0;
+            // Usage of the finished rounds. Providers report usage per request,
+            // so later rounds are added on top.
+            let mut total_usage: Option<TokenUsage> = None;
+
+            loop {
+                let mut round_text = String::new();
+                let mut round_usage: Option<TokenUsage> = None;
+                let mut last_chunk = None;
+
+                while let Some(item) = round.next().await {
+                    let mut chunk = match item {
+                        Ok(chunk) => chunk,
+                        Err(e) => {
+                            yield Err(e);
+                            return;
+                        }
+                    };
+
+                    for message in chunk.message.iter() {
+                        if let (Role::Assistant, ChatMessageContent::Text { text, .. }) =
+                            (&message.role, &message.content)
+                        {
+                            round_text.push_str(text);
+                        }
+                    }
+                    if let Some(usage) = chunk.usage.take() {
+                        chunk.usage = Some(add_usage(total_usage.as_ref(), &usage));
+                        round_usage = Some(usage);
+                    }
+
+                    if chunk.done {
+                        last_chunk = Some(chunk);
+                    } else {
+                        yield Ok(chunk);
+                    }
+                }
+
+                let Some(mut last_chunk) = last_chunk else {
+                    return;
+                };
+                if let Some(usage) = round_usage {
+                    total_usage = Some(add_usage(total_usage.as_ref(), &usage));
+                }
+
+                let truncated = last_chunk
+                    .finish_reason
+                    .as_deref()
+                    .is_some_and(is_max_tokens_finish);
+                if !truncated || continuations >= self.max_continuations {
+                    last_chunk.usage = total_usage;
+                    yield Ok(last_chunk);
+                    return;
+                }
+
+                let Some(placeholder) = history.messages.pop() else {
+                    return;
+                };
+                history.messages.push(ChatMessageWithId {
+                    id: Uuid::new_v4().to_string(),
+                    role: Role::Assistant,
+                    content: ChatMessageContent::Text {
+                        text: round_text,
+                        _meta: None,
+                    },
+                    images: None,
+                });
+                history.messages.push(ChatMessageWithId {
+                    id: Uuid::new_v4().to_string(),
+                    role: Role::User,
+                    content: ChatMessageContent::Text {
+                        text: CONTINUE_PROMPT.to_string(),
+                        _meta: None,
+                    },
+                    images: None,
+                });
+                history.messages.push(placeholder);
+
+                if let Some(spend_limit) = &self.spend_limit {
+                    if spend_limit.check(total_usage.as_ref(), &history).is_err() {
+                        last_chunk.usage = total_usage;
+                        last_chunk.finish_reason = Some(SPEND_LIMIT_FINISH.to_string());
+                        yield Ok(last_chunk);
+                        return;
+                    }
+                }
+
+                // Whatever the closing chunk carried still belongs to the reply
+                if !last_chunk.message.is_empty() {
+                    yield Ok(EmittedChatMessage {
+                        done: false,
+                        usage: None,
+                        finish_reason: None,
+                        cost: None,
+                        error: None,
+                        ..last_chunk
+                    });
+                }
+                continuations += 1;
+
+                match self.inner.stream_chat(history.clone()).await {
+                    Ok(next_round) => round = Box::pin(next_round),
+                    Err(e) => {
+                        yield Err(e);
+                        return;
+                    }
+                }
+            }
+        })
+    }
+}
+
+fn add_usage(total: Option<&TokenUsage>, usage: &TokenUsage) -> TokenUsage {
+    let Some(total) = total else {
+        return usage.clone();
+    };
+
+    TokenUsage {
+        input_tokens: total.input_tokens + usage.input_tokens,
+        output_tokens: total.output_tokens + usage.output_tokens,
+        cached_tokens: total.cached_tokens + usage.cached_tokens,
+        thinking_tokens: total.thinking_tokens + usage.thinking_tokens,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversation::settings::ConversationSettings;
+    use crate::llm::base::{merge_chat_messages, ChatMessage, Provider};
+    use crate::llm::pricing::{ConversationCost, ModelPricing};
+    use crate::llm::test_support::{collect_replies, get_history, get_mock};
+
+    // The reply runs out of tokens twice before it's done
+    const REPLIES: [(&str, Option<&str>); 3] = [
+        ("fn main() {\n", Some("length")),
+        ("    run();\n", Some("MAX_TOKENS")),
+        ("}\n", None),
+    ];
+
+    #[tokio::test]
+    async fn auto_continue_test() {
+        let auto_continue = AutoContinue {
+            inner: get_mock(&REPLIES),
+            max_continuations: 3,
+            spend_limit: None,
+        };
+        let emitted = collect_replies(&auto_continue, get_history("Write a long function")).await;
+
+        // One message under one id, closed once
+        assert!(emitted.iter().all(|chunk| chunk.id == "2"));
+        assert_eq!(emitted.iter().filter(|chunk| chunk.done).count(), 1);
+        let last = emitted.last().unwrap();
+        assert!(last.done);
+        assert_eq!(last.finish_reason.as_deref(), Some("stop"));
+
+        let mut merged: Vec<ChatMessage> = vec![];
+        for chunk in emitted.iter() {
+            merge_chat_messages(&mut merged, chunk.message.clone());
+        }
+        assert_eq!(merged.len(), 1);
+        assert!(matches!(
+            &merged[0].content,
+            ChatMessageContent::Text { text, .. } if text == "fn main() {\n    run();\n}\n"
+        ));
+
+        // Output tokens of all three rounds
+        assert_eq!(last.usage.as_ref().unwrap().output_tokens, 5);
+    }
+
+    #[tokio::test]
+    async fn auto_continue_limit_test() {
+        let auto_continue = AutoContinue {
+            inner: get_mock(&REPLIES),
+            max_continuations: 1,
+            spend_limit: None,
+        };
+        let emitted = collect_replies(&auto_continue, get_history("Write a long function")).await;
+
+        let last = emitted.last().unwrap();
+        assert_eq!(last.finish_reason.as_deref(), Some("MAX_TOKENS"));
+
+        // Turned off, the truncated reply is passed through as is
+        let auto_continue = AutoContinue {
+            inner: get_mock(&REPLIES),
+            max_continuations: 0,
+            spend_limit: None,
+        };
+        let emitted = collect_replies(&auto_continue, get_history("Write a long function")).await;
+        assert_eq!(
+            emitted.last().unwrap().finish_reason.as_deref(),
+            Some("length")
+        );
+    }
+
+    #[tokio::test]
+    async fn auto_continue_spend_limit_test() {
+        // A dollar per output token, so the first round already uses it all up
+        let spend_limit = SpendLimit {
+            spent: ConversationCost::default(),
+            settings: ConversationSettings {
+                max_conversation_cost: Some(1.0),
+                ..Default::default()
+            },
+            price: Some(ModelPricing {
+                provider: Provider::Mock,
+                model: String::from("mock"),
+                input: 0.0,
+                output: 1_000_000.0,
+                cached_input: None,
+                thinking: None,
+            }),
+        };
+        let auto_continue = AutoContinue {
+            inner: get_mock(&REPLIES),
+            max_continuations: 3,
+            spend_limit: Some(spend_limit),
+        };
+        let emitted = collect_replies(&auto_continue, get_history("Write a long function")).await;
+
+        assert_eq!(emitted.iter().filter(|chunk| chunk.done).count(), 1);
+        let last = emitted.last().unwrap();
+        assert_eq!(last.finish_reason.as_deref(), Some(SPEND_LIMIT_FINISH));
+
+        let mut merged: Vec<ChatMessage> = vec![];
+        for chunk in emitted.iter() {
+            merge_chat_messages(&mut merged, chunk.message.clone());
+        }
+        assert!(matches!(
+            &merged[0].content,
+            ChatMessageContent::Text { text, .. } if text == "fn main() {\n"
+        ));
+    }
+}
@@ -1,19 +1,33 @@
-use crate::api::gemini::{gemini_chat, GeminiPartMetadata};
+use crate::api::gemini::{gemini_chat, GeminiPartMetadata, UsageMetadata};
+use crate::api::gemini::{
+    BlockReason, Candidate, FinishReason, HarmCategory, HarmProbability, PromptFeedback,
+    SafetyRating, SafetySetting,
+};
 use crate::api::gemini::{Content, GeminiPart, GeminiPartData, Tool, ToolConfig};
+use crate::api::transport::HttpTransport;
 use crate::error::NexaError;
 use crate::llm::base::{
-    ChatHistory, ChatMessage, ChatMessageContent, EmittedChatMessage, Role, LLM,
+    BlockedOutcome, ChatHistory, ChatMessage, ChatMessageContent, EmittedChatMessage,
+    GenerationConfig, Role, TokenUsage, LLM,
 };
+use crate::llm::gemini_files::{resolve_gemini_file, GeminiFileRegistry};
 use futures::stream;
 use futures::StreamExt;
 use futures_util::Stream;
 use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::sync::Mutex;
 
 pub struct Gemini {
     pub model_id: String,
     pub tools: Vec<Tool>,
     pub api_key: String,
     pub tool_config: Option<ToolConfig>,
+    pub safety_settings: Option<Vec<SafetySetting>>,
+    pub system_prompt: Option<String>,
+    pub generation_config: GenerationConfig,
+    pub transport: Arc<dyn HttpTransport>,
+    pub(crate) file_registry: Arc<Mutex<GeminiFileRegistry>>,
 }
 
 pub static GEMINI_META: &str = "x-gemini-meta";
@@ -94,6 +108,36 @@ impl LLM for Gemini {
                         part_metadata,
                     }
                 }
+                ChatMessageContent::File {
+                    path,
+                    mime_type,
+                    display_name,
+                    _meta,
+                } => {
+                    let (thought, thought_signature, metadata, part_metadata) =
+                        get_gemini_meta_fields(_meta);
+                    let file = resolve_gemini_file(
+                        self.transport.as_ref(),
+                        &self.api_key,
+                        &self.file_registry,
+                        &path,
+                        &mime_type,
+                        display_name,
+                    )
+                    .await?;
+
+                    GeminiPart {
+                        thought,
+                        thought_signature,
+                        data: GeminiPartData::FileData {
+                            mime_type: file.mime_type,
+                            file_uri: file.uri,
+                            display_name: file.display_name,
+                        },
+                        metadata,
+                        part_metadata,
+                    }
+                }
             };
 
             if msg.role == cur_role {
@@ -184,24 +228,35 @@ impl LLM for Gemini {
         //     .collect();
 
         let stream = gemini_chat(
+            self.transport.as_ref(),
             combined_contents,
             self.tools.clone(),
             self.model_id.clone(),
             self.api_key.clone(),
             self.tool_config.clone(),
+            self.safety_settings.clone(),
+            self.system_prompt.clone(),
+            Some(self.generation_config.clone()),
         )
         .await?;
 
         let should_terminate_stream = false;
         let boxed_stream = Box::pin(stream);
 
+        // The finish reason comes with the last candidate but is reported on the
+        // closing chunk, like Ollama does
         Ok(stream::unfold(
-            (boxed_stream, should_terminate_stream, id),
-            |(mut stream, mut should_terminate_stream, id)| async move {
+            (boxed_stream, should_terminate_stream, id, None),
+            |(mut stream, mut should_terminate_stream, id, mut finish_reason)| async move {
                 let mut yielded_item = EmittedChatMessage {
                     id: id.clone(),
                     message: vec![],
                     done: false,
+                    usage: None,
+                    blocked: None,
+                    finish_reason: None,
+                    cost: None,
+                    error: None,
                 };
 
                 if should_terminate_stream {
@@ -211,17 +266,37 @@ impl LLM for Gemini {
                 if let Some(item) = stream.next().await {
                     match item {
                         Ok(gemini_response) => {
+                            // Gemini reports the running total on every chunk
+                            yielded_item.usage =
+                                gemini_response.usage_metadata.as_ref().map(get_token_usage);
+
+                            let blocked_prompt = gemini_response
+                                .prompt_feedback
+                                .as_ref()
+                                .and_then(get_prompt_blocked_outcome);
+                            if blocked_prompt.is_some() {
+                                yielded_item.blocked = blocked_prompt;
+                                return Some((
+                                    Ok(yielded_item),
+                                    (stream, should_terminate_stream, id, finish_reason),
+                                ));
+                            }
+
                             if gemini_response.candidates.len() < 1 {
                                 return Some((
                                     Err(NexaError::Gemini(
                                         "No candidate in the response".to_string(),
                                     )),
-                                    (stream, should_terminate_stream, id),
+                                    (stream, should_terminate_stream, id, finish_reason),
                                 ));
                             }
 
                             let first_candidate =
                                 gemini_response.candidates.first().unwrap().clone();
+                            yielded_item.blocked = get_candidate_blocked_outcome(&first_candidate);
+                            if let Some(reason) = &first_candidate.finish_reason {
+                                finish_reason = Some(get_reason_name(reason));
+                            }
 
                             for part in first_candidate.content.parts {
                                 let _meta = Some(generate_gemini_part_meta_value(&part));
@@ -250,14 +325,23 @@ impl LLM for Gemini {
                                 }
                             }
 
-                            Some((Ok(yielded_item), (stream, should_terminate_stream, id)))
+                            Some((
+                                Ok(yielded_item),
+                                (stream, should_terminate_stream, id, finish_reason),
+                            ))
+                        }
+                        Err(e) => {
+                            Some((Err(e), (stream, should_terminate_stream, id, finish_reason)))
                         }
-                        Err(e) => Some((Err(e), (stream, should_terminate_stream, id))),
                     }
                 } else {
                     yielded_item.done = true;
+                    yielded_item.finish_reason = finish_reason.take();
                     should_terminate_stream = true;
-                    Some((Ok(yielded_item), (stream, should_terminate_stream, id)))
+                    Some((
+                        Ok(yielded_item),
+                        (stream, should_terminate_stream, id, finish_reason),
+                    ))
                 }
             },
         ))
@@ -273,6 +357,132 @@ fn get_gemini_role(role: Role) -> String {
     }
 }
 
+fn get_prompt_blocked_outcome(feedback: &PromptFeedback) -> Option<BlockedOutcome> {
+    let block_reason = feedback.block_reason.as_ref()?;
+    let categories = get_flagged_categories(&feedback.safety_ratings);
+
+    let message = match block_reason {
+        BlockReason::Safety => format!(
+            "Blocked because the prompt was flagged for {}",
+            get_category_list(&categories)
+        ),
+        BlockReason::Blocklist => {
+            String::from("Blocked because the prompt contains terms from a blocklist")
+        }
+        BlockReason::ProhibitedContent => {
+            String::from("Blocked because the prompt contains prohibited content")
+        }
+        BlockReason::ImageSafety => {
+            String::from("Blocked because an image in the prompt was flagged as unsafe")
+        }
+        BlockReason::Other | BlockReason::Unspecified => {
+            String::from("Blocked by Gemini for an unspecified reason")
+        }
+    };
+
+    Some(BlockedOutcome {
+        reason: get_reason_name(block_reason),
+        message,
+        categories,
+    })
+}
+
+// Only the finish reasons that mean the reply was withheld, a normal stop or
+// running out of tokens isn't a block
+fn get_candidate_blocked_outcome(candidate: &Candidate) -> Option<BlockedOutcome> {
+    let finish_reason = candidate.finish_reason.as_ref()?;
+    let categories = get_flagged_categories(candidate.safety_ratings.as_deref().unwrap_or(&[]));
+
+    let message = match finish_reason {
+        FinishReason::Safety => format!(
+            "Blocked because the response was flagged for {}",
+            get_category_list(&categories)
+        ),
+        FinishReason::Recitation => {
+            String::from("Blocked because the response was too close to existing content")
+        }
+        FinishReason::Blocklist => {
+            String::from("Blocked because the response contained terms from a blocklist")
+        }
+        FinishReason::ProhibitedContent => {
+            String::from("Blocked because the response contained prohibited content")
+        }
+        FinishReason::Spii => {
+            String::from("Blocked because the response contained sensitive personal information")
+        }
+        FinishReason::ImageSafety => {
+            String::from("Blocked because a generated image was flagged as unsafe")
+        }
+        _ => return None,
+    };
+
+    Some(BlockedOutcome {
+        reason: get_reason_name(finish_reason),
+        message,
+        categories,
+    })
+}
+
+// Ratings Gemini marks as the cause of the block, or anything rated medium or
+// higher when it doesn't say
+fn get_flagged_categories(ratings: &[SafetyRating]) -> Vec<String> {
+    let mut flagged: Vec<&SafetyRating> = ratings
+        .iter()
+        .filter(|rating| rating.blocked == Some(true))
+        .collect();
+    if flagged.is_empty() {
+        flagged = ratings
+            .iter()
+            .filter(|rating| {
+                matches!(
+                    rating.probability,
+                    HarmProbability::Medium | HarmProbability::High
+                )
+            })
+            .collect();
+    }
+
+    flagged
+        .into_iter()
+        .map(|rating| {
+            String::from(match rating.category {
+                HarmCategory::Harassment => "harassment",
+                HarmCategory::HateSpeech => "hate speech",
+                HarmCategory::SexuallyExplicit => "sexually explicit content",
+                HarmCategory::DangerousContent => "dangerous content",
+                HarmCategory::CivicIntegrity => "civic integrity",
+                HarmCategory::Unspecified => "an unspecified category",
+            })
+        })
+        .collect()
+}
+
+fn get_category_list(categories: &[String]) -> String {
+    match categories {
+        [] => String::from("safety reasons"),
+        [category] => category.clone(),
+        [rest @ .., last] => format!("{} and {}", rest.join(", "), last),
+    }
+}
+
+fn get_reason_name<T: serde::Serialize>(reason: &T) -> String {
+    serde_json::to_value(reason)
+        .ok()
+        .and_then(|value| value.as_str().map(String::from))
+        .unwrap_or_default()
+}
+
+fn get_token_usage(usage_metadata: &UsageMetadata) -> TokenUsage {
+    TokenUsage {
+        input_tokens: usage_metadata.prompt_token_count.unwrap_or_default(),
+        output_tokens: usage_metadata.candidates_token_count.unwrap_or_default(),
+        cached_tokens: usage_metadata
+            .cached_content_token_count
+            .unwrap_or_default(),
+        thinking_tokens: usage_metadata.thoughts_token_count.unwrap_or_default(),
+    }
+}
+
 fn generate_gemini_part_meta_value(part_ref: &GeminiPart) -> Value {
     let mut inner_data = json!({});
     // Thought
@@ -345,3 +555,97 @@ fn get_gemini_meta_fields(
 
     (thought, thought_signature, metadata, part_metadata)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::gemini::HarmBlockThreshold;
+    use crate::api::transport::tests::get_fixture_path;
+    use crate::api::transport::ReplayTransport;
+    use crate::llm::base::{merge_chat_messages, ChatMessage};
+    use crate::llm::test_support::{collect_replies, get_history};
+
+    #[tokio::test]
+    async fn gemini_safety_blocked_test() {
+        let gemini = Gemini {
+            model_id: String::from("gemini-2.0-flash"),
+            tools: vec![],
+            api_key: String::from("fake-api-key"),
+            tool_config: None,
+            safety_settings: Some(vec![SafetySetting {
+                category: HarmCategory::DangerousContent,
+                threshold: HarmBlockThreshold::BlockLowAndAbove,
+            }]),
+            system_prompt: None,
+            generation_config: GenerationConfig::default(),
+            transport: Arc::new(
+                ReplayTransport::load(&get_fixture_path("gemini_safety_blocked.json")).unwrap(),
+            ),
+            file_registry: Arc::new(Mutex::new(GeminiFileRegistry::load(None))),
+        };
+
+        // The prompt itself is refused, there are no candidates at all
+        let emitted = collect_replies(&gemini, get_history("How do I make a pipe bomb?")).await;
+        let blocked = emitted[0].blocked.as_ref().unwrap();
+        assert_eq!(blocked.reason, "SAFETY");
+        assert_eq!(blocked.categories, vec!["dangerous content"]);
+        assert_eq!(
+            blocked.message,
+            "Blocked because the prompt was flagged for dangerous content"
+        );
+        assert!(emitted.last().unwrap().done);
+
+        // The reply is cut off halfway
+        let emitted = collect_replies(&gemini, get_history("How do I pick a lock?")).await;
+        assert!(emitted[0].blocked.is_none());
+        let blocked = emitted[1].blocked.as_ref().unwrap();
+        assert_eq!(blocked.reason, "SAFETY");
+        assert_eq!(
+            blocked.message,
+            "Blocked because the response was flagged for dangerous content"
+        );
+    }
+
+    #[test]
+    fn category_list_test() {
+        assert_eq!(get_category_list(&[]), "safety reasons");
+        assert_eq!(
+            get_category_list(&[
+                String::from("harassment"),
+                String::from("hate speech"),
+                String::from("dangerous content"),
+            ]),
+            "harassment, hate speech and dangerous content"
+        );
+    }
+
+    // The signature comes with the last chunk of a text part
+    #[test]
+    fn merged_meta_test() {
+        let get_chunk = |text: &str, meta: Value| {
+            vec![ChatMessage {
+                role: Role::Assistant,
+                content: ChatMessageContent::Text {
+                    text: text.to_string(),
+                    _meta: Some(meta),
+                },
+                images: None,
+            }]
+        };
+
+        let mut merged = vec![];
+        merge_chat_messages(&mut merged, get_chunk("Hel", json!({ GEMINI_META: {} })));
+        merge_chat_messages(
+            &mut merged,
+            get_chunk("lo", json!({ GEMINI_META: {"thoughtSignature": "c2ln"} })),
+        );
+        merge_chat_messages(&mut merged, get_chunk("!", json!({ GEMINI_META: {} })));
+
+        let ChatMessageContent::Text { text, _meta } = &merged[0].content else {
+            panic!("Expected text");
+        };
+        assert_eq!(text, "Hello!");
+        let (_, thought_signature, _, _) = get_gemini_meta_fields(_meta.clone());
+        assert_eq!(thought_signature.as_deref(), Some("c2ln"));
+    }
+}
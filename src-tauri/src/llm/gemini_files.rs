@@ -0,0 +1,309 @@
+use crate::api::gemini_files::{upload_gemini_file, wait_for_gemini_file};
+use crate::api::transport::HttpTransport;
+use crate::error::NexaError;
+use crate::llm::cache::{get_file_stamp, now_secs, FileStamp};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::sync::Mutex;
+
+pub(crate) static GEMINI_FILES_FILENAME: &str = "gemini-files.json";
+
+// Uploaded files are deleted by Gemini after 48 hours, unless the upload says
+// otherwise. Entries are treated as stale an hour early so a long conversation
+// never references a file that disappears mid-request.
+static GEMINI_FILE_TTL_SECS: u64 = 60 * 60 * 48;
+static GEMINI_FILE_EXPIRY_MARGIN_SECS: u64 = 60 * 60;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiFileEntry {
+    pub name: String,
+    pub uri: String,
+    pub mime_type: String,
+    pub display_name: String,
+    pub uploaded_at: u64,
+    pub expires_at: u64,
+    // Where the content was last read from, so an unchanged file isn't
+    // hashed again
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stamp: Option<FileStamp>,
+}
+
+// A file can only be read with a key of the project that uploaded it, so the
+// entries are kept apart per key. Only a hash of the key is stored.
+fn get_registry_key(api_key: &str, hash: &str) -> String {
+    format!("{}:{}", get_api_key_fingerprint(api_key), hash)
+}
+
+fn get_api_key_fingerprint(api_key: &str) -> String {
+    format!("{:x}", Sha256::digest(api_key.as_bytes()))[..16].to_string()
+}
+
+// Remembers which local files already live on Gemini, keyed by the sha256 of
+// their content and the key they were uploaded with, so each attachment is
+// uploaded once instead of every turn
+pub(crate) struct GeminiFileRegistry {
+    entries: HashMap<String, GeminiFileEntry>,
+    path: Option<PathBuf>,
+}
+
+impl GeminiFileRegistry {
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let entries = path
+            .as_ref()
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+
+        GeminiFileRegistry { entries, path }
+    }
+
+    pub fn get(&mut self, api_key: &str, hash: &str) -> Option<GeminiFileEntry> {
+        let key = get_registry_key(api_key, hash);
+        let entry = self.entries.get(&key)?;
+
+        if now_secs() + GEMINI_FILE_EXPIRY_MARGIN_SECS >= entry.expires_at {
+            self.entries.remove(&key);
+            return None;
+        }
+
+        Some(entry.clone())
+    }
+
+    // The content hash of a file that hasn't changed since it was last read
+    pub fn get_hash(&self, api_key: &str, path: &str, stamp: &FileStamp) -> Option<String> {
+        let prefix = get_registry_key(api_key, "");
+        self.entries.iter().find_map(|(key, entry)| {
+            let matches =
+                entry.path.as_deref() == Some(path) && entry.stamp.as_ref() == Some(stamp);
+            match key.strip_prefix(&prefix) {
+                Some(hash) if matches => Some(hash.to_string()),
+                _ => None,
+            }
+        })
+    }
+
+    // The entry is kept in memory even when writing it out fails
+    pub fn insert(
+        &mut self,
+        api_key: &str,
+        hash: &str,
+        entry: GeminiFileEntry,
+    ) -> Result<(), NexaError> {
+        self.entries.insert(get_registry_key(api_key, hash), entry);
+
+        self.save()
+    }
+
+    pub fn remove_by_name(&mut self, api_key: &str, name: &str) -> Result<(), NexaError> {
+        let prefix = get_registry_key(api_key, "");
+        self.entries
+            .retain(|key, entry| !(key.starts_with(&prefix) && entry.name == name));
+
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), NexaError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_vec(&self.entries)?)?;
+
+        Ok(())
+    }
+}
+
+// Returns the Gemini file for a local path, uploading it only when the content
+// has never been uploaded or the previous upload is about to expire. The file
+// is only hashed when its size or modification time changed.
+pub(crate) async fn resolve_gemini_file(
+    transport: &dyn HttpTransport,
+    api_key: &str,
+    registry: &Mutex<GeminiFileRegistry>,
+    path: &str,
+    mime_type: &str,
+    display_name: Option<String>,
+) -> Result<GeminiFileEntry, NexaError> {
+    let stamp = get_file_stamp(path)?;
+    {
+        let mut registry = registry.lock().await;
+        if let Some(hash) = registry.get_hash(api_key, path, &stamp) {
+            if let Some(entry) = registry.get(api_key, &hash) {
+                return Ok(entry);
+            }
+        }
+    }
+
+    let bytes = fs::read(path)?;
+    let hash = format!("{:x}", Sha256::digest(&bytes));
+
+    let cached_entry = registry.lock().await.get(api_key, &hash);
+    if let Some(mut entry) = cached_entry {
+        entry.path = Some(path.to_string());
+        entry.stamp = Some(stamp);
+        registry
+            .lock()
+            .await
+            .insert(api_key, &hash, entry.clone())?;
+        return Ok(entry);
+    }
+
+    let display_name = display_name.unwrap_or_else(|| {
+        Path::new(path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    });
+
+    let file = upload_gemini_file(transport, api_key, bytes, mime_type, &display_name).await?;
+    let file = wait_for_gemini_file(transport, api_key, file).await?;
+
+    let uploaded_at = now_secs();
+    let entry = GeminiFileEntry {
+        name: file.name,
+        uri: file.uri,
+        mime_type: file.mime_type,
+        display_name,
+        uploaded_at,
+        expires_at: file
+            .expiration_time
+            .as_deref()
+            .and_then(parse_utc_timestamp)
+            .unwrap_or(uploaded_at + GEMINI_FILE_TTL_SECS),
+        path: Some(path.to_string()),
+        stamp: Some(stamp),
+    };
+    registry
+        .lock()
+        .await
+        .insert(api_key, &hash, entry.clone())?;
+
+    Ok(entry)
+}
+
+// Seconds since the epoch of an RFC 3339 timestamp in UTC, the way Gemini
+// sends them, e.g. "2025-06-04T18:00:00.000000Z"
+fn parse_utc_timestamp(timestamp: &str) -> Option<u64> {
+    let (date, time) = timestamp.strip_suffix('Z')?.split_once('T')?;
+    let mut date = date.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+    let mut time = time.splitn(3, ':');
+    let hours: i64 = time.next()?.parse().ok()?;
+    let minutes: i64 = time.next()?.parse().ok()?;
+    let seconds: f64 = time.next()?.parse().ok()?;
+
+    // Days since 1970-01-01 of a proleptic Gregorian date, counted in 400
+    // year eras that start on March 1st
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    u64::try_from(days * 86_400 + hours * 3_600 + minutes * 60 + seconds as i64).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::transport::tests::get_fixture_path;
+    use crate::api::transport::ReplayTransport;
+
+    #[tokio::test]
+    async fn resolve_gemini_file_test() {
+        let path = std::env::temp_dir().join(format!("nexa-report-{}.pdf", std::process::id()));
+        fs::write(&path, b"%PDF-1.4 test").unwrap();
+        let path_str = path.to_str().unwrap();
+
+        // The cassette holds exactly one upload, so a second upload would fail
+        let transport =
+            ReplayTransport::load(&get_fixture_path("gemini_file_upload.json")).unwrap();
+        let registry = Mutex::new(GeminiFileRegistry::load(None));
+
+        let entry = resolve_gemini_file(
+            &transport,
+            "test-key",
+            &registry,
+            path_str,
+            "application/pdf",
+            Some(String::from("report.pdf")),
+        )
+        .await
+        .unwrap();
+        assert_eq!(entry.name, "files/report-123");
+        // The expiry is the one Gemini sent
+        assert_eq!(entry.expires_at, 4_084_279_200);
+        let stamp = get_file_stamp(path_str).unwrap();
+        assert!(registry
+            .lock()
+            .await
+            .get_hash("test-key", path_str, &stamp)
+            .is_some());
+
+        let cached_entry = resolve_gemini_file(
+            &transport,
+            "test-key",
+            &registry,
+            path_str,
+            "application/pdf",
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(cached_entry, entry);
+
+        // Another key can't read that upload, it needs one of its own
+        assert!(resolve_gemini_file(
+            &transport,
+            "other-key",
+            &registry,
+            path_str,
+            "application/pdf",
+            None,
+        )
+        .await
+        .is_err());
+
+        // Stale uploads are dropped so the next resolve uploads again
+        for entry in registry.lock().await.entries.values_mut() {
+            entry.expires_at = now_secs() + 60;
+        }
+        let result = resolve_gemini_file(
+            &transport,
+            "test-key",
+            &registry,
+            path_str,
+            "application/pdf",
+            None,
+        )
+        .await;
+        let _ = fs::remove_file(&path);
+
+        assert!(result.is_err());
+        assert!(registry.lock().await.entries.is_empty());
+    }
+
+    #[test]
+    fn parse_utc_timestamp_test() {
+        assert_eq!(parse_utc_timestamp("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(
+            parse_utc_timestamp("2025-06-04T18:00:00.000000Z"),
+            Some(1_749_060_000)
+        );
+        assert_eq!(
+            parse_utc_timestamp("2024-02-29T12:30:15Z"),
+            Some(1_709_209_815)
+        );
+        assert_eq!(parse_utc_timestamp("2025-06-04T18:00:00+02:00"), None);
+    }
+}
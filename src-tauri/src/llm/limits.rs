@@ -0,0 +1,635 @@
+use crate::error::NexaError;
+use crate::llm::base::{ChatHistory, EmittedChatMessage, Provider, TokenUsage, LLM};
+use async_stream::stream;
+use futures_util::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+use tokio::time::sleep;
+
+pub(crate) static RATE_LIMITS_FILENAME: &str = "rate-limits.json";
+
+static SECS_PER_DAY: u64 = 60 * 60 * 24;
+
+// Limits for a whole provider, or for one model when `model` is set. A request
+// has to fit into every limit that matches it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimit {
+    pub provider: Provider,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens_per_minute: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_day: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens_per_day: Option<u64>,
+}
+
+impl RateLimit {
+    fn matches(&self, provider: &Provider, model: &str) -> bool {
+        self.provider == *provider && self.model.as_deref().is_none_or(|name| name == model)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyUsage {
+    pub provider: Provider,
+    pub model: String,
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct QuotaStatus {
+    pub limit: RateLimit,
+    pub requests_today: u64,
+    pub tokens_today: u64,
+    // None where the limit doesn't set the matching value
+    pub remaining_requests_today: Option<u64>,
+    pub remaining_tokens_today: Option<u64>,
+    pub remaining_requests_this_minute: Option<u64>,
+    pub remaining_tokens_this_minute: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct QuotaReport {
+    pub limits: Vec<QuotaStatus>,
+    pub usage: Vec<DailyUsage>,
+    // Unix seconds, daily counters start over at midnight UTC like Gemini's
+    pub resets_at: u64,
+}
+
+// Starts full and refills continuously, so a burst up to the per minute limit
+// goes out at once and after that requests are spaced evenly. It may go
+// below zero when a reply used more tokens than estimated up front.
+#[derive(Clone, Debug)]
+struct TokenBucket {
+    capacity: f64,
+    available: f64,
+    refill_per_sec: f64,
+    updated_at: Instant,
+}
+
+impl TokenBucket {
+    fn per_minute(limit: u64, now: Instant) -> Self {
+        TokenBucket {
+            capacity: limit as f64,
+            available: limit as f64,
+            refill_per_sec: limit as f64 / 60.0,
+            updated_at: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.available = (self.available + elapsed * self.refill_per_sec).min(self.capacity);
+        self.updated_at = now;
+    }
+
+    // Anything bigger than the bucket would wait forever, it goes out once
+    // the bucket is full instead
+    fn get_wait(&self, amount: u64) -> Duration {
+        let missing = (amount as f64).min(self.capacity) - self.available;
+        match missing > 0.0 && self.refill_per_sec > 0.0 {
+            true => Duration::from_secs_f64(missing / self.refill_per_sec),
+            false => Duration::ZERO,
+        }
+    }
+
+    fn take(&mut self, amount: f64) {
+        self.available -= amount;
+    }
+}
+
+#[derive(Clone, Debug)]
+struct LimitBuckets {
+    requests: Option<TokenBucket>,
+    tokens: Option<TokenBucket>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct RateLimitsFile {
+    limits: Vec<RateLimit>,
+    day: u64,
+    usage: Vec<DailyUsage>,
+}
+
+// The per minute buckets only live in memory, the daily counters are saved
+// so a restart doesn't hand out the day's quota again
+pub(crate) struct RateLimiter {
+    limits: Vec<RateLimit>,
+    buckets: Vec<LimitBuckets>,
+    day: u64,
+    usage: Vec<DailyUsage>,
+    path: Option<PathBuf>,
+}
+
+impl RateLimiter {
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let limits_file = path
+            .as_ref()
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|bytes| serde_json::from_slice::<RateLimitsFile>(&bytes).ok())
+            .unwrap_or_default();
+
+        let mut limiter = RateLimiter {
+            buckets: get_buckets(&limits_file.limits, Instant::now()),
+            limits: limits_file.limits,
+            day: limits_file.day,
+            usage: limits_file.usage,
+            path,
+        };
+        limiter.roll_over(get_today());
+
+        limiter
+    }
+
+    pub fn get_limits(&self) -> Vec<RateLimit> {
+        self.limits.clone()
+    }
+
+    pub fn set_limits(&mut self, limits: Vec<RateLimit>) -> Result<(), NexaError> {
+        self.buckets = get_buckets(&limits, Instant::now());
+        self.limits = limits;
+        self.save()
+    }
+
+    // Takes a request and `tokens` from every matching limit if they all have
+    // room, otherwise returns how long to wait before trying again. A spent
+    // daily quota is an error, waiting for it would take hours.
+    pub fn try_acquire(
+        &mut self,
+        provider: &Provider,
+        model: &str,
+        tokens: u64,
+        now: Instant,
+    ) -> Result<Option<Duration>, NexaError> {
+        self.roll_over(get_today());
+
+        let mut wait = Duration::ZERO;
+        for (limit, buckets) in self.limits.iter().zip(self.buckets.iter_mut()) {
+            if !limit.matches(provider, model) {
+                continue;
+            }
+
+            let (requests_today, tokens_today) = get_usage_today(&self.usage, limit);
+            if limit
+                .requests_per_day
+                .is_some_and(|max| requests_today >= max)
+                || limit.tokens_per_day.is_some_and(|max| tokens_today >= max)
+            {
+                return Err(NexaError::RateLimit(format!(
+                    "Daily quota for {} reached",
+                    get_limit_name(limit)
+                )));
+            }
+
+            if let Some(bucket) = buckets.requests.as_mut() {
+                bucket.refill(now);
+                wait = wait.max(bucket.get_wait(1));
+            }
+            if let Some(bucket) = buckets.tokens.as_mut() {
+                bucket.refill(now);
+                wait = wait.max(bucket.get_wait(tokens));
+            }
+        }
+
+        if !wait.is_zero() {
+            return Ok(Some(wait));
+        }
+
+        for (limit, buckets) in self.limits.iter().zip(self.buckets.iter_mut()) {
+            if !limit.matches(provider, model) {
+                continue;
+            }
+            if let Some(bucket) = buckets.requests.as_mut() {
+                bucket.take(1.0);
+            }
+            if let Some(bucket) = buckets.tokens.as_mut() {
+                bucket.take(tokens as f64);
+            }
+        }
+        self.get_daily_usage(provider, model).requests += 1;
+
+        Ok(None)
+    }
+
+    // Settles a request once the reply is in: the token buckets are corrected
+    // by what the provider reported against the estimate taken up front
+    pub fn record(
+        &mut self,
+        provider: &Provider,
+        model: &str,
+        reserved_tokens: u64,
+        usage: Option<&TokenUsage>,
+    ) -> Result<(), NexaError> {
+        self.roll_over(get_today());
+
+        let used_tokens = usage.map(get_total_tokens).unwrap_or(0);
+        for (limit, buckets) in self.limits.iter().zip(self.buckets.iter_mut()) {
+            if !limit.matches(provider, model) {
+                continue;
+            }
+            if let Some(bucket) = buckets.tokens.as_mut() {
+                bucket.take(used_tokens as f64 - reserved_tokens as f64);
+            }
+        }
+
+        if let Some(usage) = usage {
+            let daily_usage = self.get_daily_usage(provider, model);
+            daily_usage.input_tokens += usage.input_tokens;
+            daily_usage.output_tokens += usage.output_tokens + usage.thinking_tokens;
+        }
+
+        self.save()
+    }
+
+    pub fn get_quota_report(&mut self, now: Instant) -> QuotaReport {
+        let today = get_today();
+        self.roll_over(today);
+
+        let limits = self
+            .limits
+            .iter()
+            .zip(self.buckets.iter_mut())
+            .map(|(limit, buckets)| {
+                let (requests_today, tokens_today) = get_usage_today(&self.usage, limit);
+                let remaining = |bucket: &mut TokenBucket| {
+                    bucket.refill(now);
+                    bucket.available.max(0.0) as u64
+                };
+
+                QuotaStatus {
+                    limit: limit.clone(),
+                    requests_today,
+                    tokens_today,
+                    remaining_requests_today: limit
+                        .requests_per_day
+                        .map(|max| max.saturating_sub(requests_today)),
+                    remaining_tokens_today: limit
+                        .tokens_per_day
+                        .map(|max| max.saturating_sub(tokens_today)),
+                    remaining_requests_this_minute: buckets.requests.as_mut().map(remaining),
+                    remaining_tokens_this_minute: buckets.tokens.as_mut().map(remaining),
+                }
+            })
+            .collect();
+
+        QuotaReport {
+            limits,
+            usage: self.usage.clone(),
+            resets_at: (today + 1) * SECS_PER_DAY,
+        }
+    }
+
+    fn get_daily_usage(&mut self, provider: &Provider, model: &str) -> &mut DailyUsage {
+        let index = match self
+            .usage
+            .iter()
+            .position(|usage| usage.provider == *provider && usage.model == model)
+        {
+            Some(index) => index,
+            None => {
+                self.usage.push(DailyUsage {
+                    provider: provider.clone(),
+                    model: model.to_string(),
+                    requests: 0,
+                    input_tokens: 0,
+                    output_tokens: 0,
+                });
+                self.usage.len() - 1
+            }
+        };
+
+        &mut self.usage[index]
+    }
+
+    fn roll_over(&mut self, today: u64) {
+        if self.day != today {
+            self.day = today;
+            self.usage.clear();
+        }
+    }
+
+    fn save(&self) -> Result<(), NexaError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let limits_file = json!({
+            "limits": self.limits,
+            "day": self.day,
+            "usage": self.usage,
+        });
+        fs::write(path, serde_json::to_vec(&limits_file)?)?;
+
+        Ok(())
+    }
+}
+
+fn get_buckets(limits: &[RateLimit], now: Instant) -> Vec<LimitBuckets> {
+    limits
+        .iter()
+        .map(|limit| LimitBuckets {
+            requests: limit
+                .requests_per_minute
+                .map(|max| TokenBucket::per_minute(max, now)),
+            tokens: limit
+                .tokens_per_minute
+                .map(|max| TokenBucket::per_minute(max, now)),
+        })
+        .collect()
+}
+
+fn get_usage_today(usage: &[DailyUsage], limit: &RateLimit) -> (u64, u64) {
+    usage
+        .iter()
+        .filter(|usage| limit.matches(&usage.provider, &usage.model))
+        .fold((0, 0), |(requests, tokens), usage| {
+            (
+                requests + usage.requests,
+                tokens + usage.input_tokens + usage.output_tokens,
+            )
+        })
+}
+
+fn get_limit_name(limit: &RateLimit) -> String {
+    match &limit.model {
+        Some(model) => format!("{:?} {}", limit.provider, model),
+        None => format!("{:?}", limit.provider),
+    }
+}
+
+fn get_total_tokens(usage: &TokenUsage) -> u64 {
+    usage.input_tokens + usage.output_tokens + usage.thinking_tokens
+}
+
+fn get_today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+        / SECS_PER_DAY
+}
+
+// Rough guess of the prompt size, about four characters per token, used to
+// hold back requests before the provider reports the real count
+pub(crate) fn estimate_tokens(history: &ChatHistory) -> u64 {
+    let chars: usize = history
+        .messages
+        .iter()
+        .map(|message| {
+            serde_json::to_string(&message.content)
+                .map(|content| content.len())
+                .unwrap_or(0)
+        })
+        .sum();
+
+    (chars / 4) as u64 + 1
+}
+
+// The tokens taken up front for a request. It's settled with the usage the
+// reply reported, or with what was seen so far when the stream is dropped
+// before the end.
+struct Reservation {
+    limiter: Arc<Mutex<RateLimiter>>,
+    provider: Provider,
+    model: String,
+    reserved_tokens: u64,
+    usage: Option<TokenUsage>,
+    settled: bool,
+}
+
+impl Reservation {
+    async fn settle(&mut self) -> Result<(), NexaError> {
+        self.settled = true;
+        self.limiter.lock().await.record(
+            &self.provider,
+            &self.model,
+            self.reserved_tokens,
+            self.usage.as_ref(),
+        )
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if self.settled {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+
+        let limiter = self.limiter.clone();
+        let provider = self.provider.clone();
+        let model = std::mem::take(&mut self.model);
+        let reserved_tokens = self.reserved_tokens;
+        let usage = self.usage.take();
+        runtime.spawn(async move {
+            let _ = limiter
+                .lock()
+                .await
+                .record(&provider, &model, reserved_tokens, usage.as_ref());
+        });
+    }
+}
+
+// Holds a request back until it fits into the configured limits instead of
+// letting the provider answer with a 429, then counts the usage the reply
+// reports. Cache hits never get here, so they don't use up any quota.
+pub(crate) struct RateLimited<L: LLM> {
+    pub(crate) inner: L,
+    pub(crate) limiter: Arc<Mutex<RateLimiter>>,
+    pub(crate) provider: Provider,
+    pub(crate) model: String,
+}
+
+impl<L: LLM> LLM for RateLimited<L> {
+    async fn stream_chat(
+        &self,
+        history: ChatHistory,
+    ) -> Result<impl Stream<Item = Result<EmittedChatMessage, NexaError>>, NexaError> {
+        let reserved_tokens = estimate_tokens(&history);
+        loop {
+            let wait = self.limiter.lock().await.try_acquire(
+                &self.provider,
+                &self.model,
+                reserved_tokens,
+                Instant::now(),
+            )?;
+            match wait {
+                Some(wait) => sleep(wait).await,
+                None => break,
+            }
+        }
+
+        let mut reservation = Reservation {
+            limiter: self.limiter.clone(),
+            provider: self.provider.clone(),
+            model: self.model.clone(),
+            reserved_tokens,
+            usage: None,
+            settled: false,
+        };
+        let inner_stream = self.inner.stream_chat(history).await?;
+
+        Ok(stream! {
+            let mut inner_stream = Box::pin(inner_stream);
+
+            while let Some(item) = inner_stream.next().await {
+                if let Ok(chunk) = &item {
+                    if chunk.usage.is_some() {
+                        reservation.usage = chunk.usage.clone();
+                    }
+                }
+                yield item;
+            }
+
+            if let Err(e) = reservation.settle().await {
+                yield Err(e);
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::mock::{Mock, MOCK_ECHO_MODEL};
+    use crate::llm::test_support::get_history;
+
+    fn get_limit(model: Option<&str>) -> RateLimit {
+        RateLimit {
+            provider: Provider::Gemini,
+            model: model.map(String::from),
+            requests_per_minute: Some(2),
+            tokens_per_minute: Some(1000),
+            requests_per_day: Some(3),
+            tokens_per_day: None,
+        }
+    }
+
+    #[test]
+    fn token_bucket_test() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::per_minute(60, now);
+        assert_eq!(bucket.get_wait(60), Duration::ZERO);
+
+        bucket.take(60.0);
+        assert_eq!(bucket.get_wait(1), Duration::from_secs(1));
+        // More than the whole bucket only waits for a full one
+        assert_eq!(bucket.get_wait(500), Duration::from_secs(60));
+
+        bucket.refill(now + Duration::from_secs(30));
+        assert_eq!(bucket.available, 30.0);
+    }
+
+    #[test]
+    fn rate_limiter_test() {
+        let mut limiter = RateLimiter::load(None);
+        limiter
+            .set_limits(vec![get_limit(None), get_limit(Some("gemini-2.5-pro"))])
+            .unwrap();
+        let now = Instant::now();
+
+        // Two requests a minute go out right away, the third one has to wait
+        assert_eq!(
+            limiter
+                .try_acquire(&Provider::Gemini, "gemini-2.5-flash", 100, now)
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            limiter
+                .try_acquire(&Provider::Gemini, "gemini-2.5-flash", 100, now)
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            limiter
+                .try_acquire(&Provider::Gemini, "gemini-2.5-flash", 100, now)
+                .unwrap(),
+            Some(Duration::from_secs(30))
+        );
+        // Other providers aren't limited
+        assert_eq!(
+            limiter
+                .try_acquire(&Provider::Ollama, "llama3.2", 100, now)
+                .unwrap(),
+            None
+        );
+
+        let usage = TokenUsage {
+            input_tokens: 400,
+            output_tokens: 300,
+            ..Default::default()
+        };
+        limiter
+            .record(&Provider::Gemini, "gemini-2.5-flash", 100, Some(&usage))
+            .unwrap();
+
+        let later = now + Duration::from_secs(60);
+        let report = limiter.get_quota_report(later);
+        let provider_status = &report.limits[0];
+        assert_eq!(provider_status.requests_today, 2);
+        assert_eq!(provider_status.tokens_today, 700);
+        assert_eq!(provider_status.remaining_requests_today, Some(1));
+        assert_eq!(provider_status.remaining_requests_this_minute, Some(2));
+        // The reported 700 tokens replaced the 100 estimated for that request
+        assert_eq!(provider_status.remaining_tokens_this_minute, Some(1000));
+        assert_eq!(report.limits[1].requests_today, 0);
+
+        assert!(limiter
+            .try_acquire(&Provider::Gemini, "gemini-2.5-flash", 100, later)
+            .unwrap()
+            .is_none());
+        assert!(limiter
+            .try_acquire(&Provider::Gemini, "gemini-2.5-flash", 100, later)
+            .is_err_and(|e| matches!(e, NexaError::RateLimit(_))));
+    }
+
+    #[tokio::test]
+    async fn dropped_stream_test() {
+        let mut limiter = RateLimiter::load(None);
+        limiter.set_limits(vec![get_limit(None)]).unwrap();
+        let limiter = Arc::new(Mutex::new(limiter));
+        let rate_limited = RateLimited {
+            inner: Mock::from_model(MOCK_ECHO_MODEL).unwrap(),
+            limiter: limiter.clone(),
+            provider: Provider::Gemini,
+            model: String::from("gemini-2.5-flash"),
+        };
+        let mut stream = Box::pin(
+            rate_limited
+                .stream_chat(get_history("Hello there, how are you"))
+                .await
+                .unwrap(),
+        );
+        assert!(stream.next().await.unwrap().is_ok());
+        drop(stream);
+        sleep(Duration::from_millis(10)).await;
+
+        // The tokens reserved for the request are given back
+        let report = limiter.lock().await.get_quota_report(Instant::now());
+        assert_eq!(report.limits[0].requests_today, 1);
+        assert_eq!(report.limits[0].remaining_tokens_this_minute, Some(1000));
+    }
+}
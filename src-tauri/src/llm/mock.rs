@@ -0,0 +1,420 @@
+use crate::error::NexaError;
+use crate::llm::base::{
+    ChatHistory, ChatMessage, ChatMessageContent, EmittedChatMessage, Role, TokenUsage, LLM,
+};
+use futures::stream;
+use futures_util::Stream;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+use tokio::time::{sleep, Duration};
+
+pub(crate) static MOCK_ECHO_MODEL: &str = "echo";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MockScript {
+    pub responses: Vec<MockResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MockResponse {
+    pub steps: Vec<MockStep>,
+    // Reported on the final chunk, "stop" unless the script says otherwise
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type")]
+#[serde(rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum MockStep {
+    // Streams back the text of the latest user message
+    Echo {
+        chunk_size: Option<usize>,
+        delay_ms: Option<u64>,
+    },
+    Text {
+        text: String,
+        chunk_size: Option<usize>,
+        delay_ms: Option<u64>,
+    },
+    FunctionCall {
+        id: Option<String>,
+        name: String,
+        args: Option<Value>,
+    },
+    // Fails the stream at this point, later steps are never reached
+    Error {
+        message: String,
+    },
+}
+
+impl MockScript {
+    pub fn echo() -> Self {
+        MockScript {
+            responses: vec![MockResponse {
+                steps: vec![MockStep::Echo {
+                    chunk_size: Some(4),
+                    delay_ms: Some(20),
+                }],
+                finish_reason: None,
+            }],
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, NexaError> {
+        let bytes = fs::read(path).map_err(|e| {
+            NexaError::Mock(format!("Can't read mock script {}: {}", path.display(), e))
+        })?;
+
+        serde_json::from_slice(&bytes)
+            .map_err(|e| NexaError::Mock(format!("Invalid mock script {}: {}", path.display(), e)))
+    }
+}
+
+pub(crate) struct Mock {
+    pub(crate) script: MockScript,
+}
+
+impl Mock {
+    // The model id is either "echo" or the path of a script file
+    pub(crate) fn from_model(model: &str) -> Result<Self, NexaError> {
+        let script = match model {
+            m if m == MOCK_ECHO_MODEL => MockScript::echo(),
+            path => MockScript::load(Path::new(path))?,
+        };
+
+        Ok(Mock { script })
+    }
+}
+
+impl LLM for Mock {
+    async fn stream_chat(
+        &self,
+        mut history: ChatHistory,
+    ) -> Result<impl Stream<Item = Result<EmittedChatMessage, NexaError>>, NexaError> {
+        let last_message = history
+            .messages
+            .pop()
+            .ok_or(NexaError::Mock("Empty chat history".to_string()))?;
+        let id = last_message.id;
+
+        // Every user or function message starts a new model turn. Turn n plays
+        // response n, and the last response keeps playing once the script runs out.
+        let turn = history
+            .messages
+            .iter()
+            .filter(|message| matches!(message.role, Role::User | Role::Function))
+            .count()
+            .saturating_sub(1);
+        let response = self
+            .script
+            .responses
+            .get(turn)
+            .or(self.script.responses.last())
+            .ok_or(NexaError::Mock("Mock script without responses".to_string()))?;
+
+        let last_user_text = history
+            .messages
+            .iter()
+            .rev()
+            .find_map(|message| match (&message.role, &message.content) {
+                (Role::User, ChatMessageContent::Text { text, .. }) => Some(text.clone()),
+                _ => None,
+            })
+            .unwrap_or_default();
+        let input_tokens = history
+            .messages
+            .iter()
+            .map(|message| match &message.content {
+                ChatMessageContent::Text { text, .. } => count_words(text),
+                _ => 0,
+            })
+            .sum();
+
+        let finish_reason = response
+            .finish_reason
+            .clone()
+            .unwrap_or(String::from("stop"));
+
+        let mut events: VecDeque<(u64, Result<ChatMessage, NexaError>)> = VecDeque::new();
+        let mut output_tokens = 0;
+        for step in response.steps.iter() {
+            match step {
+                MockStep::Echo {
+                    chunk_size,
+                    delay_ms,
+                } => {
+                    output_tokens += count_words(&last_user_text);
+                    push_text_events(&mut events, &last_user_text, *chunk_size, *delay_ms);
+                }
+                MockStep::Text {
+                    text,
+                    chunk_size,
+                    delay_ms,
+                } => {
+                    output_tokens += count_words(text);
+                    push_text_events(&mut events, text, *chunk_size, *delay_ms);
+                }
+                MockStep::FunctionCall { id, name, args } => events.push_back((
+                    0,
+                    Ok(ChatMessage {
+                        role: Role::Assistant,
+                        content: ChatMessageContent::FunctionCallRequest {
+                            id: id.clone(),
+                            name: name.clone(),
+                            args: args.clone(),
+                            _meta: None,
+                        },
+                        images: None,
+                    }),
+                )),
+                MockStep::Error { message } => {
+                    events.push_back((0, Err(NexaError::Mock(message.clone()))));
+                    break;
+                }
+            }
+        }
+
+        Ok(stream::unfold(
+            (events, false),
+            move |(mut events, finished)| {
+                let id = id.clone();
+                let finish_reason = finish_reason.clone();
+                async move {
+                    if finished {
+                        return None;
+                    }
+
+                    let Some((delay_ms, event)) = events.pop_front() else {
+                        // Closing chunk so the UI sees the same shape as real providers
+                        let done = EmittedChatMessage {
+                            id,
+                            message: vec![],
+                            done: true,
+                            usage: Some(TokenUsage {
+                                input_tokens,
+                                output_tokens,
+                                ..Default::default()
+                            }),
+                            blocked: None,
+                            finish_reason: Some(finish_reason),
+                            cost: None,
+                            error: None,
+                        };
+                        return Some((Ok(done), (events, true)));
+                    };
+
+                    if delay_ms > 0 {
+                        sleep(Duration::from_millis(delay_ms)).await;
+                    }
+
+                    match event {
+                        Ok(message) => {
+                            let chunk = EmittedChatMessage {
+                                id,
+                                message: vec![message],
+                                done: false,
+                                usage: None,
+                                blocked: None,
+                                finish_reason: None,
+                                cost: None,
+                                error: None,
+                            };
+                            Some((Ok(chunk), (events, false)))
+                        }
+                        Err(e) => Some((Err(e), (events, true))),
+                    }
+                }
+            },
+        ))
+    }
+}
+
+fn push_text_events(
+    events: &mut VecDeque<(u64, Result<ChatMessage, NexaError>)>,
+    text: &str,
+    chunk_size: Option<usize>,
+    delay_ms: Option<u64>,
+) {
+    let chars: Vec<char> = text.chars().collect();
+    let chunk_size = chunk_size.unwrap_or(chars.len()).max(1);
+
+    for chunk in chars.chunks(chunk_size) {
+        events.push_back((
+            delay_ms.unwrap_or_default(),
+            Ok(ChatMessage {
+                role: Role::Assistant,
+                content: ChatMessageContent::Text {
+                    text: chunk.iter().collect(),
+                    _meta: None,
+                },
+                images: None,
+            }),
+        ));
+    }
+}
+
+// Rough stand-in for a tokenizer, good enough to exercise the usage UI
+fn count_words(text: &str) -> u64 {
+    text.split_whitespace().count() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::base::merge_chat_messages;
+    use crate::llm::test_support::{
+        collect_chat, collect_replies, get_history, get_message, get_text,
+    };
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    #[test]
+    fn mock_script_parsing_test() {
+        let raw_script = json!({
+            "responses": [{
+                "steps": [
+                    {"type": "text", "text": "Hi", "chunkSize": 1, "delayMs": 5},
+                    {"type": "functionCall", "name": "weather-_-get_forecast", "args": {"city": "Boston"}},
+                    {"type": "error", "message": "Quota exceeded"}
+                ]
+            }]
+        });
+
+        let expected_script = MockScript {
+            responses: vec![MockResponse {
+                steps: vec![
+                    MockStep::Text {
+                        text: String::from("Hi"),
+                        chunk_size: Some(1),
+                        delay_ms: Some(5),
+                    },
+                    MockStep::FunctionCall {
+                        id: None,
+                        name: String::from("weather-_-get_forecast"),
+                        args: Some(json!({"city": "Boston"})),
+                    },
+                    MockStep::Error {
+                        message: String::from("Quota exceeded"),
+                    },
+                ],
+                finish_reason: None,
+            }],
+        };
+
+        let parsed_script: MockScript = serde_json::from_value(raw_script).unwrap();
+        assert_eq!(parsed_script, expected_script);
+    }
+
+    #[tokio::test]
+    async fn mock_echo_test() {
+        let mock = Mock::from_model(MOCK_ECHO_MODEL).unwrap();
+        let history = get_history("Hello there");
+
+        let emitted = collect_replies(&mock, history).await;
+
+        // "Hello there" in chunks of 4 chars plus the closing chunk
+        assert_eq!(emitted.len(), 4);
+        assert!(emitted.iter().all(|message| message.id == "2"));
+
+        let mut merged = vec![];
+        for message in emitted.iter() {
+            merge_chat_messages(&mut merged, message.message.clone());
+        }
+        assert!(matches!(
+            &merged[..],
+            [ChatMessage { content: ChatMessageContent::Text { text, .. }, .. }] if text == "Hello there"
+        ));
+
+        let last = emitted.last().unwrap();
+        assert!(last.done);
+        assert_eq!(last.usage.as_ref().unwrap().output_tokens, 2);
+    }
+
+    #[tokio::test]
+    async fn mock_tool_call_flow_test() {
+        let script_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join("mock")
+            .join("tool_call.json");
+        let mock = Mock::from_model(script_path.to_str().unwrap()).unwrap();
+
+        // First turn asks for the tool
+        let history = get_history("Weather in Boston?");
+        let emitted = collect_chat(&mock, history).await;
+        let first = emitted[0].as_ref().unwrap();
+        assert!(matches!(
+            &first.message[0].content,
+            ChatMessageContent::FunctionCallRequest { name, .. } if name == "weather-_-get_forecast"
+        ));
+
+        // Second turn answers once the tool result is in the history
+        let history = ChatHistory {
+            messages: vec![
+                get_message("1", Role::User, get_text("Weather in Boston?")),
+                get_message(
+                    "2",
+                    Role::Assistant,
+                    ChatMessageContent::FunctionCallRequest {
+                        id: None,
+                        name: String::from("weather-_-get_forecast"),
+                        args: Some(json!({"city": "Boston"})),
+                        _meta: None,
+                    },
+                ),
+                get_message(
+                    "3",
+                    Role::Function,
+                    ChatMessageContent::FunctionCallResponse {
+                        id: None,
+                        name: String::from("weather-_-get_forecast"),
+                        response: json!({"forecast": "Sunny"}),
+                        _meta: None,
+                    },
+                ),
+                get_message("4", Role::Assistant, get_text("")),
+            ],
+        };
+        let emitted = collect_chat(&mock, history).await;
+        assert!(emitted.iter().all(|message| message.is_ok()));
+        assert!(emitted.last().unwrap().as_ref().unwrap().done);
+    }
+
+    #[tokio::test]
+    async fn mock_error_test() {
+        let mock = Mock {
+            script: MockScript {
+                responses: vec![MockResponse {
+                    steps: vec![
+                        MockStep::Text {
+                            text: String::from("Partial"),
+                            chunk_size: None,
+                            delay_ms: None,
+                        },
+                        MockStep::Error {
+                            message: String::from("Injected failure"),
+                        },
+                        MockStep::Text {
+                            text: String::from("Never sent"),
+                            chunk_size: None,
+                            delay_ms: None,
+                        },
+                    ],
+                    finish_reason: None,
+                }],
+            },
+        };
+        let history = get_history("Hi");
+
+        let emitted = collect_chat(&mock, history).await;
+        assert_eq!(emitted.len(), 2);
+        assert!(emitted[0].is_ok());
+        assert!(
+            matches!(&emitted[1], Err(NexaError::Mock(message)) if message == "Injected failure")
+        );
+    }
+}
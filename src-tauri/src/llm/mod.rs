@@ -1,5 +1,15 @@
 pub mod base;
+pub mod cache;
 pub mod commands;
+pub mod compare;
 pub mod constants;
+pub mod continuation;
 pub mod gemini;
+pub mod gemini_files;
+pub mod limits;
+pub mod mock;
 pub mod ollama;
+pub mod pricing;
+pub mod rotation;
+#[cfg(test)]
+pub(crate) mod test_support;
@@ -1,5 +1,15 @@
-use crate::llm::base::Role;
+use crate::api::transport::{split_lines, HttpRequest, HttpTransport};
+use crate::error::NexaError;
+use crate::llm::base::{
+    ChatHistory, ChatMessage, ChatMessageContent, EmittedChatMessage, GenerationConfig, Role,
+    TokenUsage, LLM,
+};
+use crate::llm::constants::OLLAMA_BASE_URL;
+use futures::stream::StreamExt;
+use futures_util::Stream;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Arc;
 
 #[derive(Serialize, Deserialize)]
 pub(crate) struct OllamaChatMessage {
@@ -14,10 +24,12 @@ pub(crate) struct OllamaChatResponse {
     pub(crate) created_at: String,
     pub(crate) message: OllamaChatMessage,
     pub(crate) done: bool,
+    #[serde(default)]
+    pub(crate) done_reason: Option<String>,
     pub(crate) total_duration: Option<u64>,
     pub(crate) load_duration: Option<u64>,
-    pub(crate) prompt_evel_count: Option<u64>,
-    pub(crate) prompt_evel_duration: Option<u64>,
+    pub(crate) prompt_eval_count: Option<u64>,
+    pub(crate) prompt_eval_duration: Option<u64>,
     pub(crate) eval_count: Option<u64>,
     pub(crate) eval_duration: Option<u64>,
 }
@@ -42,8 +54,393 @@ pub(crate) struct OllamaModelInfo {
 pub(crate) struct OllamaChatRequest {
     pub(crate) model: String,
     pub(crate) messages: Vec<OllamaChatMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) keep_alive: Option<OllamaKeepAlive>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) options: Option<OllamaOptions>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub(crate) struct OllamaOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) num_predict: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) stop: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) seed: Option<i64>,
+}
+
+// Ollama accepts either a duration string ("5m", "1h") or a number of seconds.
+// A negative number keeps the model loaded forever and 0 unloads it right away.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum OllamaKeepAlive {
+    Seconds(i64),
+    Duration(String),
+}
+
+// An empty generate request only loads (or unloads) the model
+#[derive(Serialize, Deserialize)]
+pub(crate) struct OllamaGenerateRequest {
+    pub(crate) model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) keep_alive: Option<OllamaKeepAlive>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct OllamaPsResponse {
+    pub(crate) models: Vec<OllamaRunningModel>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OllamaRunningModel {
+    pub name: String,
+    pub model: String,
+    pub size: u64,
+    #[serde(default)]
+    pub size_vram: u64,
+    pub expires_at: String,
+    #[serde(default)]
+    pub digest: String,
 }
 
 pub(crate) struct Ollama {
     pub(crate) model: String,
+    pub(crate) keep_alive: Option<OllamaKeepAlive>,
+    pub(crate) system_prompt: Option<String>,
+    pub(crate) generation_config: GenerationConfig,
+    pub(crate) transport: Arc<dyn HttpTransport>,
+}
+
+impl LLM for Ollama {
+    async fn stream_chat(
+        &self,
+        mut history: ChatHistory,
+    ) -> Result<impl Stream<Item = Result<EmittedChatMessage, NexaError>>, NexaError> {
+        let last_message = history
+            .messages
+            .pop()
+            .ok_or(NexaError::Ollama("Empty chat history".to_string()))?;
+        let id = last_message.id.clone();
+
+        let mut messages: Vec<OllamaChatMessage> = vec![];
+        if let Some(system_prompt) = &self.system_prompt {
+            messages.push(OllamaChatMessage {
+                role: Role::System,
+                content: system_prompt.clone(),
+                images: None,
+            });
+        }
+        for msg in history.messages.into_iter() {
+            if let ChatMessageContent::Text { text, _meta } = msg.content {
+                messages.push(OllamaChatMessage {
+                    role: msg.role,
+                    content: text,
+                    images: msg.images,
+                });
+            }
+        }
+
+        let req = OllamaChatRequest {
+            model: self.model.clone(),
+            messages,
+            keep_alive: self.keep_alive.clone(),
+            options: get_ollama_options(&self.generation_config),
+        };
+
+        let res = self
+            .transport
+            .send(HttpRequest::post(format!("{OLLAMA_BASE_URL}/api/chat")).json(&req)?)
+            .await?
+            .error_for_status(NexaError::Ollama)
+            .await?;
+
+        let lines = split_lines(res.body);
+
+        Ok(lines.map(move |line| {
+            let stream_response: OllamaChatResponse = serde_json::from_str(&line?)?;
+
+            let usage = match stream_response.done {
+                true => Some(TokenUsage {
+                    input_tokens: stream_response.prompt_eval_count.unwrap_or_default(),
+                    output_tokens: stream_response.eval_count.unwrap_or_default(),
+                    ..Default::default()
+                }),
+                false => None,
+            };
+
+            Ok(EmittedChatMessage {
+                id: id.clone(),
+                message: vec![ChatMessage {
+                    role: stream_response.message.role,
+                    images: stream_response.message.images,
+                    content: ChatMessageContent::Text {
+                        text: stream_response.message.content,
+                        _meta: None,
+                    },
+                }],
+                done: stream_response.done,
+                usage,
+                blocked: None,
+                finish_reason: stream_response.done_reason,
+                cost: None,
+                error: None,
+            })
+        }))
+    }
+}
+
+pub(crate) async fn get_ollama_tags(
+    transport: &dyn HttpTransport,
+) -> Result<Vec<OllamaModelTag>, NexaError> {
+    let res = transport
+        .send(HttpRequest::get(format!("{OLLAMA_BASE_URL}/api/tags")))
+        .await?
+        .error_for_status(NexaError::Ollama)
+        .await?;
+
+    let ollama_tags: OllamaTagsResponse = res.json().await?;
+    Ok(ollama_tags.models)
+}
+
+pub(crate) async fn get_ollama_model_info(
+    transport: &dyn HttpTransport,
+    model: &str,
+) -> Result<OllamaModelInfo, NexaError> {
+    let res = transport
+        .send(
+            HttpRequest::post(format!("{OLLAMA_BASE_URL}/api/show")).json(&json!({
+                "model": model
+            }))?,
+        )
+        .await?
+        .error_for_status(NexaError::Ollama)
+        .await?;
+
+    res.json().await
+}
+
+pub(crate) async fn get_ollama_ps(
+    transport: &dyn HttpTransport,
+) -> Result<Vec<OllamaRunningModel>, NexaError> {
+    let res = transport
+        .send(HttpRequest::get(format!("{OLLAMA_BASE_URL}/api/ps")))
+        .await?
+        .error_for_status(NexaError::Ollama)
+        .await?;
+
+    let ps_response: OllamaPsResponse = res.json().await?;
+    Ok(ps_response.models)
+}
+
+pub(crate) async fn send_ollama_generate_request(
+    transport: &dyn HttpTransport,
+    request: OllamaGenerateRequest,
+) -> Result<(), NexaError> {
+    transport
+        .send(HttpRequest::post(format!("{OLLAMA_BASE_URL}/api/generate")).json(&request)?)
+        .await?
+        .error_for_status(|e| {
+            NexaError::Ollama(format!("Failed to load or unload {}: {}", request.model, e))
+        })
+        .await?
+        .bytes()
+        .await?;
+
+    Ok(())
+}
+
+// Left out of the request when nothing is set, so the model's Modelfile
+// parameters apply
+fn get_ollama_options(generation_config: &GenerationConfig) -> Option<OllamaOptions> {
+    if *generation_config == GenerationConfig::default() {
+        return None;
+    }
+
+    Some(OllamaOptions {
+        temperature: generation_config.temperature,
+        top_p: generation_config.top_p,
+        top_k: generation_config.top_k,
+        num_predict: generation_config.max_output_tokens,
+        stop: generation_config.stop_sequences.clone(),
+        seed: generation_config.seed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::transport::tests::get_fixture_path;
+    use crate::api::transport::ReplayTransport;
+    use crate::llm::base::ChatMessageWithId;
+    use serde_json::{self, json, Value};
+
+    #[test]
+    fn ollama_ps_response_parsing_test() {
+        let raw_json_string = r#"{
+            "models": [
+                {
+                    "name": "mistral:latest",
+                    "model": "mistral:latest",
+                    "size": 5137025024,
+                    "digest": "2ae6f6dd7a3dd734790bbbf58b8909a606e0e7e97e94b7604e0aa7ae4490e6d8",
+                    "details": {
+                        "parent_model": "",
+                        "format": "gguf",
+                        "family": "llama",
+                        "parameter_size": "7.2B",
+                        "quantization_level": "Q4_0"
+                    },
+                    "expires_at": "2024-06-04T14:38:31.83753-07:00",
+                    "size_vram": 5137025024
+                }
+            ]
+        }"#;
+
+        let ps_response: OllamaPsResponse = serde_json::from_str(raw_json_string).unwrap();
+        assert_eq!(
+            ps_response.models,
+            vec![OllamaRunningModel {
+                name: String::from("mistral:latest"),
+                model: String::from("mistral:latest"),
+                size: 5137025024,
+                size_vram: 5137025024,
+                expires_at: String::from("2024-06-04T14:38:31.83753-07:00"),
+                digest: String::from(
+                    "2ae6f6dd7a3dd734790bbbf58b8909a606e0e7e97e94b7604e0aa7ae4490e6d8"
+                ),
+            }]
+        );
+    }
+
+    #[test]
+    fn ollama_keep_alive_serde_test() {
+        // Unload
+        let request = OllamaGenerateRequest {
+            model: String::from("llama3.2"),
+            keep_alive: Some(OllamaKeepAlive::Seconds(0)),
+        };
+        let serialized_value = serde_json::to_value(request).unwrap();
+        assert_eq!(
+            serialized_value,
+            json!({
+                "model": "llama3.2",
+                "keep_alive": 0
+            })
+        );
+
+        // Duration string
+        let keep_alive: OllamaKeepAlive = serde_json::from_value(json!("10m")).unwrap();
+        assert_eq!(keep_alive, OllamaKeepAlive::Duration(String::from("10m")));
+
+        // Left to the server default
+        let request = OllamaChatRequest {
+            model: String::from("llama3.2"),
+            messages: vec![],
+            keep_alive: None,
+            options: None,
+        };
+        let serialized_value: Value = serde_json::to_value(request).unwrap();
+        assert_eq!(
+            serialized_value,
+            json!({
+                "model": "llama3.2",
+                "messages": []
+            })
+        );
+    }
+
+    #[test]
+    fn ollama_options_test() {
+        assert_eq!(get_ollama_options(&GenerationConfig::default()), None);
+
+        let generation_config = GenerationConfig {
+            temperature: Some(0.2),
+            max_output_tokens: Some(256),
+            stop_sequences: Some(vec![String::from("END")]),
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(get_ollama_options(&generation_config)).unwrap(),
+            json!({
+                "temperature": 0.2,
+                "num_predict": 256,
+                "stop": ["END"]
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn ollama_chat_replay_test() {
+        let ollama = Ollama {
+            model: String::from("llama3.2"),
+            keep_alive: None,
+            system_prompt: None,
+            generation_config: GenerationConfig::default(),
+            transport: Arc::new(
+                ReplayTransport::load(&get_fixture_path("ollama_chat.json")).unwrap(),
+            ),
+        };
+
+        let history = ChatHistory {
+            messages: vec![
+                ChatMessageWithId {
+                    id: String::from("user-1"),
+                    role: Role::User,
+                    content: ChatMessageContent::Text {
+                        text: String::from("What color is the sky? Answer in one sentence."),
+                        _meta: None,
+                    },
+                    images: None,
+                },
+                ChatMessageWithId {
+                    id: String::from("assistant-1"),
+                    role: Role::Assistant,
+                    content: ChatMessageContent::Text {
+                        text: String::new(),
+                        _meta: None,
+                    },
+                    images: None,
+                },
+            ],
+        };
+
+        let emitted: Vec<EmittedChatMessage> = ollama
+            .stream_chat(history)
+            .await
+            .unwrap()
+            .map(|message| message.unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(emitted.len(), 4);
+        assert!(emitted.iter().all(|message| message.id == "assistant-1"));
+
+        let text: String = emitted
+            .iter()
+            .flat_map(|message| message.message.iter())
+            .map(|message| match &message.content {
+                ChatMessageContent::Text { text, .. } => text.clone(),
+                _ => String::new(),
+            })
+            .collect();
+        assert_eq!(text, "The sky is blue.");
+
+        let last = emitted.last().unwrap();
+        assert!(last.done);
+        assert_eq!(
+            last.usage,
+            Some(TokenUsage {
+                input_tokens: 26,
+                output_tokens: 5,
+                ..Default::default()
+            })
+        );
+    }
 }
@@ -0,0 +1,363 @@
+use crate::conversation::settings::ConversationSettings;
+use crate::conversation::tree::ConversationTree;
+use crate::error::NexaError;
+use crate::llm::base::{
+    ChatHistory, ChatMessageContent, EmittedChatMessage, Provider, Role, TokenUsage, LLM,
+};
+use crate::llm::limits::estimate_tokens;
+use futures_util::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use tokio::sync::Mutex;
+
+pub(crate) static PRICING_FILENAME: &str = "pricing.json";
+// The finish reason of a reply whose continuation a spend limit stopped
+pub(crate) static SPEND_LIMIT_FINISH: &str = "SPEND_LIMIT";
+
+static TOKENS_PER_PRICE_UNIT: f64 = 1_000_000.0;
+
+// Prices in USD per million tokens, the way providers publish them. `model`
+// matches every model name starting with it, the longest match wins, so
+// "gemini-2.5-flash" also prices "gemini-2.5-flash-preview-05-20".
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelPricing {
+    pub provider: Provider,
+    pub model: String,
+    pub input: f64,
+    pub output: f64,
+    // Input tokens served from the context cache, billed at the input price
+    // when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_input: Option<f64>,
+    // Billed at the output price when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<f64>,
+}
+
+impl ModelPricing {
+    // Providers count cached tokens as part of the input, they're only billed
+    // once at the cached price
+    pub fn get_cost(&self, usage: &TokenUsage) -> f64 {
+        let cached_tokens = usage.cached_tokens.min(usage.input_tokens);
+        let uncached_tokens = usage.input_tokens - cached_tokens;
+
+        (uncached_tokens as f64 * self.input
+            + cached_tokens as f64 * self.cached_input.unwrap_or(self.input)
+            + usage.output_tokens as f64 * self.output
+            + usage.thinking_tokens as f64 * self.thinking.unwrap_or(self.output))
+            / TOKENS_PER_PRICE_UNIT
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct PricingTable {
+    prices: Vec<ModelPricing>,
+    path: Option<PathBuf>,
+}
+
+impl PricingTable {
+    // No file yet means no prices, a file that doesn't parse is an error
+    pub fn load(path: Option<PathBuf>) -> Result<Self, NexaError> {
+        let prices = match path.as_ref().map(fs::read) {
+            None => vec![],
+            Some(Err(e)) if e.kind() == ErrorKind::NotFound => vec![],
+            Some(bytes) => serde_json::from_slice(&bytes?)?,
+        };
+
+        Ok(PricingTable { prices, path })
+    }
+
+    pub fn get_prices(&self) -> Vec<ModelPricing> {
+        self.prices.clone()
+    }
+
+    pub fn set_prices(&mut self, prices: Vec<ModelPricing>) -> Result<(), NexaError> {
+        self.prices = prices;
+        self.save()
+    }
+
+    pub fn get(&self, provider: &Provider, model: &str) -> Option<ModelPricing> {
+        self.prices
+            .iter()
+            .filter(|price| price.provider == *provider && model.starts_with(&price.model))
+            .max_by_key(|price| price.model.len())
+            .cloned()
+    }
+
+    fn save(&self) -> Result<(), NexaError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_vec(&self.prices)?)?;
+
+        Ok(())
+    }
+}
+
+// Fills in the cost of the reply on the chunk that reports its usage. Sits on
+// top of the other wrappers so the usage of every continuation round is in.
+// Models without a price get no cost.
+pub(crate) struct Priced<'a, L: LLM> {
+    pub(crate) inner: L,
+    pub(crate) pricing: &'a Mutex<PricingTable>,
+    pub(crate) provider: Provider,
+    pub(crate) model: String,
+}
+
+impl<'a, L: LLM> LLM for Priced<'a, L> {
+    async fn stream_chat(
+        &self,
+        history: ChatHistory,
+    ) -> Result<impl Stream<Item = Result<EmittedChatMessage, NexaError>>, NexaError> {
+        let price = self.pricing.lock().await.get(&self.provider, &self.model);
+        let inner_stream = self.inner.stream_chat(history).await?;
+
+        Ok(inner_stream.map(move |item| {
+            item.map(|mut chunk| {
+                if let (None, Some(usage), Some(price)) = (chunk.cost, &chunk.usage, &price) {
+                    chunk.cost = Some(price.get_cost(usage));
+                }
+                chunk
+            })
+        }))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationCost {
+    // Every reply in the conversation, including the ones on other branches
+    pub total: f64,
+    // Replies since the user last wrote something themselves, which is what
+    // a model calling tools on its own runs up
+    pub agent_loop: f64,
+}
+
+pub(crate) fn get_conversation_cost(
+    tree: &ConversationTree,
+    leaf_id: Option<&str>,
+) -> Result<ConversationCost, NexaError> {
+    let total = tree
+        .messages
+        .iter()
+        .filter_map(|message| message.cost)
+        .sum();
+
+    let mut agent_loop = 0.0;
+    if let Some(leaf_id) = leaf_id {
+        for message in tree.get_path(leaf_id)?.into_iter().rev() {
+            let from_user = message.role == Role::User
+                && !matches!(
+                    message.content,
+                    ChatMessageContent::FunctionCallResponse { .. }
+                );
+            if from_user {
+                break;
+            }
+            agent_loop += message.cost.unwrap_or(0.0);
+        }
+    }
+
+    Ok(ConversationCost { total, agent_loop })
+}
+
+// Refuses the next request once it would go over a spend limit of the
+// conversation. The reply's own cost isn't known up front, only its prompt.
+pub(crate) fn check_spend_limits(
+    spent: &ConversationCost,
+    settings: &ConversationSettings,
+    price: Option<&ModelPricing>,
+    history: &ChatHistory,
+) -> Result<(), NexaError> {
+    let prompt_cost = price
+        .map(|price| {
+            price.get_cost(&TokenUsage {
+                input_tokens: estimate_tokens(history),
+                ..Default::default()
+            })
+        })
+        .unwrap_or(0.0);
+
+    if let Some(max) = settings.max_conversation_cost {
+        if spent.total + prompt_cost >= max {
+            return Err(NexaError::SpendLimit(format!(
+                "This conversation has cost ${:.4} of its ${:.4} limit",
+                spent.total, max
+            )));
+        }
+    }
+    if let Some(max) = settings.max_agent_loop_cost {
+        if spent.agent_loop + prompt_cost >= max {
+            return Err(NexaError::SpendLimit(format!(
+                "The replies since your last message have cost ${:.4} of their ${:.4} limit",
+                spent.agent_loop, max
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+// The limits of a stored conversation and what it had spent before the
+// reply, so they can be checked again before every continuation round
+#[derive(Clone, Debug)]
+pub(crate) struct SpendLimit {
+    pub(crate) spent: ConversationCost,
+    pub(crate) settings: ConversationSettings,
+    pub(crate) price: Option<ModelPricing>,
+}
+
+impl SpendLimit {
+    // `usage` is what the reply has taken so far
+    pub(crate) fn check(
+        &self,
+        usage: Option<&TokenUsage>,
+        history: &ChatHistory,
+    ) -> Result<(), NexaError> {
+        let reply_cost = match (&self.price, usage) {
+            (Some(price), Some(usage)) => price.get_cost(usage),
+            _ => 0.0,
+        };
+        let spent = ConversationCost {
+            total: self.spent.total + reply_cost,
+            agent_loop: self.spent.agent_loop + reply_cost,
+        };
+
+        check_spend_limits(&spent, &self.settings, self.price.as_ref(), history)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversation::tree::TreeMessage;
+    use serde_json::json;
+
+    fn get_message(
+        id: &str,
+        parent_id: Option<&str>,
+        role: Role,
+        cost: Option<f64>,
+    ) -> TreeMessage {
+        TreeMessage {
+            id: id.to_string(),
+            parent_id: parent_id.map(|id| id.to_string()),
+            role,
+            content: match id.starts_with("tool") {
+                true => ChatMessageContent::FunctionCallResponse {
+                    id: None,
+                    name: String::from("weather-_-get_forecast"),
+                    response: json!({"forecast": "sunny"}),
+                    _meta: None,
+                },
+                false => ChatMessageContent::Text {
+                    text: String::from("Hi"),
+                    _meta: None,
+                },
+            },
+            images: None,
+            created_at: 0,
+            provider: None,
+            model: None,
+            usage: None,
+            cost,
+        }
+    }
+
+    #[test]
+    fn model_pricing_test() {
+        let mut table = PricingTable::load(None).unwrap();
+        table
+            .set_prices(vec![
+                ModelPricing {
+                    provider: Provider::Gemini,
+                    model: String::from("gemini-2.5"),
+                    input: 1.0,
+                    output: 1.0,
+                    cached_input: None,
+                    thinking: None,
+                },
+                ModelPricing {
+                    provider: Provider::Gemini,
+                    model: String::from("gemini-2.5-flash"),
+                    input: 0.3,
+                    output: 2.5,
+                    cached_input: Some(0.075),
+                    thinking: None,
+                },
+            ])
+            .unwrap();
+        assert!(table.get(&Provider::Ollama, "gemini-2.5-flash").is_none());
+
+        let price = table
+            .get(&Provider::Gemini, "gemini-2.5-flash-preview-05-20")
+            .unwrap();
+        let usage = TokenUsage {
+            input_tokens: 1_000_000,
+            output_tokens: 100_000,
+            cached_tokens: 200_000,
+            thinking_tokens: 100_000,
+        };
+        // 0.8M input, 0.2M cached input and 0.2M output at the output price
+        let cost = price.get_cost(&usage);
+        assert!((cost - (0.24 + 0.015 + 0.5)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn load_pricing_test() {
+        let path = std::env::temp_dir().join(format!("nexa-pricing-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        assert!(PricingTable::load(Some(path.clone()))
+            .unwrap()
+            .prices
+            .is_empty());
+
+        fs::write(&path, "[{\"provider\": \"gemini\"").unwrap();
+        assert!(PricingTable::load(Some(path.clone())).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn spend_limits_test() {
+        let mut tree = ConversationTree::default();
+        for message in [
+            get_message("u1", None, Role::User, None),
+            get_message("a1", Some("u1"), Role::Assistant, Some(0.5)),
+            get_message("u2", Some("a1"), Role::User, None),
+            get_message("a2", Some("u2"), Role::Assistant, Some(0.25)),
+            get_message("tool1", Some("a2"), Role::User, None),
+            get_message("a3", Some("tool1"), Role::Assistant, Some(0.25)),
+        ] {
+            tree.add_message(message).unwrap();
+        }
+
+        let spent = get_conversation_cost(&tree, Some("a3")).unwrap();
+        assert_eq!(
+            spent,
+            ConversationCost {
+                total: 1.0,
+                agent_loop: 0.5,
+            }
+        );
+
+        let history = tree.get_history("a3").unwrap();
+        let mut settings = ConversationSettings {
+            max_conversation_cost: Some(2.0),
+            max_agent_loop_cost: Some(1.0),
+            ..Default::default()
+        };
+        assert!(check_spend_limits(&spent, &settings, None, &history).is_ok());
+
+        settings.max_agent_loop_cost = Some(0.5);
+        assert!(check_spend_limits(&spent, &settings, None, &history)
+            .is_err_and(|e| matches!(e, NexaError::SpendLimit(_))));
+    }
+}
@@ -0,0 +1,108 @@
+use crate::error::NexaError;
+use crate::llm::base::{ChatHistory, EmittedChatMessage, LLM};
+use futures_util::Stream;
+
+// One copy of the same provider per credential profile, in rotation order.
+// When a request is refused for quota the next copy is tried. Quota errors
+// come back before anything is streamed, so nothing reaches the UI twice.
+pub(crate) struct CredentialRotation<L: LLM> {
+    pub(crate) candidates: Vec<L>,
+}
+
+impl<L: LLM> LLM for CredentialRotation<L> {
+    async fn stream_chat(
+        &self,
+        history: ChatHistory,
+    ) -> Result<impl Stream<Item = Result<EmittedChatMessage, NexaError>>, NexaError> {
+        let mut last_error = None;
+        let mut exhausted = 0;
+
+        for candidate in self.candidates.iter() {
+            match candidate.stream_chat(history.clone()).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) if is_quota_error(&e) => {
+                    exhausted += 1;
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        // The message keeps its "HTTP 429" prefix, so it's still a quota error
+        match last_error {
+            Some(NexaError::Gemini(message)) if exhausted > 1 => Err(NexaError::Gemini(format!(
+                "{} (all {} credential profiles are out of quota)",
+                message, exhausted
+            ))),
+            Some(e) => Err(e),
+            None => Err(NexaError::Command(String::from(
+                "No credentials to send the request with",
+            ))),
+        }
+    }
+}
+
+pub(crate) fn is_quota_error(e: &NexaError) -> bool {
+    matches!(e, NexaError::Gemini(message) if message.starts_with("HTTP 429"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::mock::Mock;
+    use crate::llm::test_support::{collect_replies, get_history};
+
+    struct QuotaLimited {
+        exhausted: bool,
+        mock: Mock,
+    }
+
+    impl LLM for QuotaLimited {
+        async fn stream_chat(
+            &self,
+            history: ChatHistory,
+        ) -> Result<impl Stream<Item = Result<EmittedChatMessage, NexaError>>, NexaError> {
+            if self.exhausted {
+                return Err(NexaError::Gemini(String::from(
+                    "HTTP 429 Too Many Requests: RESOURCE_EXHAUSTED",
+                )));
+            }
+
+            self.mock.stream_chat(history).await
+        }
+    }
+
+    #[tokio::test]
+    async fn credential_rotation_test() {
+        let rotation = CredentialRotation {
+            candidates: vec![
+                QuotaLimited {
+                    exhausted: true,
+                    mock: Mock::from_model("echo").unwrap(),
+                },
+                QuotaLimited {
+                    exhausted: false,
+                    mock: Mock::from_model("echo").unwrap(),
+                },
+            ],
+        };
+        let emitted = collect_replies(&rotation, get_history("Hi")).await;
+        assert!(emitted.last().unwrap().done);
+
+        // Out of profiles, the quota error is passed on
+        let rotation = CredentialRotation {
+            candidates: (0..2)
+                .map(|_| QuotaLimited {
+                    exhausted: true,
+                    mock: Mock::from_model("echo").unwrap(),
+                })
+                .collect(),
+        };
+        assert!(rotation
+            .stream_chat(get_history("Hi"))
+            .await
+            .is_err_and(|e| {
+                is_quota_error(&e) && e.to_string().contains("all 2 credential profiles")
+            }));
+    }
+}
@@ -0,0 +1,70 @@
+use crate::error::NexaError;
+use crate::llm::base::{
+    ChatHistory, ChatMessageContent, ChatMessageWithId, EmittedChatMessage, Role, LLM,
+};
+use crate::llm::mock::{Mock, MockResponse, MockScript, MockStep};
+use futures_util::StreamExt;
+
+pub(crate) fn get_text(text: &str) -> ChatMessageContent {
+    ChatMessageContent::Text {
+        text: text.to_string(),
+        _meta: None,
+    }
+}
+
+pub(crate) fn get_message(id: &str, role: Role, content: ChatMessageContent) -> ChatMessageWithId {
+    ChatMessageWithId {
+        id: id.to_string(),
+        role,
+        content,
+        images: None,
+    }
+}
+
+// A user turn and the empty assistant message its reply streams into
+pub(crate) fn get_history(text: &str) -> ChatHistory {
+    ChatHistory {
+        messages: vec![
+            get_message("1", Role::User, get_text(text)),
+            get_message("2", Role::Assistant, get_text("")),
+        ],
+    }
+}
+
+// One response per (text, finish reason), each streamed in chunks of 3 chars
+pub(crate) fn get_mock(responses: &[(&str, Option<&str>)]) -> Mock {
+    Mock {
+        script: MockScript {
+            responses: responses
+                .iter()
+                .map(|(text, finish_reason)| MockResponse {
+                    steps: vec![MockStep::Text {
+                        text: text.to_string(),
+                        chunk_size: Some(3),
+                        delay_ms: None,
+                    }],
+                    finish_reason: finish_reason.map(String::from),
+                })
+                .collect(),
+        },
+    }
+}
+
+pub(crate) async fn collect_chat(
+    llm: &impl LLM,
+    history: ChatHistory,
+) -> Vec<Result<EmittedChatMessage, NexaError>> {
+    llm.stream_chat(history).await.unwrap().collect().await
+}
+
+// For streams that aren't expected to fail
+pub(crate) async fn collect_replies(
+    llm: &impl LLM,
+    history: ChatHistory,
+) -> Vec<EmittedChatMessage> {
+    collect_chat(llm, history)
+        .await
+        .into_iter()
+        .map(|item| item.unwrap())
+        .collect()
+}
@@ -1,24 +1,31 @@
 use crate::{
+    api::transport::HttpTransport,
     error::NexaError,
     mcp::{
+        config::{MCPServerConfig, MCPTransportType},
         connection::{
             self, mcp_stdio_connect, MCPStdioConnection, MCPTransportReader, MCPTransportWriter,
         },
+        http::mcp_http_connect,
+        sse::mcp_sse_connect,
         structs::{
-            Id, ListToolsResult, MCPDataPacket, MCPNotification, MCPRequest, MCPResponse, Tool,
-            JSON_RPC,
+            Id, ListToolsResult, MCPDataPacket, MCPNotification, MCPRequest, MCPResponse,
+            Paginated, Tool, JSON_RPC,
         },
     },
 };
 use futures::lock::Mutex;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{
     collections::HashMap,
     sync::{Arc, LazyLock},
 };
 use tokio::{
     select,
-    sync::{oneshot, RwLock},
+    sync::{
+        mpsc::{self, UnboundedReceiver},
+        oneshot, RwLock,
+    },
     task,
 };
 use tokio_util::sync::CancellationToken;
@@ -30,6 +37,19 @@ use crate::mcp::connection::MCPConnection;
 pub(crate) static MCP_PROTOCOL_VERSION: &str = "2025-06-18";
 pub(crate) static MCP_CLIENT_NAME: &str = "Nexa MCP Client";
 pub(crate) static MCP_CLIENT_VERSION: &str = "1.0.0";
+static MCP_MAX_LIST_PAGES: usize = 100;
+pub(crate) static MCP_TOOLS_LIST_CHANGED: &str = "notifications/tools/list_changed";
+
+type PendingRequests = HashMap<Id, oneshot::Sender<MCPResponse>>;
+
+// What the listener has to tell whoever keeps an eye on the server
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum MCPClientEvent {
+    // The method of a list_changed notification
+    ListChanged(String),
+    // The connection broke, nothing the server sends arrives anymore
+    Closed(String),
+}
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 #[serde(rename_all = "camelCase")]
@@ -140,48 +160,145 @@ pub(crate) enum MCPStatus {
     Connecting,
 }
 
-pub(crate) enum Transport {
-    Stdio,
-    Http,
-}
-
 pub(crate) struct MCPClient {
     configuration: ClientConfiguration,
     transport_output: Mutex<Option<Box<dyn MCPTransportReader>>>,
     transport_input: Box<dyn MCPTransportWriter>,
-    transport_type: Transport,
+    transport_type: MCPTransportType,
     tool_list: RwLock<HashMap<String, Tool>>,
-    tool_calls_map: Arc<Mutex<HashMap<Id, oneshot::Sender<MCPResponse>>>>,
+    // None once the listener stopped, which drops every sender left in it
+    tool_calls_map: Arc<Mutex<Option<PendingRequests>>>,
     request_id: Mutex<u64>,
     status: RwLock<MCPStatus>,
 
     server_config: RwLock<ServerConfiguration>,
     cancel_token: CancellationToken,
+    // Kept until someone takes them
+    events: Mutex<Option<UnboundedReceiver<MCPClientEvent>>>,
 }
 
 impl MCPClient {
-    pub fn new_stdio_client<S, I>(command: S, args: I) -> Result<Self, NexaError>
-    where
-        S: Into<String>,
-        I: IntoIterator<Item = S>,
-    {
-        let (stdio_writer, stdio_reader) = mcp_stdio_connect(command, args)?;
-
-        Ok(MCPClient {
+    fn new(
+        transport_type: MCPTransportType,
+        transport_input: Box<dyn MCPTransportWriter>,
+        transport_output: Box<dyn MCPTransportReader>,
+    ) -> Self {
+        MCPClient {
             configuration: ClientConfiguration::default(),
             status: RwLock::new(MCPStatus::Connecting),
-            transport_output: Mutex::new(Some(Box::new(stdio_reader))),
-            transport_input: Box::new(stdio_writer),
-            transport_type: Transport::Stdio,
+            transport_output: Mutex::new(Some(transport_output)),
+            transport_input,
+            transport_type,
             request_id: Mutex::new(1),
 
             tool_list: RwLock::new(HashMap::new()),
-            tool_calls_map: Arc::new(Mutex::new(HashMap::new())),
+            tool_calls_map: Arc::new(Mutex::new(Some(HashMap::new()))),
 
             server_config: RwLock::new(ServerConfiguration::default()),
             // Cancel token for listening async task
             cancel_token: CancellationToken::new(),
-        })
+            events: Mutex::new(None),
+        }
+    }
+
+    pub fn new_stdio_client<S, I>(command: S, args: I) -> Result<Self, NexaError>
+    where
+        S: Into<String>,
+        I: IntoIterator<Item = S>,
+    {
+        Self::new_stdio_client_with(command, args, &HashMap::new(), None)
+    }
+
+    pub fn new_stdio_client_with<S, I>(
+        command: S,
+        args: I,
+        env: &HashMap<String, String>,
+        cwd: Option<&str>,
+    ) -> Result<Self, NexaError>
+    where
+        S: Into<String>,
+        I: IntoIterator<Item = S>,
+    {
+        let (stdio_writer, stdio_reader) = mcp_stdio_connect(command, args, env, cwd)?;
+
+        Ok(Self::new(
+            MCPTransportType::Stdio,
+            Box::new(stdio_writer),
+            Box::new(stdio_reader),
+        ))
+    }
+
+    pub fn new_http_client(
+        http_transport: Arc<dyn HttpTransport>,
+        url: impl Into<String>,
+        headers: HashMap<String, String>,
+    ) -> Self {
+        let (http_writer, http_reader) = mcp_http_connect(http_transport, url, headers);
+
+        Self::new(
+            MCPTransportType::Http,
+            Box::new(http_writer),
+            Box::new(http_reader),
+        )
+    }
+
+    // Unlike the other transports, the SSE stream has to be open before
+    // there's anywhere to send the first request to
+    pub async fn new_sse_client(
+        http_transport: Arc<dyn HttpTransport>,
+        url: &str,
+        headers: HashMap<String, String>,
+    ) -> Result<Self, NexaError> {
+        let (sse_writer, sse_reader) = mcp_sse_connect(http_transport, url, headers).await?;
+
+        Ok(Self::new(
+            MCPTransportType::Sse,
+            Box::new(sse_writer),
+            Box::new(sse_reader),
+        ))
+    }
+
+    pub async fn from_config(
+        config: &MCPServerConfig,
+        http_transport: Arc<dyn HttpTransport>,
+    ) -> Result<Self, NexaError> {
+        let transport_type = config.get_transport_type()?;
+        if transport_type == MCPTransportType::Stdio {
+            let command = config
+                .command
+                .as_ref()
+                .ok_or(NexaError::MCPConnection(String::from(
+                    "A stdio server needs a command",
+                )))?;
+
+            return Self::new_stdio_client_with(
+                command.as_str(),
+                config.args.iter().map(|arg| arg.as_str()),
+                &config.env,
+                config.cwd.as_deref(),
+            );
+        }
+
+        let url = config
+            .url
+            .as_ref()
+            .ok_or(NexaError::MCPConnection(String::from(
+                "An HTTP server needs a url",
+            )))?;
+        match transport_type {
+            MCPTransportType::Sse => {
+                Self::new_sse_client(http_transport, url, config.headers.clone()).await
+            }
+            _ => Ok(Self::new_http_client(
+                http_transport,
+                url,
+                config.headers.clone(),
+            )),
+        }
+    }
+
+    pub fn get_transport_type(&self) -> MCPTransportType {
+        self.transport_type.clone()
     }
 
     pub async fn get_server_config(&self) -> ServerConfiguration {
@@ -205,145 +322,209 @@ impl MCPClient {
         id
     }
 
-    async fn initialize(&self) -> Result<(), NexaError> {
-        match &self.transport_type {
-            Transport::Stdio => {
-                let default_client_config = self.configuration.clone();
-                let default_client_config_value = serde_json::to_value(default_client_config)?;
-                let request_id = Id::NumberId(self.get_request_id().await);
-
-                let initialize_request = MCPRequest {
-                    jsonrpc: JSON_RPC.to_string(),
-                    id: request_id.clone(),
-                    method: String::from("initialize"),
-                    params: Some(default_client_config_value),
-                };
-
-                self.transport_input
-                    .send(serde_json::to_value(initialize_request)?)
-                    .await?;
-
-                let response = self
-                    .transport_output
-                    .lock()
-                    .await
-                    .as_mut()
-                    .ok_or(NexaError::MCPConnection(String::from("Missing Stdout")))?
-                    .receive()
-                    .await?;
-
-                if let MCPDataPacket::Response(initialize_response) = response {
-                    match initialize_response {
-                        MCPResponse::Success {
-                            jsonrpc,
-                            id,
-                            result,
-                        } => {
-                            if jsonrpc != JSON_RPC {
-                                return Err(NexaError::MCPConnection(String::from(
-                                    "Incorrect JSON RPC version",
-                                )));
-                            }
+    // Until the listener runs, whatever the server sends in between (a log
+    // notification, a ping over the HTTP stream) is passed over
+    async fn receive_response(&self) -> Result<MCPDataPacket, NexaError> {
+        let mut transport_output = self.transport_output.lock().await;
+        let transport_output = transport_output
+            .as_mut()
+            .ok_or(NexaError::MCPConnection(String::from("Missing Stdout")))?;
 
-                            if id != request_id {
-                                return Err(NexaError::MCPConnection(String::from(
-                                    "Incorrect response id",
-                                )));
-                            }
+        loop {
+            let packet = transport_output.receive().await?;
+            if let MCPDataPacket::Response(_) = packet {
+                return Ok(packet);
+            }
+        }
+    }
+
+    // Before the listener runs the response is read here, after that the
+    // listener hands it over by id like it does for tool calls
+    async fn send_request(
+        &self,
+        method: &str,
+        params: Option<Value>,
+    ) -> Result<MCPResponse, NexaError> {
+        let request_id = Id::NumberId(self.get_request_id().await);
+        let request = serde_json::to_value(MCPRequest {
+            jsonrpc: JSON_RPC.to_string(),
+            id: request_id.clone(),
+            method: method.to_string(),
+            params,
+        })?;
+
+        let mut transport_output = self.transport_output.lock().await;
+        if let Some(transport_output) = transport_output.as_mut() {
+            self.transport_input.send(request).await?;
+            loop {
+                if let MCPDataPacket::Response(response) = transport_output.receive().await? {
+                    match &response {
+                        MCPResponse::Success { id, .. } | MCPResponse::Fail { id, .. }
+                            if *id == request_id =>
+                        {
+                            return Ok(response)
+                        }
+                        _ => continue,
+                    }
+                }
+            }
+        }
+        drop(transport_output);
+
+        let tr = self.wait_for_response(&request_id).await?;
+        if let Err(e) = self.transport_input.send(request).await {
+            if let Some(map) = self.tool_calls_map.lock().await.as_mut() {
+                map.remove(&request_id);
+            }
+            return Err(e);
+        }
+
+        tr.await.map_err(|_| {
+            NexaError::MCPConnection(format!("The MCP server went away during {}", method))
+        })
+    }
 
-                            // Update Server Configuration
-                            let server_config_received: ServerConfiguration =
-                                serde_json::from_value(result)?;
-                            let mut server_config_handle = self.server_config.write().await;
-                            *server_config_handle = server_config_received;
+    async fn wait_for_response(
+        &self,
+        id: &Id,
+    ) -> Result<oneshot::Receiver<MCPResponse>, NexaError> {
+        let (tx, tr) = oneshot::channel::<MCPResponse>();
+        self.tool_calls_map
+            .lock()
+            .await
+            .as_mut()
+            .ok_or(NexaError::MCPConnection(String::from(
+                "The MCP server isn't connected anymore",
+            )))?
+            .insert(id.clone(), tx);
 
-                            // Update Status
-                            let mut status_handle = self.status.write().await;
-                            *status_handle = MCPStatus::Connected;
+        Ok(tr)
+    }
 
-                            let initialized_notification = MCPNotification {
-                                jsonrpc: JSON_RPC.to_string(),
-                                method: String::from("notifications/initialized"),
-                                params: None,
-                            };
+    async fn initialize(&self) -> Result<(), NexaError> {
+        let default_client_config = self.configuration.clone();
+        let default_client_config_value = serde_json::to_value(default_client_config)?;
+        let request_id = Id::NumberId(self.get_request_id().await);
 
-                            self.transport_input
-                                .send(serde_json::to_value(initialized_notification)?)
-                                .await?;
+        let initialize_request = MCPRequest {
+            jsonrpc: JSON_RPC.to_string(),
+            id: request_id.clone(),
+            method: String::from("initialize"),
+            params: Some(default_client_config_value),
+        };
 
-                            return Ok(());
-                        }
-                        MCPResponse::Fail { jsonrpc, id, error } => {
-                            return Err(NexaError::MCPConnection(format!(
-                                "Initialization Failed: {}",
-                                error.message
-                            )))
-                        }
+        self.transport_input
+            .send(serde_json::to_value(initialize_request)?)
+            .await?;
+
+        let response = self.receive_response().await?;
+
+        if let MCPDataPacket::Response(initialize_response) = response {
+            match initialize_response {
+                MCPResponse::Success {
+                    jsonrpc,
+                    id,
+                    result,
+                } => {
+                    if jsonrpc != JSON_RPC {
+                        return Err(NexaError::MCPConnection(String::from(
+                            "Incorrect JSON RPC version",
+                        )));
                     }
-                }
 
-                Err(NexaError::MCPConnection(String::from(
-                    "Incorrect server response during initialization phase",
-                )))
+                    if id != request_id {
+                        return Err(NexaError::MCPConnection(String::from(
+                            "Incorrect response id",
+                        )));
+                    }
+
+                    // Update Server Configuration
+                    let server_config_received: ServerConfiguration =
+                        serde_json::from_value(result)?;
+                    let mut server_config_handle = self.server_config.write().await;
+                    *server_config_handle = server_config_received;
+
+                    // Update Status
+                    let mut status_handle = self.status.write().await;
+                    *status_handle = MCPStatus::Connected;
+
+                    let initialized_notification = MCPNotification {
+                        jsonrpc: JSON_RPC.to_string(),
+                        method: String::from("notifications/initialized"),
+                        params: None,
+                    };
+
+                    self.transport_input
+                        .send(serde_json::to_value(initialized_notification)?)
+                        .await?;
+
+                    return Ok(());
+                }
+                MCPResponse::Fail { jsonrpc, id, error } => {
+                    return Err(NexaError::MCPConnection(format!(
+                        "Initialization Failed: {}",
+                        error.message
+                    )))
+                }
             }
-            Transport::Http => Err(NexaError::MCPConnection(String::from("Unimplemented"))),
         }
+
+        Err(NexaError::MCPConnection(String::from(
+            "Incorrect server response during initialization phase",
+        )))
     }
 
-    async fn list_tools(&self) -> Result<(), NexaError> {
-        match &self.transport_type {
-            Transport::Stdio => {
-                let tools_list_request = MCPRequest {
-                    jsonrpc: JSON_RPC.to_string(),
-                    id: Id::NumberId(self.get_request_id().await),
-                    method: String::from("tools/list"),
-                    params: None,
-                };
-
-                let _ = self
-                    .transport_input
-                    .send(serde_json::to_value(tools_list_request)?)
-                    .await?;
-
-                let response = self
-                    .transport_output
-                    .lock()
-                    .await
-                    .as_mut()
-                    .ok_or(NexaError::MCPConnection(String::from("Missing Stdout")))?
-                    .receive()
-                    .await?;
-
-                if let MCPDataPacket::Response(tools_list_response) = response {
-                    match tools_list_response {
-                        MCPResponse::Success {
-                            jsonrpc,
-                            id,
-                            result,
-                        } => {
-                            let tools_list_result: ListToolsResult =
-                                serde_json::from_value(result)?;
-                            let mut tool_list_handle = self.tool_list.write().await;
-                            for tool in tools_list_result.tools.iter() {
-                                tool_list_handle.insert(tool.name.clone(), tool.clone());
-                            }
-                            return Ok(());
-                        }
-                        MCPResponse::Fail { jsonrpc, id, error } => {
-                            return Err(NexaError::MCPConnection(String::from(format!(
-                                "Tools List Failed: {}",
-                                error.message
-                            ))))
-                        }
-                    }
+    // Follows `nextCursor` from page to page and returns every page. A server
+    // that keeps handing out cursors is cut off with an error after
+    // MCP_MAX_LIST_PAGES.
+    async fn list_all<T: DeserializeOwned>(&self, method: &str) -> Result<Vec<T>, NexaError> {
+        let mut pages = vec![];
+        let mut cursor: Option<String> = None;
+
+        for _ in 0..MCP_MAX_LIST_PAGES {
+            let params = cursor.as_ref().map(|cursor| json!({ "cursor": cursor }));
+            let result = match self.send_request(method, params).await? {
+                MCPResponse::Success { result, .. } => result,
+                MCPResponse::Fail { error, .. } => {
+                    return Err(NexaError::MCPConnection(format!(
+                        "{} failed: {}",
+                        method, error.message
+                    )))
                 }
+            };
+
+            let paginated: Paginated<T> = serde_json::from_value(result)?;
+            pages.push(paginated.page);
 
-                Err(NexaError::MCPConnection(String::from(
-                    "Incorrect server response during initialization phase",
-                )))
+            if paginated.next_cursor.is_none() || paginated.next_cursor == cursor {
+                return Ok(pages);
             }
-            Transport::Http => Err(NexaError::MCPConnection(String::from("Unimplemented"))),
+            cursor = paginated.next_cursor;
         }
+
+        Err(NexaError::MCPConnection(format!(
+            "{} still had more after {} pages",
+            method, MCP_MAX_LIST_PAGES
+        )))
+    }
+
+    // The whole list is fetched before it replaces the old one, so a chat
+    // never sees half of it. Also called again on a list_changed notification.
+    pub async fn list_tools(&self) -> Result<(), NexaError> {
+        let pages: Vec<ListToolsResult> = self.list_all("tools/list").await?;
+
+        *self.tool_list.write().await = pages
+            .into_iter()
+            .flat_map(|page| page.tools)
+            .map(|tool| (tool.name.clone(), tool))
+            .collect();
+
+        Ok(())
+    }
+
+    // Only there once the listener runs, and only for the first caller
+    pub async fn take_events(&self) -> Option<UnboundedReceiver<MCPClientEvent>> {
+        self.events.lock().await.take()
     }
 
     pub async fn start_listening(&self) -> Result<(), NexaError> {
@@ -358,8 +539,11 @@ impl MCPClient {
             .ok_or(NexaError::MCPConnection(String::from("Missing Stdout")))?;
         let cancel_token = self.cancel_token.clone();
         let tool_calls_map = self.tool_calls_map.clone();
+        let (events, events_receiver) = mpsc::unbounded_channel();
+        *self.events.lock().await = Some(events_receiver);
 
         task::spawn(async move {
+            let mut closed = None;
             loop {
                 select! {
                     res = cancel_token.cancelled() => {
@@ -376,28 +560,38 @@ impl MCPClient {
                                         match &response {
                                             MCPResponse::Success{jsonrpc, id, result} => {
                                                 dbg!(&id);
-                                                if let Some(response_pipe) = map.remove(id) {
+                                                if let Some(response_pipe) = map.as_mut().and_then(|map| map.remove(id)) {
                                                     let _ = response_pipe.send(response);
                                                 }
                                             }
                                             MCPResponse::Fail{jsonrpc, id, error} => {
-                                                if let Some(response_pipe) = map.remove(id) {
+                                                if let Some(response_pipe) = map.as_mut().and_then(|map| map.remove(id)) {
                                                     let _ = response_pipe.send(response);
                                                 }
                                             }
                                         }
                                     }
                                     MCPDataPacket::Request(request) => {}
-                                    MCPDataPacket::Notification(notification) => {}
+                                    MCPDataPacket::Notification(notification) => {
+                                        if notification.method.ends_with("/list_changed") {
+                                            let _ = events.send(MCPClientEvent::ListChanged(notification.method));
+                                        }
+                                    }
                                 }
                             }
                             Err(e) => {
+                                closed = Some(e.to_string());
                                 break
                             }
                         }
                     }
                 }
             }
+
+            *tool_calls_map.lock().await = None;
+            if let Some(error) = closed {
+                let _ = events.send(MCPClientEvent::Closed(error));
+            }
         });
 
         Ok(())
@@ -415,10 +609,7 @@ impl MCPClient {
         }
 
         let id = Id::NumberId(self.get_request_id().await);
-        let (tx, tr) = oneshot::channel::<MCPResponse>();
-
-        let mut tool_calls_map_handle = self.tool_calls_map.lock().await;
-        tool_calls_map_handle.insert(id.clone(), tx);
+        let tr = self.wait_for_response(&id).await?;
 
         let name: String = name.into();
         let call_tool_request = MCPRequest {
@@ -461,6 +652,80 @@ mod tests {
 
     use super::*;
 
+    // The second page is only handed out for the cursor the first one gave
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn paginated_tools_list_test() {
+        let script = r#"
+            read -r line
+            printf '{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2025-06-18","capabilities":{"tools":{}},"serverInfo":{"name":"paged","version":"1.0.0"}}}\n'
+            read -r line
+            read -r line
+            printf '{"jsonrpc":"2.0","id":2,"result":{"tools":[{"name":"first","inputSchema":{"type":"object"}}],"nextCursor":"page-2"}}\n'
+            read -r line
+            case "$line" in
+                *'"cursor":"page-2"'*) printf '{"jsonrpc":"2.0","id":3,"result":{"tools":[{"name":"second","inputSchema":{"type":"object"}}]}}\n' ;;
+                *) printf '{"jsonrpc":"2.0","id":3,"error":{"code":-32602,"message":"Invalid cursor"}}\n' ;;
+            esac
+            cat > /dev/null
+        "#;
+        let client = MCPClient::new_stdio_client("sh", ["-c", script]).unwrap();
+        client.start_listening().await.unwrap();
+
+        let mut names: Vec<String> = client
+            .get_tool_list()
+            .await
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["first", "second"]);
+    }
+
+    // The notification comes right behind the first list, the second list is
+    // fetched through the running listener. The server quits after that.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn tools_list_changed_test() {
+        let script = r#"
+            read -r line
+            printf '{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2025-06-18","capabilities":{"tools":{"listChanged":true}},"serverInfo":{"name":"changing","version":"1.0.0"}}}\n'
+            read -r line
+            read -r line
+            printf '{"jsonrpc":"2.0","id":2,"result":{"tools":[{"name":"first","inputSchema":{"type":"object"}}]}}\n'
+            printf '{"jsonrpc":"2.0","method":"notifications/tools/list_changed"}\n'
+            read -r line
+            printf '{"jsonrpc":"2.0","id":3,"result":{"tools":[{"name":"second","inputSchema":{"type":"object"}},{"name":"third","inputSchema":{"type":"object"}}]}}\n'
+        "#;
+        let client = MCPClient::new_stdio_client("sh", ["-c", script]).unwrap();
+        client.start_listening().await.unwrap();
+        assert_eq!(client.get_tool_list().await.len(), 1);
+
+        let mut events = client.take_events().await.unwrap();
+        assert!(client.take_events().await.is_none());
+        assert_eq!(
+            events.recv().await.unwrap(),
+            MCPClientEvent::ListChanged(MCP_TOOLS_LIST_CHANGED.to_string())
+        );
+
+        client.list_tools().await.unwrap();
+        let mut names: Vec<String> = client
+            .get_tool_list()
+            .await
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["second", "third"]);
+
+        assert!(matches!(
+            events.recv().await.unwrap(),
+            MCPClientEvent::Closed(_)
+        ));
+        assert!(client.list_tools().await.is_err());
+        assert!(client.call_tool("second", json!({})).await.is_err());
+    }
+
     #[tokio::test]
     async fn client_test() {
         let mut client = MCPClient::new_stdio_client(
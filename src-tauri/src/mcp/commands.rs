@@ -1,38 +1,32 @@
-use std::sync::Arc;
-
-use serde_json::{json, Value};
-use tauri::{AppHandle, Emitter, State};
-use tokio::sync::RwLock;
-
 use crate::{
     error::NexaError,
-    mcp::{client::MCPClient, structs::EmittedMCPResponse},
+    mcp::{
+        manager::{start_mcp_servers, MCPServerStatus},
+        structs::{EmittedMCPResponse, JSON_RPC},
+    },
+    tools::native::{get_native_tool_result, NATIVE_TOOLS_SERVER_NAME},
     AppData,
 };
+use serde_json::{json, Value};
+use tauri::{AppHandle, Emitter, State};
 
+// Stops every running server and starts the ones in mcp.json again, so edits
+// to the file apply without restarting the app
 #[tauri::command]
-pub async fn initialize_mcp_client(state: State<'_, AppData>) -> Result<(), NexaError> {
-    let mut client = MCPClient::new_stdio_client(
-        "uvx",
-        [
-            "--env-file",
-            "/Users/chihlee/temp/.env",
-            "--from",
-            "git+https://github.com/Jazzcort/status-report-assistant-mcp",
-            "mcp-serve",
-        ],
-    )?;
-
-    let _ = client.start_listening().await;
-    let server_config = client.get_server_config().await;
-
-    let mut mcp_clients_map = state.mcp_clients.write().await;
-
-    mcp_clients_map.insert(server_config.server_info.name, Arc::new(client));
+pub async fn reload_mcp_servers(app: AppHandle) -> Result<Vec<MCPServerStatus>, NexaError> {
+    start_mcp_servers(&app).await
+}
 
-    dbg!("successfully initialized mcps");
+#[tauri::command]
+pub async fn get_mcp_server_statuses(
+    state: State<'_, AppData>,
+) -> Result<Vec<MCPServerStatus>, NexaError> {
+    Ok(state.mcp_server_statuses.read().await.clone())
+}
 
-    Ok(())
+#[tauri::command]
+pub fn get_mcp_config_path(state: State<'_, AppData>) -> String {
+    state.mcp_config_path.to_string_lossy().to_string()
 }
 
 #[tauri::command]
@@ -49,14 +43,42 @@ pub async fn call_tool(
 
     dbg!(&arguments);
 
-    let mcp_client_map = state.mcp_clients.read().await;
-    let mcp_client = mcp_client_map
+    if server_name == NATIVE_TOOLS_SERVER_NAME {
+        let result = state
+            .native_tools
+            .lock()
+            .await
+            .call_tool(&function_name, &arguments);
+        let _ = app.emit(
+            "mcp_response",
+            EmittedMCPResponse {
+                request_id: request_id.clone(),
+                response_id,
+                response: json!({
+                    "jsonrpc": JSON_RPC,
+                    "id": request_id,
+                    "result": get_native_tool_result(result),
+                }),
+            },
+        );
+
+        return Ok(());
+    }
+
+    // Not held while waiting for the answer, a reload or a broken connection
+    // has to be able to take the client out in the meantime
+    let mcp_client = state
+        .mcp_clients
+        .read()
+        .await
         .get(&server_name)
+        .cloned()
         .ok_or(NexaError::MCPToolCall(String::from(
             "Can't find the MCP Server with the given name",
         )))?;
 
     let mut receiver = mcp_client.call_tool(function_name, arguments).await?;
+    drop(mcp_client);
 
     // Need to install timeout mechanism
 
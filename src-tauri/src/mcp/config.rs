@@ -0,0 +1,132 @@
+use crate::error::NexaError;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+pub(crate) static MCP_CONFIG_FILENAME: &str = "mcp.json";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MCPTransportType {
+    Stdio,
+    // Streamable HTTP, "streamable-http" and "streamableHttp" are spelled
+    // both ways in the wild
+    #[serde(alias = "streamable-http", alias = "streamableHttp")]
+    Http,
+    Sse,
+}
+
+// One entry of the `mcpServers` map, in the format other MCP clients share.
+// Local servers set `command`, remote ones `url`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MCPServerConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    // Added on top of the app's own environment
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+    #[serde(default, rename = "type", alias = "transport")]
+    pub transport_type: Option<MCPTransportType>,
+    #[serde(default)]
+    pub disabled: bool,
+}
+
+impl MCPServerConfig {
+    // Left out, the type follows from whether a command or a url is given
+    pub fn get_transport_type(&self) -> Result<MCPTransportType, NexaError> {
+        match (&self.transport_type, &self.command, &self.url) {
+            (Some(transport_type), _, _) => Ok(transport_type.clone()),
+            (None, Some(_), _) => Ok(MCPTransportType::Stdio),
+            (None, None, Some(_)) => Ok(MCPTransportType::Http),
+            (None, None, None) => Err(NexaError::MCPConnection(String::from(
+                "Neither a command nor a url is set",
+            ))),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MCPConfig {
+    // Sorted by name, so servers are always started and listed in the same order
+    #[serde(default)]
+    pub mcp_servers: BTreeMap<String, MCPServerConfig>,
+}
+
+// No file just means no servers. A file that doesn't parse is an error, so a
+// typo doesn't look like every server vanished.
+pub(crate) fn load_mcp_config(path: &Path) -> Result<MCPConfig, NexaError> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(MCPConfig::default()),
+        Err(e) => return Err(e.into()),
+    };
+
+    serde_json::from_slice(&bytes)
+        .map_err(|e| NexaError::MCPConnection(format!("Invalid {}: {}", path.display(), e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn mcp_config_test() {
+        let config: MCPConfig = serde_json::from_value(json!({
+            "mcpServers": {
+                "status-report": {
+                    "command": "uvx",
+                    "args": ["--from", "git+https://github.com/Jazzcort/status-report-assistant-mcp", "mcp-serve"],
+                    "env": {"GITHUB_TOKEN": "secret"},
+                    "cwd": "/tmp"
+                },
+                "remote": {
+                    "url": "https://mcp.example.com/mcp",
+                    "headers": {"Authorization": "Bearer secret"}
+                },
+                "legacy": {
+                    "type": "sse",
+                    "url": "https://mcp.example.com/sse",
+                    "disabled": true
+                },
+                "broken": {}
+            }
+        }))
+        .unwrap();
+
+        let names: Vec<&String> = config.mcp_servers.keys().collect();
+        assert_eq!(names, vec!["broken", "legacy", "remote", "status-report"]);
+
+        let servers = &config.mcp_servers;
+        assert_eq!(
+            servers["status-report"].get_transport_type().unwrap(),
+            MCPTransportType::Stdio
+        );
+        assert_eq!(servers["status-report"].env["GITHUB_TOKEN"], "secret");
+        assert_eq!(
+            servers["remote"].get_transport_type().unwrap(),
+            MCPTransportType::Http
+        );
+        assert_eq!(
+            servers["legacy"].get_transport_type().unwrap(),
+            MCPTransportType::Sse
+        );
+        assert!(servers["legacy"].disabled);
+        assert!(servers["broken"].get_transport_type().is_err());
+
+        let missing = std::env::temp_dir().join("nexa-missing-mcp.json");
+        assert_eq!(load_mcp_config(&missing).unwrap(), MCPConfig::default());
+    }
+}
@@ -3,6 +3,7 @@ use crate::mcp::structs::{Id, MCPDataPacket};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::{self, json, Value};
+use std::collections::HashMap;
 use std::process::Stdio;
 use std::sync::Arc;
 use tokio;
@@ -69,19 +70,33 @@ pub(crate) struct MCPStdioConnection {
     _child_process: Child,
 }
 
+// The server is killed along with the writer, so dropping a client or
+// reloading the config doesn't leave processes behind
 pub(crate) fn mcp_stdio_connect<S, I>(
     command: S,
     args: I,
+    env: &HashMap<String, String>,
+    cwd: Option<&str>,
 ) -> Result<(StdioWriter, StdioReader), NexaError>
 where
     S: Into<String>,
     I: IntoIterator<Item = S>,
 {
-    let mut child = Command::new(command.into())
+    let command: String = command.into();
+    let mut builder = Command::new(&command);
+    builder
         .args(args.into_iter().map(|s| s.into()))
+        .envs(env)
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
-        .spawn()?;
+        .kill_on_drop(true);
+    if let Some(cwd) = cwd {
+        builder.current_dir(cwd);
+    }
+
+    let mut child = builder
+        .spawn()
+        .map_err(|e| NexaError::MCPConnection(format!("Can't start {}: {}", command, e)))?;
 
     let stdin = child
         .stdin
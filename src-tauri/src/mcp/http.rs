@@ -0,0 +1,631 @@
+use crate::api::transport::{HttpRequest, HttpResponse, HttpTransport};
+use crate::error::NexaError;
+use crate::mcp::client::MCP_PROTOCOL_VERSION;
+use crate::mcp::connection::{MCPTransportReader, MCPTransportWriter};
+use crate::mcp::structs::MCPDataPacket;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::{self, Stream, StreamExt};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::select;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::time::{sleep, Duration};
+use tokio_util::sync::CancellationToken;
+
+static MCP_SESSION_ID_HEADER: &str = "Mcp-Session-Id";
+static MCP_PROTOCOL_VERSION_HEADER: &str = "MCP-Protocol-Version";
+static LAST_EVENT_ID_HEADER: &str = "Last-Event-ID";
+
+// Used until the server sends a `retry` field of its own
+static DEFAULT_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+static MAX_RECONNECT_ATTEMPTS: u32 = 5;
+
+static STREAMABLE_HTTP_REJECTED: &str = "The server doesn't take Streamable HTTP";
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct SseEvent {
+    pub id: Option<String>,
+    pub event: Option<String>,
+    pub data: String,
+    pub retry: Option<u64>,
+}
+
+struct SseParser<S> {
+    byte_stream: S,
+    buffer: Vec<u8>,
+    event: SseEvent,
+    data_lines: Vec<String>,
+    finished: bool,
+}
+
+impl<S> SseParser<S> {
+    // An event without any data line is only there to move the event id or
+    // the retry delay, it's still passed on for those
+    fn take_event(&mut self) -> Option<SseEvent> {
+        let mut event = std::mem::take(&mut self.event);
+        if self.data_lines.is_empty() && event.id.is_none() && event.retry.is_none() {
+            return None;
+        }
+
+        event.data = self.data_lines.drain(..).collect::<Vec<_>>().join("\n");
+        Some(event)
+    }
+
+    fn read_field(&mut self, line: &str) {
+        // Lines starting with a colon are comments, servers send them to
+        // keep the connection open
+        if line.starts_with(':') {
+            return;
+        }
+
+        let (name, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match name {
+            "data" => self.data_lines.push(value.to_string()),
+            "event" => self.event.event = Some(value.to_string()),
+            "id" if !value.contains('\0') => self.event.id = Some(value.to_string()),
+            "retry" => self.event.retry = value.parse().ok().or(self.event.retry),
+            _ => {}
+        }
+    }
+}
+
+// Unlike `split_lines`, blank lines matter here since they end an event
+pub(crate) fn sse_events<S>(byte_stream: S) -> impl Stream<Item = Result<SseEvent, NexaError>>
+where
+    S: Stream<Item = Result<Bytes, NexaError>> + Unpin,
+{
+    let parser = SseParser {
+        byte_stream,
+        buffer: vec![],
+        event: SseEvent::default(),
+        data_lines: vec![],
+        finished: false,
+    };
+
+    stream::unfold(parser, |mut parser| async move {
+        loop {
+            if let Some(pos) = parser.buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = parser.buffer.drain(..=pos).collect();
+                let line = String::from_utf8_lossy(&line);
+                let line = line.trim_end_matches(['\n', '\r']);
+
+                if line.is_empty() {
+                    if let Some(event) = parser.take_event() {
+                        return Some((Ok(event), parser));
+                    }
+                } else {
+                    parser.read_field(line);
+                }
+                continue;
+            }
+
+            if parser.finished {
+                // A stream cut off in the middle of an event drops that event
+                return None;
+            }
+
+            match parser.byte_stream.next().await {
+                Some(Ok(bytes)) => parser.buffer.extend_from_slice(&bytes),
+                Some(Err(e)) => {
+                    parser.finished = true;
+                    parser.buffer.clear();
+                    return Some((Err(e), parser));
+                }
+                None => parser.finished = true,
+            }
+        }
+    })
+}
+
+// A message can also be a JSON-RPC batch
+pub(crate) fn parse_packets(data: &[u8]) -> Result<Vec<MCPDataPacket>, NexaError> {
+    match serde_json::from_slice::<Value>(data)? {
+        Value::Array(values) => values
+            .into_iter()
+            .map(|value| Ok(serde_json::from_value(value)?))
+            .collect(),
+        value => Ok(vec![serde_json::from_value(value)?]),
+    }
+}
+
+// Servers that only speak the older HTTP+SSE transport answer the initialize
+// POST with a 4xx, which is the cue to try that one instead
+pub(crate) fn is_streamable_http_rejected(e: &NexaError) -> bool {
+    matches!(e, NexaError::MCPConnection(message) if message.starts_with(STREAMABLE_HTTP_REJECTED))
+}
+
+fn is_method(data: &Value, method: &str) -> bool {
+    data.get("method").and_then(|value| value.as_str()) == Some(method)
+}
+
+// What the writer and the tasks reading event streams share. Everything the
+// server sends, whichever response or stream it came on, ends up in `sender`.
+struct HttpSession {
+    transport: Arc<dyn HttpTransport>,
+    url: String,
+    headers: HashMap<String, String>,
+    session_id: Mutex<Option<String>>,
+    sender: UnboundedSender<Result<MCPDataPacket, NexaError>>,
+    cancel_token: CancellationToken,
+}
+
+impl HttpSession {
+    fn get_session_id(&self) -> Option<String> {
+        self.session_id.lock().unwrap().clone()
+    }
+
+    // Every request but the initialize one carries the session and the
+    // protocol version
+    fn get_request(&self, mut request: HttpRequest, initialized: bool) -> HttpRequest {
+        for (name, value) in self.headers.iter() {
+            request = request.header(name, value);
+        }
+        if !initialized {
+            return request;
+        }
+
+        if let Some(session_id) = self.get_session_id() {
+            request = request.header(MCP_SESSION_ID_HEADER, session_id);
+        }
+        request.header(MCP_PROTOCOL_VERSION_HEADER, MCP_PROTOCOL_VERSION)
+    }
+
+    // `answered` tells whether a response went by, which is what a POST
+    // stream is waiting for. Errs when the stream broke off.
+    async fn forward_events(
+        &self,
+        response: HttpResponse,
+        last_event_id: &mut Option<String>,
+        reconnect_delay: &mut Duration,
+        answered: &mut bool,
+    ) -> Result<(), NexaError> {
+        let mut events = Box::pin(sse_events(response.body));
+
+        loop {
+            let event = select! {
+                _ = self.cancel_token.cancelled() => return Ok(()),
+                event = events.next() => event,
+            };
+
+            let event = match event {
+                Some(Ok(event)) => event,
+                Some(Err(e)) => return Err(e),
+                None => return Ok(()),
+            };
+
+            if event.id.is_some() {
+                *last_event_id = event.id;
+            }
+            if let Some(retry) = event.retry {
+                *reconnect_delay = Duration::from_millis(retry);
+            }
+            if event.data.is_empty() {
+                continue;
+            }
+
+            // Like a line over stdio, a message that can't be read ends the
+            // connection
+            match parse_packets(event.data.as_bytes()) {
+                Ok(packets) => {
+                    for packet in packets {
+                        *answered |= matches!(packet, MCPDataPacket::Response(_));
+                        let _ = self.sender.send(Ok(packet));
+                    }
+                }
+                Err(e) => {
+                    let _ = self.sender.send(Err(e));
+                }
+            }
+        }
+    }
+
+    // Reads the GET stream, reconnecting with the last event id seen so the
+    // server can replay what was missed. With `until_answered` it only picks
+    // up a POST stream that broke off before its response.
+    async fn listen(self: Arc<Self>, mut last_event_id: Option<String>, until_answered: bool) {
+        let mut reconnect_delay = DEFAULT_RECONNECT_DELAY;
+        let mut failed_attempts = 0;
+        let mut last_error = None;
+
+        loop {
+            let mut request = HttpRequest::get(&self.url).header("Accept", "text/event-stream");
+            if let Some(last_event_id) = &last_event_id {
+                request = request.header(LAST_EVENT_ID_HEADER, last_event_id);
+            }
+
+            let result = select! {
+                _ = self.cancel_token.cancelled() => return,
+                result = self.transport.send(self.get_request(request, true)) => result,
+            };
+            match result {
+                Ok(response) if response.is_success() => {
+                    failed_attempts = 0;
+                    let mut answered = false;
+                    let result = self
+                        .forward_events(
+                            response,
+                            &mut last_event_id,
+                            &mut reconnect_delay,
+                            &mut answered,
+                        )
+                        .await;
+                    if answered && until_answered {
+                        return;
+                    }
+                    if let Err(e) = result {
+                        last_error = Some(e);
+                    }
+                }
+                // The server doesn't offer a stream of its own (405 by the
+                // spec), it still answers every POST
+                Ok(response) if !until_answered && (400..500).contains(&response.status) => return,
+                Ok(response) => {
+                    failed_attempts += 1;
+                    last_error = Some(NexaError::MCPConnection(format!(
+                        "HTTP {}",
+                        response.status
+                    )));
+                }
+                Err(e) => {
+                    failed_attempts += 1;
+                    last_error = Some(e);
+                }
+            }
+
+            // Whatever the server sends from now on would be lost, a pending
+            // request among it
+            if failed_attempts >= MAX_RECONNECT_ATTEMPTS {
+                let error = last_error.map(|e| e.to_string()).unwrap_or_default();
+                let _ = self.sender.send(Err(NexaError::MCPConnection(format!(
+                    "Lost the event stream of the MCP server: {}",
+                    error
+                ))));
+                return;
+            }
+            select! {
+                _ = self.cancel_token.cancelled() => return,
+                _ = sleep(reconnect_delay) => {}
+            }
+        }
+    }
+}
+
+pub(crate) struct StreamableHttpWriter {
+    session: Arc<HttpSession>,
+}
+
+#[async_trait]
+impl MCPTransportWriter for StreamableHttpWriter {
+    async fn send(&self, data: Value) -> Result<(), NexaError> {
+        let initialize = is_method(&data, "initialize");
+        let request = HttpRequest::post(&self.session.url)
+            .header("Accept", "application/json, text/event-stream")
+            .json(&data)?;
+        let response = self
+            .session
+            .transport
+            .send(self.session.get_request(request, !initialize))
+            .await?;
+
+        if initialize && (400..500).contains(&response.status) {
+            let status = response.status;
+            let body = response.text().await.unwrap_or_default();
+            return Err(NexaError::MCPConnection(format!(
+                "{} (HTTP {}: {})",
+                STREAMABLE_HTTP_REJECTED, status, body
+            )));
+        }
+        if response.status == 404 && self.session.get_session_id().is_some() {
+            return Err(NexaError::MCPConnection(String::from(
+                "The server ended the session",
+            )));
+        }
+        let response = response.error_for_status(NexaError::MCPConnection).await?;
+
+        if initialize {
+            *self.session.session_id.lock().unwrap() =
+                response.header(MCP_SESSION_ID_HEADER).map(String::from);
+        }
+        if is_method(&data, "notifications/initialized") {
+            tokio::spawn(self.session.clone().listen(None, false));
+        }
+
+        // Accepted, a notification or a response has nothing to answer with
+        if response.status == 202 {
+            return Ok(());
+        }
+
+        let event_stream = response
+            .header("Content-Type")
+            .is_some_and(|content_type| content_type.starts_with("text/event-stream"));
+        if event_stream {
+            // The response may take a while, so the stream is read on its own
+            // and `send` returns as soon as the server took the request
+            let session = self.session.clone();
+            tokio::spawn(async move {
+                let mut last_event_id = None;
+                let mut reconnect_delay = DEFAULT_RECONNECT_DELAY;
+                let mut answered = false;
+                let result = session
+                    .forward_events(
+                        response,
+                        &mut last_event_id,
+                        &mut reconnect_delay,
+                        &mut answered,
+                    )
+                    .await;
+                if answered || session.cancel_token.is_cancelled() {
+                    return;
+                }
+
+                // Only a stream with event ids can be picked up again
+                match (last_event_id, result) {
+                    (Some(last_event_id), _) => session.listen(Some(last_event_id), true).await,
+                    (None, Err(e)) => {
+                        let _ = session.sender.send(Err(e));
+                    }
+                    (None, Ok(())) => {
+                        let _ = session
+                            .sender
+                            .send(Err(NexaError::MCPConnection(String::from(
+                                "The MCP server ended the response stream without an answer",
+                            ))));
+                    }
+                }
+            });
+            return Ok(());
+        }
+
+        let body = response.bytes().await?;
+        if body.is_empty() {
+            return Ok(());
+        }
+        for packet in parse_packets(&body)? {
+            let _ = self.session.sender.send(Ok(packet));
+        }
+
+        Ok(())
+    }
+}
+
+// Tells the server the session is over, servers keep it around otherwise
+impl Drop for StreamableHttpWriter {
+    fn drop(&mut self) {
+        self.session.cancel_token.cancel();
+
+        if self.session.get_session_id().is_none() {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+
+        let request = self
+            .session
+            .get_request(HttpRequest::delete(&self.session.url), true);
+        let transport = self.session.transport.clone();
+        runtime.spawn(async move {
+            let _ = transport.send(request).await;
+        });
+    }
+}
+
+pub(crate) struct StreamableHttpReader {
+    receiver: UnboundedReceiver<Result<MCPDataPacket, NexaError>>,
+}
+
+#[async_trait]
+impl MCPTransportReader for StreamableHttpReader {
+    async fn receive(&mut self) -> Result<MCPDataPacket, NexaError> {
+        self.receiver
+            .recv()
+            .await
+            .unwrap_or(Err(NexaError::MCPConnection(String::from(
+                "The MCP server closed the connection",
+            ))))
+    }
+}
+
+pub(crate) fn mcp_http_connect(
+    transport: Arc<dyn HttpTransport>,
+    url: impl Into<String>,
+    headers: HashMap<String, String>,
+) -> (StreamableHttpWriter, StreamableHttpReader) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let session = HttpSession {
+        transport,
+        url: url.into(),
+        headers,
+        session_id: Mutex::new(None),
+        sender,
+        cancel_token: CancellationToken::new(),
+    };
+
+    (
+        StreamableHttpWriter {
+            session: Arc::new(session),
+        },
+        StreamableHttpReader { receiver },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::transport::HttpMethod;
+    use crate::mcp::client::MCPClient;
+    use crate::mcp::structs::MCPResponse;
+    use serde_json::json;
+
+    fn get_header<'a>(request: &'a HttpRequest, name: &str) -> Option<&'a str> {
+        request
+            .headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn get_response(status: u16, content_type: &str, chunks: Vec<String>) -> HttpResponse {
+        let chunks: Vec<Result<Bytes, NexaError>> = chunks
+            .into_iter()
+            .map(|chunk| Ok(Bytes::from(chunk)))
+            .collect();
+
+        HttpResponse {
+            status,
+            headers: vec![
+                (String::from("content-type"), content_type.to_string()),
+                (String::from("mcp-session-id"), String::from("session-1")),
+            ],
+            body: stream::iter(chunks).boxed(),
+        }
+    }
+
+    // Stands in for a Streamable HTTP server. The tools/list stream breaks
+    // off before its response, which only comes once the client resumes it.
+    #[derive(Default)]
+    struct StandInServer {
+        requests: Mutex<Vec<HttpRequest>>,
+    }
+
+    #[async_trait]
+    impl HttpTransport for StandInServer {
+        async fn send(&self, request: HttpRequest) -> Result<HttpResponse, NexaError> {
+            self.requests.lock().unwrap().push(request.clone());
+            let body: Value = request
+                .body
+                .as_ref()
+                .and_then(|body| serde_json::from_slice(body).ok())
+                .unwrap_or_default();
+
+            let response = match (&request.method, body["method"].as_str()) {
+                (HttpMethod::Post, Some("initialize")) => get_response(
+                    200,
+                    "application/json",
+                    vec![json!({
+                        "jsonrpc": "2.0",
+                        "id": body["id"],
+                        "result": {
+                            "protocolVersion": MCP_PROTOCOL_VERSION,
+                            "capabilities": {"tools": {}},
+                            "serverInfo": {"name": "stand-in", "version": "1.0.0"}
+                        }
+                    })
+                    .to_string()],
+                ),
+                (HttpMethod::Post, Some("tools/list")) => get_response(
+                    200,
+                    "text/event-stream",
+                    vec![String::from(": started\n\nid: list-1\nretry: 10\ndata:\n\n")],
+                ),
+                (HttpMethod::Post, Some("tools/call")) => get_response(
+                    200,
+                    "application/json",
+                    vec![json!({
+                        "jsonrpc": "2.0",
+                        "id": body["id"],
+                        "result": {"content": [{"type": "text", "text": "hi"}]}
+                    })
+                    .to_string()],
+                ),
+                (HttpMethod::Post, _) => get_response(202, "application/json", vec![]),
+                (HttpMethod::Get, _) if get_header(&request, LAST_EVENT_ID_HEADER).is_some() => {
+                    get_response(
+                        200,
+                        "text/event-stream",
+                        vec![
+                            String::from("id: list-2\r\ndata: {\"jsonrpc\":\"2.0\",\"id\":2,"),
+                            String::from("\"result\":{\"tools\":[{\"name\":\"echo\",\"inputSchema\":{\"type\":\"object\"}}]}}\r\n\r\n"),
+                        ],
+                    )
+                }
+                (HttpMethod::Get, _) => HttpResponse {
+                    body: stream::iter(vec![Ok(Bytes::from(
+                        "data: {\"jsonrpc\":\"2.0\",\"method\":\"notifications/message\",\"params\":{\"level\":\"info\",\"data\":\"hello\"}}\n\n",
+                    ))])
+                    .chain(stream::pending())
+                    .boxed(),
+                    ..get_response(200, "text/event-stream", vec![])
+                },
+                (HttpMethod::Delete, _) => get_response(200, "application/json", vec![]),
+            };
+
+            Ok(response)
+        }
+    }
+
+    #[test]
+    fn sse_events_test() {
+        let chunks: Vec<Result<Bytes, NexaError>> = vec![
+            Ok(Bytes::from(": keep-alive\n\nevent: message\nid: 7\nda")),
+            Ok(Bytes::from(
+                "ta: first\r\ndata:second\r\n\r\nretry: 500\n\ndata: cut off",
+            )),
+        ];
+        let events: Vec<SseEvent> = futures::executor::block_on(
+            sse_events(stream::iter(chunks))
+                .map(|event| event.unwrap())
+                .collect(),
+        );
+
+        assert_eq!(
+            events,
+            vec![
+                SseEvent {
+                    id: Some(String::from("7")),
+                    event: Some(String::from("message")),
+                    data: String::from("first\nsecond"),
+                    retry: None,
+                },
+                SseEvent {
+                    retry: Some(500),
+                    ..Default::default()
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn streamable_http_client_test() {
+        let server = Arc::new(StandInServer::default());
+        let client = MCPClient::new_http_client(
+            server.clone(),
+            "http://localhost:8000/mcp",
+            HashMap::from([(String::from("Authorization"), String::from("Bearer secret"))]),
+        );
+
+        client.start_listening().await.unwrap();
+        assert_eq!(
+            client.get_server_config().await.server_info.name,
+            "stand-in"
+        );
+        assert_eq!(client.get_tool_list().await.len(), 1);
+
+        let response = client.call_tool("echo", json!({})).await.unwrap().await;
+        assert!(matches!(response, Ok(MCPResponse::Success { .. })));
+
+        drop(client);
+        sleep(Duration::from_millis(50)).await;
+
+        let requests = server.requests.lock().unwrap();
+        assert!(requests
+            .iter()
+            .all(|request| get_header(request, "Authorization") == Some("Bearer secret")));
+
+        // Only the initialize request goes out before there's a session
+        assert_eq!(get_header(&requests[0], MCP_SESSION_ID_HEADER), None);
+        assert!(requests[1..]
+            .iter()
+            .all(|request| get_header(request, MCP_SESSION_ID_HEADER) == Some("session-1")));
+
+        let resumed = requests
+            .iter()
+            .find(|request| get_header(request, LAST_EVENT_ID_HEADER).is_some())
+            .unwrap();
+        assert_eq!(get_header(resumed, LAST_EVENT_ID_HEADER), Some("list-1"));
+        assert_eq!(requests.last().unwrap().method, HttpMethod::Delete);
+    }
+}
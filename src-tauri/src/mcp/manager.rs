@@ -1,30 +1,299 @@
+use crate::api::transport::HttpTransport;
+use crate::error::NexaError;
+use crate::mcp::client::{MCPClient, MCPClientEvent, MCP_TOOLS_LIST_CHANGED};
+use crate::mcp::config::{load_mcp_config, MCPServerConfig, MCPTransportType};
+use crate::mcp::http::is_streamable_http_rejected;
+use crate::tools::native::NATIVE_TOOLS_SERVER_NAME;
+use crate::AppData;
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::time::{timeout, Duration};
 
-use crate::mcp::connection;
-use serde_json::json;
-use std::pin::pin;
-
-// async fn testing() {
-//     let mut manager: HashMap<String, Box<dyn connection::MCPConnection>> = HashMap::new();
-//
-//     manager.insert(
-//         "function1".to_string(),
-//         Box::new(connection::MCPStdioConnection::new()),
-//     );
-//
-//     if let Some(conn) = manager.get("function1") {
-//         let a = conn.call_tool(None);
-//         let result = a.await;
-//         dbg!(result);
-//     }
-// }
+// uvx and npx may have to download the server first
+static MCP_START_TIMEOUT: Duration = Duration::from_secs(60);
+// A refetch holds on to the client, it mustn't keep it alive forever
+static MCP_REFRESH_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MCPServerState {
+    Starting,
+    Connected,
+    Failed,
+    Disabled,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MCPServerStatus {
+    // The key in mcp.json, which is also what tools are prefixed with
+    pub name: String,
+    pub transport_type: Option<MCPTransportType>,
+    pub state: MCPServerState,
+    pub error: Option<String>,
+    // What the server calls itself in the handshake
+    pub server_name: Option<String>,
+    pub tool_count: usize,
+}
+
+impl MCPServerStatus {
+    fn new(name: &str, config: &MCPServerConfig, state: MCPServerState) -> Self {
+        MCPServerStatus {
+            name: name.to_string(),
+            transport_type: config.get_transport_type().ok(),
+            state,
+            error: None,
+            server_name: None,
+            tool_count: 0,
+        }
+    }
+}
+
+// A server that never answers the initialize request would otherwise hold up
+// the others forever
+async fn start_mcp_client(client: MCPClient) -> Result<MCPClient, NexaError> {
+    timeout(MCP_START_TIMEOUT, client.start_listening())
+        .await
+        .map_err(|_| {
+            NexaError::MCPConnection(format!(
+                "No answer within {} seconds",
+                MCP_START_TIMEOUT.as_secs()
+            ))
+        })??;
+
+    Ok(client)
+}
+
+// A url without a type could be either HTTP transport, so one refusing
+// Streamable HTTP is tried again over the older HTTP+SSE one
+pub(crate) async fn connect_mcp_server(
+    config: &MCPServerConfig,
+    http_transport: Arc<dyn HttpTransport>,
+) -> Result<MCPClient, NexaError> {
+    let client = MCPClient::from_config(config, http_transport.clone()).await?;
+    let transport_type = client.get_transport_type();
+
+    match start_mcp_client(client).await {
+        Err(e) if transport_type == MCPTransportType::Http && is_streamable_http_rejected(&e) => {
+            let legacy_config = MCPServerConfig {
+                transport_type: Some(MCPTransportType::Sse),
+                ..config.clone()
+            };
+            start_mcp_client(MCPClient::from_config(&legacy_config, http_transport).await?).await
+        }
+        result => result,
+    }
+}
+
+async fn update_mcp_server_status(
+    app: &AppHandle,
+    name: &str,
+    event: &str,
+    update: impl FnOnce(&mut MCPServerStatus),
+) {
+    let state = app.state::<AppData>();
+    let mut statuses = state.mcp_server_statuses.write().await;
+    if let Some(status) = statuses.iter_mut().find(|status| status.name == name) {
+        update(status);
+        let _ = app.emit(event, status.clone());
+    }
+}
+
+// Keeps a server's status in line with what its client reports. The tools are
+// fetched again whenever the server says they changed, so the next chat gets
+// the new ones, and a broken connection takes the server out. Holds on to the
+// client weakly: once a reload drops it the listener stops, and with it this.
+async fn watch_mcp_client(app: AppHandle, name: String, client: &Arc<MCPClient>) {
+    let Some(mut events) = client.take_events().await else {
+        return;
+    };
+    let client = Arc::downgrade(client);
+
+    tauri::async_runtime::spawn(async move {
+        while let Some(event) = events.recv().await {
+            let Some(client) = client.upgrade() else {
+                return;
+            };
+
+            match event {
+                MCPClientEvent::ListChanged(method) if method == MCP_TOOLS_LIST_CHANGED => {
+                    let error = match timeout(MCP_REFRESH_TIMEOUT, client.list_tools()).await {
+                        Ok(Ok(())) => None,
+                        Ok(Err(e)) => Some(e.to_string()),
+                        Err(_) => Some(format!(
+                            "No answer within {} seconds",
+                            MCP_REFRESH_TIMEOUT.as_secs()
+                        )),
+                    };
+                    // The previous tools stay when fetching the new ones fails
+                    let tool_count = client.get_tool_list().await.len();
+                    update_mcp_server_status(&app, &name, "mcp_tools_changed", |status| {
+                        status.tool_count = tool_count;
+                        status.error = error
+                            .map(|error| format!("Couldn't fetch the changed tools: {}", error));
+                    })
+                    .await;
+                }
+                // Resources and prompts aren't listed anywhere yet
+                MCPClientEvent::ListChanged(_) => {}
+                MCPClientEvent::Closed(error) => {
+                    // Unless a reload put another client in its place already
+                    let state = app.state::<AppData>();
+                    let mut mcp_clients = state.mcp_clients.write().await;
+                    if mcp_clients
+                        .get(&name)
+                        .is_some_and(|current| Arc::ptr_eq(current, &client))
+                    {
+                        mcp_clients.remove(&name);
+                        drop(mcp_clients);
+                        update_mcp_server_status(&app, &name, "mcp_server_failed", |status| {
+                            status.state = MCPServerState::Failed;
+                            status.error = Some(format!("Lost the connection: {}", error));
+                            status.tool_count = 0;
+                        })
+                        .await;
+                    }
+                    return;
+                }
+            }
+        }
+    });
+}
+
+// Starts every enabled server in mcp.json, all at the same time. One server
+// failing doesn't keep the others from starting, its error ends up in its
+// status. The servers running now keep serving chats until the new ones are
+// up and take their place; two reloads run one after the other.
+pub(crate) async fn start_mcp_servers(app: &AppHandle) -> Result<Vec<MCPServerStatus>, NexaError> {
+    let state = app.state::<AppData>();
+    let state = state.inner();
+    let _reload = state.mcp_reload.lock().await;
+    let config = load_mcp_config(&state.mcp_config_path)?;
+
+    *state.mcp_server_statuses.write().await = config
+        .mcp_servers
+        .iter()
+        .map(|(name, server)| {
+            let server_state = match server.disabled {
+                true => MCPServerState::Disabled,
+                false => MCPServerState::Starting,
+            };
+            MCPServerStatus::new(name, server, server_state)
+        })
+        .collect();
+
+    let starts = config.mcp_servers.iter().map(|(name, server)| async move {
+        if server.disabled {
+            return (
+                MCPServerStatus::new(name, server, MCPServerState::Disabled),
+                None,
+            );
+        }
+        if name == NATIVE_TOOLS_SERVER_NAME {
+            let status = MCPServerStatus {
+                error: Some(format!("{} is taken by the built-in tools", name)),
+                ..MCPServerStatus::new(name, server, MCPServerState::Failed)
+            };
+            return (status, None);
+        }
+
+        match connect_mcp_server(server, state.mcp_http_transport.clone()).await {
+            Ok(client) => {
+                let status = MCPServerStatus {
+                    transport_type: Some(client.get_transport_type()),
+                    server_name: Some(client.get_server_config().await.server_info.name),
+                    tool_count: client.get_tool_list().await.len(),
+                    ..MCPServerStatus::new(name, server, MCPServerState::Connected)
+                };
+                (status, Some(client))
+            }
+            Err(e) => {
+                let status = MCPServerStatus {
+                    error: Some(e.to_string()),
+                    ..MCPServerStatus::new(name, server, MCPServerState::Failed)
+                };
+                (status, None)
+            }
+        }
+    });
+    let results = join_all(starts).await;
+
+    let mut mcp_clients = HashMap::new();
+    let mut statuses = vec![];
+    for (status, client) in results {
+        if let Some(client) = client {
+            mcp_clients.insert(status.name.clone(), Arc::new(client));
+        }
+        statuses.push(status);
+    }
+    *state.mcp_server_statuses.write().await = statuses.clone();
+    // Dropping the old clients stops them and their watchers
+    *state.mcp_clients.write().await = mcp_clients.clone();
+
+    // Only once they are in place, so a watcher finds its client there
+    for (name, client) in mcp_clients {
+        watch_mcp_client(app.clone(), name, &client).await;
+    }
+
+    Ok(statuses)
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::api::transport::{Cassette, ReplayTransport};
+    use std::collections::HashMap;
+
+    // Just enough of a server to get through the handshake, it names itself
+    // after $SERVER_NAME and the directory it runs in
+    fn get_fake_server_config() -> MCPServerConfig {
+        let script = r#"
+            read -r line
+            printf '{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2025-06-18","capabilities":{"tools":{}},"serverInfo":{"name":"%s %s","version":"1.0.0"}}}\n' "$SERVER_NAME" "$PWD"
+            read -r line
+            read -r line
+            printf '{"jsonrpc":"2.0","id":2,"result":{"tools":[{"name":"echo","inputSchema":{"type":"object"}}]}}\n'
+            cat > /dev/null
+        "#;
+
+        MCPServerConfig {
+            command: Some(String::from("sh")),
+            args: vec![String::from("-c"), script.to_string()],
+            env: HashMap::from([(String::from("SERVER_NAME"), String::from("fake"))]),
+            cwd: Some(String::from("/")),
+            ..Default::default()
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn connect_mcp_server_test() {
+        // Any request that reaches the network fails
+        let http_transport: Arc<dyn HttpTransport> =
+            Arc::new(ReplayTransport::new(Cassette::default()));
+
+        let client = connect_mcp_server(&get_fake_server_config(), http_transport.clone())
+            .await
+            .unwrap();
+        assert_eq!(client.get_server_config().await.server_info.name, "fake /");
+        assert_eq!(client.get_tool_list().await.len(), 1);
+
+        let missing = MCPServerConfig {
+            command: Some(String::from("nexa-no-such-mcp-server")),
+            ..Default::default()
+        };
+        assert!(connect_mcp_server(&missing, http_transport.clone())
+            .await
+            .is_err_and(|e| e.to_string().contains("nexa-no-such-mcp-server")));
 
-    // #[tokio::test]
-    // async fn test_manager() {
-    //     let _ = testing().await;
-    // }
+        let remote = MCPServerConfig {
+            transport_type: Some(MCPTransportType::Sse),
+            url: Some(String::from("http://localhost:1/sse")),
+            ..Default::default()
+        };
+        assert!(connect_mcp_server(&remote, http_transport).await.is_err());
+    }
 }
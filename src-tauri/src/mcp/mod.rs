@@ -1,5 +1,8 @@
 pub mod client;
 pub mod commands;
+pub mod config;
 pub mod connection;
+pub mod http;
 pub mod manager;
+pub mod sse;
 pub mod structs;
@@ -0,0 +1,285 @@
+use crate::api::transport::{HttpRequest, HttpTransport};
+use crate::error::NexaError;
+use crate::mcp::connection::{MCPTransportReader, MCPTransportWriter};
+use crate::mcp::http::{parse_packets, sse_events};
+use crate::mcp::structs::MCPDataPacket;
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tauri_plugin_http::reqwest::Url;
+use tokio::select;
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tokio_util::sync::CancellationToken;
+
+// The HTTP+SSE transport of the 2024-11-05 spec. The client keeps one SSE
+// stream open, the server announces on it where to POST messages to and
+// sends every reply back over the stream.
+
+// The endpoint is sent relative to the stream more often than not. One on
+// another origin is refused, it would get the headers (and tokens) too.
+fn resolve_endpoint(url: &str, endpoint: &str) -> Result<String, NexaError> {
+    let invalid =
+        |e: String| NexaError::MCPConnection(format!("Invalid endpoint {}: {}", endpoint, e));
+
+    let base = Url::parse(url).map_err(|e| invalid(e.to_string()))?;
+    let resolved = base.join(endpoint).map_err(|e| invalid(e.to_string()))?;
+    if resolved.origin() != base.origin() {
+        return Err(invalid(String::from(
+            "it isn't on the same origin as the stream",
+        )));
+    }
+
+    Ok(resolved.to_string())
+}
+
+pub(crate) struct LegacySseWriter {
+    transport: Arc<dyn HttpTransport>,
+    endpoint: String,
+    headers: HashMap<String, String>,
+    cancel_token: CancellationToken,
+}
+
+#[async_trait]
+impl MCPTransportWriter for LegacySseWriter {
+    // The reply comes over the stream, the POST itself is only accepted
+    async fn send(&self, data: Value) -> Result<(), NexaError> {
+        let mut request = HttpRequest::post(&self.endpoint).json(&data)?;
+        for (name, value) in self.headers.iter() {
+            request = request.header(name, value);
+        }
+
+        self.transport
+            .send(request)
+            .await?
+            .error_for_status(NexaError::MCPConnection)
+            .await?;
+
+        Ok(())
+    }
+}
+
+// Closing the stream ends the session on the server
+impl Drop for LegacySseWriter {
+    fn drop(&mut self) {
+        self.cancel_token.cancel();
+    }
+}
+
+pub(crate) struct LegacySseReader {
+    receiver: UnboundedReceiver<Result<MCPDataPacket, NexaError>>,
+}
+
+#[async_trait]
+impl MCPTransportReader for LegacySseReader {
+    async fn receive(&mut self) -> Result<MCPDataPacket, NexaError> {
+        self.receiver
+            .recv()
+            .await
+            .unwrap_or(Err(NexaError::MCPConnection(String::from(
+                "The MCP server closed the SSE stream",
+            ))))
+    }
+}
+
+pub(crate) async fn mcp_sse_connect(
+    transport: Arc<dyn HttpTransport>,
+    url: &str,
+    headers: HashMap<String, String>,
+) -> Result<(LegacySseWriter, LegacySseReader), NexaError> {
+    let mut request = HttpRequest::get(url).header("Accept", "text/event-stream");
+    for (name, value) in headers.iter() {
+        request = request.header(name, value);
+    }
+    let response = transport
+        .send(request)
+        .await?
+        .error_for_status(NexaError::MCPConnection)
+        .await?;
+
+    let mut events = Box::pin(sse_events(response.body));
+    let endpoint = loop {
+        match events.next().await {
+            Some(Ok(event)) if event.event.as_deref() == Some("endpoint") => {
+                break resolve_endpoint(url, event.data.trim())?;
+            }
+            Some(Ok(_)) => continue,
+            Some(Err(e)) => return Err(e),
+            None => {
+                return Err(NexaError::MCPConnection(String::from(
+                    "The SSE stream ended before announcing an endpoint",
+                )))
+            }
+        }
+    };
+
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let cancel_token = CancellationToken::new();
+    let stream_token = cancel_token.clone();
+    // The sender goes away with this task, which is how the reader learns
+    // the stream is gone. The error it broke off with is passed on first.
+    tokio::spawn(async move {
+        loop {
+            let event = select! {
+                _ = stream_token.cancelled() => return,
+                event = events.next() => event,
+            };
+
+            let event = match event {
+                Some(Ok(event)) => event,
+                Some(Err(e)) => {
+                    let _ = sender.send(Err(e));
+                    return;
+                }
+                None => return,
+            };
+            if !matches!(event.event.as_deref(), None | Some("message")) || event.data.is_empty() {
+                continue;
+            }
+
+            match parse_packets(event.data.as_bytes()) {
+                Ok(packets) => {
+                    for packet in packets {
+                        let _ = sender.send(Ok(packet));
+                    }
+                }
+                Err(e) => {
+                    let _ = sender.send(Err(e));
+                }
+            }
+        }
+    });
+
+    Ok((
+        LegacySseWriter {
+            transport,
+            endpoint,
+            headers,
+            cancel_token,
+        },
+        LegacySseReader { receiver },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::transport::{HttpMethod, HttpResponse};
+    use crate::mcp::config::{MCPServerConfig, MCPTransportType};
+    use crate::mcp::manager::connect_mcp_server;
+    use bytes::Bytes;
+    use futures::stream;
+    use serde_json::json;
+    use std::sync::Mutex;
+    use tokio::sync::mpsc::UnboundedSender;
+
+    // Stands in for a server that predates Streamable HTTP: the initialize
+    // POST to the stream's url is refused, replies go over the one stream
+    #[derive(Default)]
+    struct StandInServer {
+        requests: Mutex<Vec<HttpRequest>>,
+        stream: Mutex<Option<UnboundedSender<String>>>,
+    }
+
+    fn get_response(status: u16, body: Vec<String>) -> HttpResponse {
+        let chunks: Vec<Result<Bytes, NexaError>> = body
+            .into_iter()
+            .map(|chunk| Ok(Bytes::from(chunk)))
+            .collect();
+
+        HttpResponse {
+            status,
+            headers: vec![],
+            body: stream::iter(chunks).boxed(),
+        }
+    }
+
+    #[async_trait]
+    impl HttpTransport for StandInServer {
+        async fn send(&self, request: HttpRequest) -> Result<HttpResponse, NexaError> {
+            self.requests.lock().unwrap().push(request.clone());
+
+            if request.method == HttpMethod::Get {
+                let (sender, receiver) = mpsc::unbounded_channel::<String>();
+                sender
+                    .send(String::from(
+                        ": connected\n\nevent: endpoint\ndata: /messages?session_id=abc\n\n",
+                    ))
+                    .unwrap();
+                *self.stream.lock().unwrap() = Some(sender);
+
+                let body = stream::unfold(receiver, |mut receiver| async move {
+                    let chunk = receiver.recv().await?;
+                    Some((Ok(Bytes::from(chunk)), receiver))
+                });
+                return Ok(HttpResponse {
+                    body: body.boxed(),
+                    ..get_response(200, vec![])
+                });
+            }
+
+            if !request.url.ends_with("/messages?session_id=abc") {
+                return Ok(get_response(405, vec![String::from("Method Not Allowed")]));
+            }
+
+            let body: Value = serde_json::from_slice(request.body.as_ref().unwrap())?;
+            let result = match body["method"].as_str() {
+                Some("initialize") => json!({
+                    "protocolVersion": "2024-11-05",
+                    "capabilities": {"tools": {}},
+                    "serverInfo": {"name": "legacy", "version": "1.0.0"}
+                }),
+                Some("tools/list") => json!({
+                    "tools": [{"name": "echo", "inputSchema": {"type": "object"}}]
+                }),
+                _ => return Ok(get_response(202, vec![])),
+            };
+            let reply = json!({"jsonrpc": "2.0", "id": body["id"], "result": result});
+            if let Some(stream) = self.stream.lock().unwrap().as_ref() {
+                let _ = stream.send(format!("event: message\ndata: {}\n\n", reply));
+            }
+
+            Ok(get_response(202, vec![String::from("Accepted")]))
+        }
+    }
+
+    #[test]
+    fn resolve_endpoint_test() {
+        let url = "https://mcp.example.com/v1/sse";
+        assert_eq!(
+            resolve_endpoint(url, "/messages?session_id=abc").unwrap(),
+            "https://mcp.example.com/messages?session_id=abc"
+        );
+        assert_eq!(
+            resolve_endpoint(url, "messages").unwrap(),
+            "https://mcp.example.com/v1/messages"
+        );
+        assert!(resolve_endpoint(url, "https://attacker.example.com/messages").is_err());
+    }
+
+    #[tokio::test]
+    async fn legacy_sse_fallback_test() {
+        let server = Arc::new(StandInServer::default());
+        let config = MCPServerConfig {
+            url: Some(String::from("http://localhost:8000/sse")),
+            headers: HashMap::from([(
+                String::from("Authorization"),
+                String::from("Bearer secret"),
+            )]),
+            ..Default::default()
+        };
+
+        let client = connect_mcp_server(&config, server.clone()).await.unwrap();
+        assert_eq!(client.get_transport_type(), MCPTransportType::Sse);
+        assert_eq!(client.get_server_config().await.server_info.name, "legacy");
+        assert_eq!(client.get_tool_list().await.len(), 1);
+
+        let requests = server.requests.lock().unwrap();
+        assert_eq!(requests[0].method, HttpMethod::Post);
+        assert_eq!(requests[1].method, HttpMethod::Get);
+        assert!(requests.iter().all(|request| request
+            .headers
+            .contains(&(String::from("Authorization"), String::from("Bearer secret")))));
+    }
+}
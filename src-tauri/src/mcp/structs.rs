@@ -131,16 +131,19 @@ pub(crate) struct Tool {
     pub(crate) title: Option<String>,
 }
 
+// One page of a list result. `T` holds the items of the method, the cursor
+// is there as long as the server has more.
 #[derive(Deserialize, Clone, PartialEq, Debug)]
 #[serde(rename_all = "camelCase")]
-pub(crate) struct ListToolsResult {
-    #[serde(rename = "_meta")]
-    pub(crate) _meta: Option<Value>,
+pub(crate) struct Paginated<T> {
     pub(crate) next_cursor: Option<String>,
-    pub(crate) tools: Vec<Tool>,
-
     #[serde(flatten)]
-    pub(crate) extra_fields: Value,
+    pub(crate) page: T,
+}
+
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+pub(crate) struct ListToolsResult {
+    pub(crate) tools: Vec<Tool>,
 }
 
 #[cfg(test)]
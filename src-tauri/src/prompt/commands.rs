@@ -0,0 +1,48 @@
+use crate::error::NexaError;
+use crate::prompt::library::{
+    render_prompt_template, PromptTemplate, PromptTemplateList, RenderedPrompt,
+};
+use crate::AppData;
+use std::collections::HashMap;
+use tauri::State;
+
+#[tauri::command]
+pub async fn list_prompt_templates(
+    state: State<'_, AppData>,
+) -> Result<PromptTemplateList, NexaError> {
+    state.prompt_library.list()
+}
+
+// Where the templates are read from, so the UI can point people at the folder
+#[tauri::command]
+pub async fn get_prompt_template_dir(state: State<'_, AppData>) -> Result<String, NexaError> {
+    Ok(state.prompt_library.get_dir().to_string_lossy().to_string())
+}
+
+#[tauri::command]
+pub async fn save_prompt_template(
+    state: State<'_, AppData>,
+    id: String,
+    template: PromptTemplate,
+) -> Result<(), NexaError> {
+    state.prompt_library.save(&id, &template)
+}
+
+#[tauri::command]
+pub async fn delete_prompt_template(
+    state: State<'_, AppData>,
+    id: String,
+) -> Result<(), NexaError> {
+    state.prompt_library.delete(&id)
+}
+
+#[tauri::command]
+pub async fn render_prompt(
+    state: State<'_, AppData>,
+    id: String,
+    variables: HashMap<String, String>,
+) -> Result<RenderedPrompt, NexaError> {
+    let template = state.prompt_library.get(&id)?;
+
+    render_prompt_template(&template, &variables)
+}
@@ -0,0 +1,393 @@
+use crate::error::NexaError;
+use crate::llm::base::{ChatMessage, ChatMessageContent, Provider, Role};
+use minijinja::{Environment, UndefinedBehavior};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::PathBuf;
+
+pub(crate) static PROMPTS_DIRNAME: &str = "prompts";
+static PROMPT_TEMPLATE_EXTENSION: &str = "toml";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptVariable {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    // Without a default, a required variable has to be filled in to render.
+    // Optional ones render as an empty string.
+    #[serde(default)]
+    pub required: bool,
+}
+
+// One template per <id>.toml file. `template` and `systemPrompt` are Jinja
+// templates over the declared variables.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptTemplate {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<Provider>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub template: String,
+    #[serde(default)]
+    pub variables: Vec<PromptVariable>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptTemplateEntry {
+    pub id: String,
+    #[serde(flatten)]
+    pub template: PromptTemplate,
+}
+
+// Broken files are listed next to the templates, with why they didn't load
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptTemplateList {
+    pub templates: Vec<PromptTemplateEntry>,
+    pub errors: Vec<PromptTemplateError>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptTemplateError {
+    pub id: String,
+    pub error: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RenderedPrompt {
+    pub message: ChatMessage,
+    pub system_prompt: Option<String>,
+    pub provider: Option<Provider>,
+    pub model: Option<String>,
+}
+
+// Templates live as plain files in a folder, so a team can keep them in git
+// and pick up changes without restarting. Nothing is cached, every call reads
+// the folder again.
+pub(crate) struct PromptLibrary {
+    dir: PathBuf,
+}
+
+impl PromptLibrary {
+    pub fn new(dir: PathBuf) -> Self {
+        PromptLibrary { dir }
+    }
+
+    pub fn get_dir(&self) -> &PathBuf {
+        &self.dir
+    }
+
+    // Files that don't parse are reported apart, so one broken template
+    // doesn't hide the rest of the library
+    pub fn list(&self) -> Result<PromptTemplateList, NexaError> {
+        let read_dir = match fs::read_dir(&self.dir) {
+            Ok(read_dir) => read_dir,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(PromptTemplateList::default())
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut list = PromptTemplateList::default();
+        for dir_entry in read_dir {
+            let path = dir_entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(PROMPT_TEMPLATE_EXTENSION) {
+                continue;
+            }
+            let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+
+            match self.get(id) {
+                Ok(template) => list.templates.push(PromptTemplateEntry {
+                    id: id.to_string(),
+                    template,
+                }),
+                Err(e) => list.errors.push(PromptTemplateError {
+                    id: id.to_string(),
+                    error: e.to_string(),
+                }),
+            }
+        }
+        list.templates
+            .sort_by(|a, b| a.template.name.cmp(&b.template.name));
+        list.errors.sort_by(|a, b| a.id.cmp(&b.id));
+
+        Ok(list)
+    }
+
+    pub fn get(&self, id: &str) -> Result<PromptTemplate, NexaError> {
+        let contents = fs::read_to_string(self.get_path(id)?)?;
+        let template: PromptTemplate = toml::from_str(&contents)
+            .map_err(|e| NexaError::Prompt(format!("Can't parse template {}: {}", id, e)))?;
+        validate_template(&template)?;
+
+        Ok(template)
+    }
+
+    pub fn save(&self, id: &str, template: &PromptTemplate) -> Result<(), NexaError> {
+        validate_template(template)?;
+
+        let contents = toml::to_string_pretty(template)
+            .map_err(|e| NexaError::Prompt(format!("Can't serialize template {}: {}", id, e)))?;
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.get_path(id)?, contents)?;
+
+        Ok(())
+    }
+
+    pub fn delete(&self, id: &str) -> Result<(), NexaError> {
+        fs::remove_file(self.get_path(id)?)?;
+
+        Ok(())
+    }
+
+    // Ids end up in file names, so they are kept to a safe set of characters
+    fn get_path(&self, id: &str) -> Result<PathBuf, NexaError> {
+        let valid = !id.is_empty()
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(NexaError::Prompt(format!(
+                "Invalid template id {:?}, use letters, digits, - and _",
+                id
+            )));
+        }
+
+        Ok(self
+            .dir
+            .join(format!("{}.{}", id, PROMPT_TEMPLATE_EXTENSION)))
+    }
+}
+
+pub fn render_prompt_template(
+    template: &PromptTemplate,
+    values: &HashMap<String, String>,
+) -> Result<RenderedPrompt, NexaError> {
+    for name in values.keys() {
+        if !template
+            .variables
+            .iter()
+            .any(|variable| variable.name == *name)
+        {
+            return Err(NexaError::Prompt(format!("Unknown variable {}", name)));
+        }
+    }
+
+    let mut context = HashMap::new();
+    for variable in template.variables.iter() {
+        let value = match (values.get(&variable.name), &variable.default) {
+            (Some(value), _) => value.clone(),
+            (None, Some(default)) => default.clone(),
+            (None, None) if variable.required => {
+                return Err(NexaError::Prompt(format!(
+                    "Missing value for required variable {}",
+                    variable.name
+                )))
+            }
+            (None, None) => String::new(),
+        };
+        context.insert(variable.name.clone(), value);
+    }
+
+    let env = get_environment();
+    let render = |source: &str| {
+        env.render_str(source, &context)
+            .map_err(|e| NexaError::Prompt(format!("Can't render template: {}", e)))
+    };
+
+    Ok(RenderedPrompt {
+        message: ChatMessage {
+            role: Role::User,
+            content: ChatMessageContent::Text {
+                text: render(&template.template)?,
+                _meta: None,
+            },
+            images: None,
+        },
+        system_prompt: template.system_prompt.as_deref().map(render).transpose()?,
+        provider: template.provider.clone(),
+        model: template.model.clone(),
+    })
+}
+
+// Checks the template syntax and that it only uses declared variables, so
+// typos show up when the template is saved instead of when it's used
+fn validate_template(template: &PromptTemplate) -> Result<(), NexaError> {
+    let mut names = BTreeSet::new();
+    for variable in template.variables.iter() {
+        let valid = variable
+            .name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && variable
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            return Err(NexaError::Prompt(format!(
+                "Invalid variable name {:?}",
+                variable.name
+            )));
+        }
+        if !names.insert(variable.name.as_str()) {
+            return Err(NexaError::Prompt(format!(
+                "Variable {} is declared twice",
+                variable.name
+            )));
+        }
+    }
+
+    let mut env = get_environment();
+    let sources = [Some(&template.template), template.system_prompt.as_ref()];
+    for (index, source) in sources.into_iter().flatten().enumerate() {
+        let name = index.to_string();
+        env.add_template_owned(name.clone(), source.clone())
+            .map_err(|e| NexaError::Prompt(format!("Invalid template syntax: {}", e)))?;
+
+        let used = env
+            .get_template(&name)
+            .map_err(|e| NexaError::Prompt(e.to_string()))?
+            .undeclared_variables(false);
+        let mut undeclared: Vec<String> = used
+            .into_iter()
+            .filter(|name| !names.contains(name.as_str()))
+            .collect();
+        if !undeclared.is_empty() {
+            undeclared.sort();
+            return Err(NexaError::Prompt(format!(
+                "Template uses undeclared variables: {}",
+                undeclared.join(", ")
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+fn get_environment() -> Environment<'static> {
+    let mut env = Environment::new();
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    env.set_keep_trailing_newline(true);
+
+    env
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_template() -> PromptTemplate {
+        toml::from_str(
+            r#"
+name = "Summarize"
+systemPrompt = "You write {{ tone }} summaries."
+provider = "gemini"
+model = "gemini-2.0-flash"
+template = """
+Summarize this {{ kind }} in {{ sentences }} sentences:
+{{ text }}"""
+
+[[variables]]
+name = "text"
+required = true
+
+[[variables]]
+name = "kind"
+default = "article"
+
+[[variables]]
+name = "sentences"
+default = "3"
+
+[[variables]]
+name = "tone"
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn render_prompt_template_test() {
+        let template = get_template();
+
+        let values = HashMap::from([
+            (String::from("text"), String::from("Rust 2024 is out.")),
+            (String::from("tone"), String::from("neutral")),
+        ]);
+        let rendered = render_prompt_template(&template, &values).unwrap();
+        assert!(matches!(
+            &rendered.message.content,
+            ChatMessageContent::Text { text, .. }
+                if text == "Summarize this article in 3 sentences:\nRust 2024 is out."
+        ));
+        assert_eq!(
+            rendered.system_prompt.as_deref(),
+            Some("You write neutral summaries.")
+        );
+        assert_eq!(rendered.model.as_deref(), Some("gemini-2.0-flash"));
+
+        // Required variable without a value
+        assert!(render_prompt_template(&template, &HashMap::new()).is_err());
+        // Values for variables the template doesn't declare
+        let values = HashMap::from([
+            (String::from("text"), String::from("Hi")),
+            (String::from("txet"), String::from("Hi")),
+        ]);
+        assert!(render_prompt_template(&template, &values).is_err());
+    }
+
+    #[test]
+    fn validate_template_test() {
+        assert!(validate_template(&get_template()).is_ok());
+
+        let mut template = get_template();
+        template.template = String::from("{{ text }} {{ missing }}");
+        assert!(validate_template(&template).is_err());
+
+        let mut template = get_template();
+        template.template = String::from("{% if text %}");
+        assert!(validate_template(&template).is_err());
+
+        let mut template = get_template();
+        template.variables[1].name = String::from("text");
+        assert!(validate_template(&template).is_err());
+    }
+
+    #[test]
+    fn prompt_library_test() {
+        let dir = std::env::temp_dir().join(format!("nexa-prompts-{}", std::process::id()));
+        let library = PromptLibrary::new(dir.clone());
+        assert!(library.list().unwrap().templates.is_empty());
+
+        library.save("summarize", &get_template()).unwrap();
+        fs::write(dir.join("broken.toml"), "name = ").unwrap();
+        assert!(library.save("../escape", &get_template()).is_err());
+
+        let list = library.list().unwrap();
+        assert_eq!(list.templates.len(), 1);
+        assert_eq!(list.templates[0].id, "summarize");
+        assert_eq!(list.templates[0].template, get_template());
+        assert_eq!(list.errors.len(), 1);
+        assert_eq!(list.errors[0].id, "broken");
+
+        library.delete("summarize").unwrap();
+        assert!(library.get("summarize").is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
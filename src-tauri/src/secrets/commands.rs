@@ -0,0 +1,163 @@
+use crate::api::gemini::check_gemini_api_key;
+use crate::error::NexaError;
+use crate::llm::base::Provider;
+use crate::llm::constants::GEMINI_KETRING_KEY;
+use crate::secrets::profiles::get_profile_secret_key;
+use crate::secrets::store::SecretBackend;
+use crate::AppData;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SecretStatus {
+    pub key: String,
+    // Where the key was found, none if it isn't set anywhere
+    pub backend: Option<SecretBackend>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SecretTestResult {
+    pub key: String,
+    pub backend: Option<SecretBackend>,
+    pub valid: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialProfileStatus {
+    pub provider: Provider,
+    pub name: String,
+    // Secret store key holding the profile's credential, for set/test_secret
+    pub key: String,
+    pub backend: Option<SecretBackend>,
+}
+
+#[tauri::command]
+pub async fn set_secret(
+    state: State<'_, AppData>,
+    key: String,
+    value: String,
+) -> Result<SecretStatus, NexaError> {
+    let backend = state.secret_store.set(&key, &value)?;
+
+    Ok(SecretStatus {
+        key,
+        backend: Some(backend),
+    })
+}
+
+#[tauri::command]
+pub async fn delete_secret(state: State<'_, AppData>, key: String) -> Result<(), NexaError> {
+    state.secret_store.delete(&key)
+}
+
+#[tauri::command]
+pub async fn get_secret_status(
+    state: State<'_, AppData>,
+    key: String,
+) -> Result<SecretStatus, NexaError> {
+    let backend = state.secret_store.get(&key)?.map(|(_, backend)| backend);
+
+    Ok(SecretStatus { key, backend })
+}
+
+// Checks that the key is stored and, for provider keys, that the provider
+// accepts it
+#[tauri::command]
+pub async fn test_secret(
+    state: State<'_, AppData>,
+    key: String,
+) -> Result<SecretTestResult, NexaError> {
+    let Some((value, backend)) = state.secret_store.get(&key)? else {
+        return Ok(SecretTestResult {
+            key,
+            backend: None,
+            valid: false,
+            error: Some(String::from("Not set")),
+        });
+    };
+
+    let checked = match key.as_str() {
+        k if k == GEMINI_KETRING_KEY || k.starts_with(&format!("{}-", GEMINI_KETRING_KEY)) => {
+            check_gemini_api_key(state.http_transport.as_ref(), &value).await
+        }
+        _ => Ok(()),
+    };
+
+    Ok(SecretTestResult {
+        key,
+        backend: Some(backend),
+        valid: checked.is_ok(),
+        error: checked.err().map(|e| e.to_string()),
+    })
+}
+
+#[tauri::command]
+pub async fn list_credential_profiles(
+    state: State<'_, AppData>,
+    provider: Provider,
+) -> Result<Vec<CredentialProfileStatus>, NexaError> {
+    let names = state.credential_profiles.lock().await.list(&provider);
+
+    let mut profiles = vec![];
+    for name in names {
+        let key = get_profile_secret_key(&provider, &name)?;
+        let backend = state
+            .secret_store
+            .get(&key)
+            .ok()
+            .flatten()
+            .map(|(_, backend)| backend);
+
+        profiles.push(CredentialProfileStatus {
+            provider: provider.clone(),
+            name,
+            key,
+            backend,
+        });
+    }
+
+    Ok(profiles)
+}
+
+#[tauri::command]
+pub async fn add_credential_profile(
+    state: State<'_, AppData>,
+    provider: Provider,
+    name: String,
+    value: String,
+) -> Result<CredentialProfileStatus, NexaError> {
+    let key = get_profile_secret_key(&provider, &name)?;
+    let backend = state.secret_store.set(&key, &value)?;
+    state
+        .credential_profiles
+        .lock()
+        .await
+        .add(&provider, &name)?;
+
+    Ok(CredentialProfileStatus {
+        provider,
+        name,
+        key,
+        backend: Some(backend),
+    })
+}
+
+#[tauri::command]
+pub async fn remove_credential_profile(
+    state: State<'_, AppData>,
+    provider: Provider,
+    name: String,
+) -> Result<(), NexaError> {
+    let key = get_profile_secret_key(&provider, &name)?;
+    state
+        .credential_profiles
+        .lock()
+        .await
+        .remove(&provider, &name)?;
+
+    state.secret_store.delete(&key)
+}
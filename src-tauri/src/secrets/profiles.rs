@@ -0,0 +1,182 @@
+use crate::error::NexaError;
+use crate::llm::base::Provider;
+use crate::llm::constants::GEMINI_KETRING_KEY;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+pub(crate) static CREDENTIAL_PROFILES_FILENAME: &str = "credential-profiles.json";
+// Always there for providers that take a key, it's the key saved before
+// profiles existed
+pub(crate) static DEFAULT_PROFILE: &str = "default";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialProfile {
+    pub provider: Provider,
+    pub name: String,
+}
+
+// Only the profile names are kept here, the keys themselves live in the
+// secret store. The order is the order keys are rotated in.
+pub(crate) struct CredentialProfiles {
+    profiles: Vec<CredentialProfile>,
+    path: Option<PathBuf>,
+}
+
+impl CredentialProfiles {
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let profiles = path
+            .as_ref()
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+
+        CredentialProfiles { profiles, path }
+    }
+
+    pub fn list(&self, provider: &Provider) -> Vec<String> {
+        let mut names = vec![DEFAULT_PROFILE.to_string()];
+        names.extend(
+            self.profiles
+                .iter()
+                .filter(|profile| profile.provider == *provider)
+                .map(|profile| profile.name.clone()),
+        );
+
+        names
+    }
+
+    pub fn add(&mut self, provider: &Provider, name: &str) -> Result<(), NexaError> {
+        get_profile_secret_key(provider, name)?;
+        if self.list(provider).iter().any(|existing| existing == name) {
+            return Ok(());
+        }
+
+        self.profiles.push(CredentialProfile {
+            provider: provider.clone(),
+            name: name.to_string(),
+        });
+        self.save()
+    }
+
+    pub fn remove(&mut self, provider: &Provider, name: &str) -> Result<(), NexaError> {
+        if name == DEFAULT_PROFILE {
+            return Err(NexaError::Secret(String::from(
+                "The default profile can't be removed",
+            )));
+        }
+
+        self.profiles
+            .retain(|profile| !(profile.provider == *provider && profile.name == name));
+        self.save()
+    }
+
+    // The profile to use first, followed by the others in order when rotation
+    // is on
+    pub fn get_rotation(
+        &self,
+        provider: &Provider,
+        first: Option<&str>,
+        rotate: bool,
+    ) -> Vec<String> {
+        let first = first.unwrap_or(DEFAULT_PROFILE).to_string();
+        if !rotate {
+            return vec![first];
+        }
+
+        let mut names = vec![first.clone()];
+        names.extend(
+            self.list(provider)
+                .into_iter()
+                .filter(|name| *name != first),
+        );
+
+        names
+    }
+
+    fn save(&self) -> Result<(), NexaError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_vec(&self.profiles)?)?;
+
+        Ok(())
+    }
+}
+
+// The default profile keeps the original entry name, so existing keys keep
+// working. Other profiles get a suffix, "team" is stored as
+// "gemini-api-key-team" and read from GEMINI_API_KEY_TEAM.
+pub(crate) fn get_profile_secret_key(provider: &Provider, name: &str) -> Result<String, NexaError> {
+    let base = match provider {
+        Provider::Gemini => GEMINI_KETRING_KEY,
+        _ => {
+            return Err(NexaError::Secret(format!(
+                "{:?} doesn't take credentials",
+                provider
+            )))
+        }
+    };
+
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if !valid {
+        return Err(NexaError::Secret(format!(
+            "Invalid profile name {:?}, use lowercase letters, digits and -",
+            name
+        )));
+    }
+
+    match name == DEFAULT_PROFILE {
+        true => Ok(base.to_string()),
+        false => Ok(format!("{}-{}", base, name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn credential_profiles_test() {
+        let mut profiles = CredentialProfiles::load(None);
+        profiles.add(&Provider::Gemini, "personal").unwrap();
+        profiles.add(&Provider::Gemini, "team").unwrap();
+        profiles.add(&Provider::Gemini, "team").unwrap();
+        assert!(profiles.add(&Provider::Gemini, "Team Key").is_err());
+        assert!(profiles.add(&Provider::Ollama, "local").is_err());
+
+        assert_eq!(
+            profiles.list(&Provider::Gemini),
+            vec!["default", "personal", "team"]
+        );
+        assert_eq!(
+            profiles.get_rotation(&Provider::Gemini, Some("team"), true),
+            vec!["team", "default", "personal"]
+        );
+        assert_eq!(
+            profiles.get_rotation(&Provider::Gemini, Some("team"), false),
+            vec!["team"]
+        );
+
+        assert_eq!(
+            get_profile_secret_key(&Provider::Gemini, "default").unwrap(),
+            GEMINI_KETRING_KEY
+        );
+        assert_eq!(
+            get_profile_secret_key(&Provider::Gemini, "billing-project").unwrap(),
+            "gemini-api-key-billing-project"
+        );
+
+        assert!(profiles.remove(&Provider::Gemini, DEFAULT_PROFILE).is_err());
+        profiles.remove(&Provider::Gemini, "personal").unwrap();
+        assert_eq!(profiles.list(&Provider::Gemini), vec!["default", "team"]);
+    }
+}
@@ -0,0 +1,490 @@
+use crate::error::NexaError;
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+use keyring::Entry;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+pub(crate) static SECRETS_FILENAME: &str = "secrets.json";
+static SECRETS_FILE_VERSION: u32 = 1;
+static SALT_LENGTH: usize = 16;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretBackend {
+    Env,
+    Keyring,
+    File,
+}
+
+pub(crate) trait SecretStore: Send + Sync {
+    fn backend(&self) -> SecretBackend;
+
+    fn is_read_only(&self) -> bool {
+        false
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>, NexaError>;
+
+    fn set(&self, key: &str, value: &str) -> Result<(), NexaError>;
+
+    // Deleting a key that isn't there is not an error
+    fn delete(&self, key: &str) -> Result<(), NexaError>;
+}
+
+// Read only, "gemini-api-key" comes from GEMINI_API_KEY. Meant for servers and
+// CI, where nobody is around to type a key in.
+pub(crate) struct EnvSecretStore;
+
+impl SecretStore for EnvSecretStore {
+    fn backend(&self) -> SecretBackend {
+        SecretBackend::Env
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>, NexaError> {
+        match env::var(get_env_name(key)) {
+            Ok(value) if !value.is_empty() => Ok(Some(value)),
+            _ => Ok(None),
+        }
+    }
+
+    fn set(&self, key: &str, _value: &str) -> Result<(), NexaError> {
+        Err(get_read_only_error(key))
+    }
+
+    fn delete(&self, key: &str) -> Result<(), NexaError> {
+        Err(get_read_only_error(key))
+    }
+}
+
+// Same entries as the secure-storage plugin, so keys saved by older versions
+// of the frontend are picked up as they are
+pub(crate) struct KeyringSecretStore {
+    service: String,
+}
+
+impl KeyringSecretStore {
+    pub fn new(service: String) -> Self {
+        KeyringSecretStore { service }
+    }
+
+    fn get_entry(&self, key: &str) -> Result<Entry, NexaError> {
+        Entry::new(&self.service, key).map_err(get_keyring_error)
+    }
+}
+
+impl SecretStore for KeyringSecretStore {
+    fn backend(&self) -> SecretBackend {
+        SecretBackend::Keyring
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>, NexaError> {
+        match self.get_entry(key)?.get_password() {
+            Ok(value) => Ok(Some(value)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(get_keyring_error(e)),
+        }
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<(), NexaError> {
+        self.get_entry(key)?
+            .set_password(value)
+            .map_err(get_keyring_error)
+    }
+
+    fn delete(&self, key: &str) -> Result<(), NexaError> {
+        match self.get_entry(key)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(get_keyring_error(e)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct SecretsFile {
+    version: u32,
+    salt: String,
+    secrets: BTreeMap<String, EncryptedSecret>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct EncryptedSecret {
+    nonce: String,
+    ciphertext: String,
+}
+
+// For machines without a Secret Service (headless Linux, containers). Every
+// value is sealed with ChaCha20-Poly1305 under a key derived from the
+// passphrase with Argon2id, and the key name is bound in as associated data
+// so values can't be swapped around in the file.
+pub(crate) struct EncryptedFileSecretStore {
+    path: PathBuf,
+    passphrase: String,
+    // Argon2 is slow on purpose, the key is derived once per salt
+    cipher: Mutex<Option<(Vec<u8>, ChaCha20Poly1305)>>,
+}
+
+impl EncryptedFileSecretStore {
+    pub fn new(path: PathBuf, passphrase: String) -> Self {
+        EncryptedFileSecretStore {
+            path,
+            passphrase,
+            cipher: Mutex::new(None),
+        }
+    }
+
+    fn read_file(&self) -> Result<Option<SecretsFile>, NexaError> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let file: SecretsFile = serde_json::from_str(&contents)?;
+        if file.version > SECRETS_FILE_VERSION {
+            return Err(NexaError::Secret(format!(
+                "Unsupported secrets file version {}",
+                file.version
+            )));
+        }
+
+        Ok(Some(file))
+    }
+
+    // The file holds every secret, so it's written next to the old one and
+    // renamed over it. A crash half way leaves the old file as it was.
+    fn write_file(&self, file: &SecretsFile) -> Result<(), NexaError> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let temp_path = self.path.with_extension("json.tmp");
+
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut temp_file = options.open(&temp_path)?;
+        temp_file.write_all(serde_json::to_string_pretty(file)?.as_bytes())?;
+        temp_file.sync_all()?;
+        fs::rename(&temp_path, &self.path)?;
+
+        Ok(())
+    }
+
+    fn get_cipher(&self, salt: &[u8]) -> Result<ChaCha20Poly1305, NexaError> {
+        let mut cached = self.cipher.lock().unwrap();
+        if let Some((cached_salt, cipher)) = cached.as_ref() {
+            if cached_salt == salt {
+                return Ok(cipher.clone());
+            }
+        }
+
+        let mut key = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(self.passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| NexaError::Secret(format!("Can't derive the file key: {}", e)))?;
+        let cipher = ChaCha20Poly1305::new(&key.into());
+        *cached = Some((salt.to_vec(), cipher.clone()));
+
+        Ok(cipher)
+    }
+
+    fn decrypt(
+        &self,
+        cipher: &ChaCha20Poly1305,
+        key: &str,
+        secret: &EncryptedSecret,
+    ) -> Result<String, NexaError> {
+        let nonce = decode_base64(&secret.nonce)?;
+        let ciphertext = decode_base64(&secret.ciphertext)?;
+        if nonce.len() != 12 {
+            return Err(NexaError::Secret(format!("Invalid nonce for {}", key)));
+        }
+
+        let plaintext = cipher
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &ciphertext,
+                    aad: key.as_bytes(),
+                },
+            )
+            .map_err(|_| {
+                NexaError::Secret(format!("Can't decrypt {}, is the passphrase right?", key))
+            })?;
+
+        String::from_utf8(plaintext).map_err(|e| NexaError::Secret(e.to_string()))
+    }
+}
+
+impl SecretStore for EncryptedFileSecretStore {
+    fn backend(&self) -> SecretBackend {
+        SecretBackend::File
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>, NexaError> {
+        let Some(file) = self.read_file()? else {
+            return Ok(None);
+        };
+        let Some(secret) = file.secrets.get(key) else {
+            return Ok(None);
+        };
+        let cipher = self.get_cipher(&decode_base64(&file.salt)?)?;
+
+        Ok(Some(self.decrypt(&cipher, key, secret)?))
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<(), NexaError> {
+        let mut file = match self.read_file()? {
+            Some(file) => file,
+            None => {
+                let mut salt = vec![0u8; SALT_LENGTH];
+                OsRng.fill_bytes(&mut salt);
+                SecretsFile {
+                    version: SECRETS_FILE_VERSION,
+                    salt: STANDARD.encode(salt),
+                    secrets: BTreeMap::new(),
+                }
+            }
+        };
+        let cipher = self.get_cipher(&decode_base64(&file.salt)?)?;
+
+        // A different passphrase would leave the file with values nobody can
+        // decrypt together, so it has to open what's already there
+        if let Some((existing_key, existing)) = file.secrets.iter().next() {
+            self.decrypt(&cipher, existing_key, existing)?;
+        }
+
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: value.as_bytes(),
+                    aad: key.as_bytes(),
+                },
+            )
+            .map_err(|_| NexaError::Secret(format!("Can't encrypt {}", key)))?;
+        file.secrets.insert(
+            key.to_string(),
+            EncryptedSecret {
+                nonce: STANDARD.encode(nonce),
+                ciphertext: STANDARD.encode(ciphertext),
+            },
+        );
+
+        self.write_file(&file)
+    }
+
+    fn delete(&self, key: &str) -> Result<(), NexaError> {
+        let Some(mut file) = self.read_file()? else {
+            return Ok(());
+        };
+
+        match file.secrets.remove(key) {
+            Some(_) => self.write_file(&file),
+            None => Ok(()),
+        }
+    }
+}
+
+// Reads go through the backends in order and the first one holding the key
+// wins, so an environment variable overrides a stored key. Writes go to the
+// first writable backend that takes them, which means the encrypted file is
+// only used when the keyring can't be reached.
+pub(crate) struct SecretStores {
+    stores: Vec<Box<dyn SecretStore>>,
+}
+
+impl SecretStores {
+    pub fn new(stores: Vec<Box<dyn SecretStore>>) -> Self {
+        SecretStores { stores }
+    }
+
+    // A backend that fails (no Secret Service running, wrong passphrase) is
+    // skipped. When no other backend has the key its error is returned, the key
+    // may well be sitting in the backend that couldn't be read.
+    pub fn get(&self, key: &str) -> Result<Option<(String, SecretBackend)>, NexaError> {
+        let mut last_error = None;
+
+        for store in self.stores.iter() {
+            match store.get(key) {
+                Ok(Some(value)) => return Ok(Some((value, store.backend()))),
+                Ok(None) => {}
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        match last_error {
+            Some(e) => Err(e),
+            None => Ok(None),
+        }
+    }
+
+    pub fn require(&self, key: &str) -> Result<String, NexaError> {
+        match self.get(key)? {
+            Some((value, _)) => Ok(value),
+            None => Err(NexaError::Secret(format!(
+                "No {} set, add it in the settings or set {}",
+                key,
+                get_env_name(key)
+            ))),
+        }
+    }
+
+    pub fn set(&self, key: &str, value: &str) -> Result<SecretBackend, NexaError> {
+        let mut last_error = None;
+
+        for store in self.stores.iter().filter(|store| !store.is_read_only()) {
+            match store.set(key, value) {
+                Ok(()) => return Ok(store.backend()),
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        Err(last_error.unwrap_or(NexaError::Secret(String::from(
+            "No writable secret store available",
+        ))))
+    }
+
+    // Removes the key from every writable backend, so an older copy doesn't
+    // come back from a backend further down the list
+    pub fn delete(&self, key: &str) -> Result<(), NexaError> {
+        let mut deleted = false;
+        let mut last_error = None;
+
+        for store in self.stores.iter().filter(|store| !store.is_read_only()) {
+            match store.delete(key) {
+                Ok(()) => deleted = true,
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        match (deleted, last_error) {
+            (false, Some(e)) => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn get_env_name(key: &str) -> String {
+    key.to_ascii_uppercase().replace('-', "_")
+}
+
+fn get_read_only_error(key: &str) -> NexaError {
+    NexaError::Secret(format!(
+        "{} comes from the environment and can't be changed here",
+        get_env_name(key)
+    ))
+}
+
+fn get_keyring_error(e: keyring::Error) -> NexaError {
+    NexaError::Secret(format!("Keyring: {}", e))
+}
+
+fn decode_base64(value: &str) -> Result<Vec<u8>, NexaError> {
+    STANDARD
+        .decode(value)
+        .map_err(|e| NexaError::Secret(format!("Invalid secrets file: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_file_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("nexa-secrets-{}-{}.json", name, std::process::id()))
+    }
+
+    #[test]
+    fn encrypted_file_secret_store_test() {
+        let path = get_file_path("file");
+        let store = EncryptedFileSecretStore::new(path.clone(), String::from("correct horse"));
+
+        assert_eq!(store.get("gemini-api-key").unwrap(), None);
+        store.set("gemini-api-key", "AIza-secret").unwrap();
+        store.set("other-key", "other").unwrap();
+        assert_eq!(
+            store.get("gemini-api-key").unwrap().as_deref(),
+            Some("AIza-secret")
+        );
+        assert!(!fs::read_to_string(&path).unwrap().contains("AIza-secret"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // A wrong passphrase can neither read nor add to the file
+        let wrong = EncryptedFileSecretStore::new(path.clone(), String::from("battery staple"));
+        assert!(wrong.get("gemini-api-key").is_err());
+        assert!(wrong.set("third-key", "value").is_err());
+
+        store.delete("gemini-api-key").unwrap();
+        assert_eq!(store.get("gemini-api-key").unwrap(), None);
+        assert_eq!(store.get("other-key").unwrap().as_deref(), Some("other"));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn secret_stores_test() {
+        let path = get_file_path("chain");
+        let stores = SecretStores::new(vec![
+            Box::new(EnvSecretStore),
+            Box::new(EncryptedFileSecretStore::new(
+                path.clone(),
+                String::from("passphrase"),
+            )),
+        ]);
+
+        assert!(stores.require("nexa-test-api-key").is_err());
+        assert_eq!(
+            stores.set("nexa-test-api-key", "stored").unwrap(),
+            SecretBackend::File
+        );
+        assert_eq!(
+            stores.get("nexa-test-api-key").unwrap(),
+            Some((String::from("stored"), SecretBackend::File))
+        );
+
+        // The environment wins over stored keys
+        env::set_var("NEXA_TEST_API_KEY", "from-env");
+        assert_eq!(
+            stores.get("nexa-test-api-key").unwrap(),
+            Some((String::from("from-env"), SecretBackend::Env))
+        );
+        env::remove_var("NEXA_TEST_API_KEY");
+
+        // A backend that can't be read is reported, not taken for a missing key
+        let locked = SecretStores::new(vec![
+            Box::new(EnvSecretStore),
+            Box::new(EncryptedFileSecretStore::new(
+                path.clone(),
+                String::from("wrong passphrase"),
+            )),
+        ]);
+        assert!(locked
+            .require("nexa-test-api-key")
+            .is_err_and(|e| e.to_string().contains("passphrase")));
+
+        stores.delete("nexa-test-api-key").unwrap();
+        assert_eq!(stores.get("nexa-test-api-key").unwrap(), None);
+        let _ = fs::remove_file(&path);
+    }
+}
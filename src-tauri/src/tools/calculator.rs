@@ -0,0 +1,269 @@
+use crate::error::NexaError;
+
+// Every level of nesting takes a few stack frames, and the expression comes
+// from the model
+static MAX_NESTING_DEPTH: usize = 64;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Operator(char),
+}
+
+fn get_error(message: impl Into<String>) -> NexaError {
+    NexaError::MCPToolCall(message.into())
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, NexaError> {
+    let chars: Vec<char> = expression.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            // Only an exponent when digits follow, otherwise "e" is the constant
+            let sign = chars.get(i + 1).is_some_and(|c| *c == '+' || *c == '-') as usize;
+            if chars.get(i).is_some_and(|c| *c == 'e' || *c == 'E')
+                && chars.get(i + 1 + sign).is_some_and(|c| c.is_ascii_digit())
+            {
+                i += 1 + sign;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+            }
+
+            let number: String = chars[start..i].iter().collect();
+            let number = number
+                .parse()
+                .map_err(|_| get_error(format!("Invalid number {}", number)))?;
+            tokens.push(Token::Number(number));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let name: String = chars[start..i].iter().collect();
+            tokens.push(Token::Name(name.to_lowercase()));
+        } else if "+-*/%^(),".contains(c) {
+            tokens.push(Token::Operator(c));
+            i += 1;
+        } else {
+            return Err(get_error(format!("Unexpected character '{}'", c)));
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn nested(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<f64, NexaError>,
+    ) -> Result<f64, NexaError> {
+        if self.depth >= MAX_NESTING_DEPTH {
+            return Err(get_error("The expression is nested too deeply"));
+        }
+
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next_operator(&mut self, operators: &str) -> Option<char> {
+        match self.peek() {
+            Some(Token::Operator(c)) if operators.contains(*c) => {
+                let c = *c;
+                self.position += 1;
+                Some(c)
+            }
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, operator: char) -> Result<(), NexaError> {
+        self.next_operator(&operator.to_string())
+            .map(|_| ())
+            .ok_or(get_error(format!("Expected '{}'", operator)))
+    }
+
+    fn parse_expression(&mut self) -> Result<f64, NexaError> {
+        let mut value = self.parse_term()?;
+        while let Some(operator) = self.next_operator("+-") {
+            let rhs = self.parse_term()?;
+            value = match operator {
+                '+' => value + rhs,
+                _ => value - rhs,
+            };
+        }
+        Ok(value)
+    }
+
+    fn parse_term(&mut self) -> Result<f64, NexaError> {
+        let mut value = self.parse_unary()?;
+        while let Some(operator) = self.next_operator("*/%") {
+            let rhs = self.parse_unary()?;
+            value = match operator {
+                '*' => value * rhs,
+                '/' => value / rhs,
+                _ => value % rhs,
+            };
+        }
+        Ok(value)
+    }
+
+    // Binds looser than ^, so -2^2 is -4
+    fn parse_unary(&mut self) -> Result<f64, NexaError> {
+        match self.next_operator("+-") {
+            Some('-') => Ok(-self.nested(Self::parse_unary)?),
+            Some(_) => self.nested(Self::parse_unary),
+            None => self.parse_power(),
+        }
+    }
+
+    fn parse_power(&mut self) -> Result<f64, NexaError> {
+        let base = self.parse_primary()?;
+        match self.next_operator("^") {
+            Some(_) => Ok(base.powf(self.nested(Self::parse_unary)?)),
+            None => Ok(base),
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<f64, NexaError> {
+        let token = self
+            .peek()
+            .cloned()
+            .ok_or(get_error("The expression ends too early"))?;
+        self.position += 1;
+
+        match token {
+            Token::Number(number) => Ok(number),
+            Token::Operator('(') => {
+                let value = self.nested(Self::parse_expression)?;
+                self.expect(')')?;
+                Ok(value)
+            }
+            Token::Name(name) => {
+                if self.next_operator("(").is_none() {
+                    return get_constant(&name);
+                }
+
+                let mut args = vec![self.nested(Self::parse_expression)?];
+                while self.next_operator(",").is_some() {
+                    args.push(self.nested(Self::parse_expression)?);
+                }
+                self.expect(')')?;
+                call_function(&name, &args)
+            }
+            Token::Operator(c) => Err(get_error(format!("Unexpected '{}'", c))),
+        }
+    }
+}
+
+fn get_constant(name: &str) -> Result<f64, NexaError> {
+    match name {
+        "pi" => Ok(std::f64::consts::PI),
+        "e" => Ok(std::f64::consts::E),
+        "tau" => Ok(std::f64::consts::TAU),
+        _ => Err(get_error(format!("Unknown constant {}", name))),
+    }
+}
+
+fn call_function(name: &str, args: &[f64]) -> Result<f64, NexaError> {
+    let value = match (name, args) {
+        ("sqrt", [x]) => x.sqrt(),
+        ("cbrt", [x]) => x.cbrt(),
+        ("abs", [x]) => x.abs(),
+        ("exp", [x]) => x.exp(),
+        ("ln", [x]) => x.ln(),
+        ("log", [x]) | ("log10", [x]) => x.log10(),
+        ("log2", [x]) => x.log2(),
+        ("log", [x, base]) => x.log(*base),
+        ("sin", [x]) => x.sin(),
+        ("cos", [x]) => x.cos(),
+        ("tan", [x]) => x.tan(),
+        ("asin", [x]) => x.asin(),
+        ("acos", [x]) => x.acos(),
+        ("atan", [x]) => x.atan(),
+        ("floor", [x]) => x.floor(),
+        ("ceil", [x]) => x.ceil(),
+        ("round", [x]) => x.round(),
+        ("pow", [x, y]) => x.powf(*y),
+        ("min", [_, ..]) => args.iter().cloned().fold(f64::INFINITY, f64::min),
+        ("max", [_, ..]) => args.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+        _ => {
+            return Err(get_error(format!(
+                "Unknown function {} with {} argument(s)",
+                name,
+                args.len()
+            )))
+        }
+    };
+
+    Ok(value)
+}
+
+// Arithmetic with the usual precedence, parentheses, ^ for powers and the
+// common math functions. Angles are in radians.
+pub(crate) fn evaluate(expression: &str) -> Result<f64, NexaError> {
+    let mut parser = Parser {
+        tokens: tokenize(expression)?,
+        position: 0,
+        depth: 0,
+    };
+
+    let value = parser.parse_expression()?;
+    if let Some(token) = parser.peek() {
+        return Err(get_error(format!("Unexpected {:?}", token)));
+    }
+    if !value.is_finite() {
+        return Err(get_error("The result isn't a finite number"));
+    }
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluate_test() {
+        assert_eq!(evaluate("1 + 2 * 3").unwrap(), 7.0);
+        assert_eq!(evaluate("(1 + 2) * 3").unwrap(), 9.0);
+        assert_eq!(evaluate("-2^2").unwrap(), -4.0);
+        assert_eq!(evaluate("2^3^2").unwrap(), 512.0);
+        assert_eq!(evaluate("1.5e3 / 10 % 7").unwrap(), 3.0);
+        assert_eq!(evaluate("max(1, sqrt(16), 3)").unwrap(), 4.0);
+        assert!((evaluate("2 * e").unwrap() - 2.0 * std::f64::consts::E).abs() < 1e-12);
+        assert!((evaluate("sin(pi / 2)").unwrap() - 1.0).abs() < 1e-12);
+
+        assert!(evaluate("1 / 0").is_err());
+        assert!(evaluate("(1 + 2").is_err());
+        assert!(evaluate("1 2").is_err());
+        assert!(evaluate("rm -rf").is_err());
+
+        // Too deep to parse, but not deep enough to overflow the stack
+        assert!(evaluate(&format!("{}1", "(".repeat(100_000))).is_err());
+        assert!(evaluate(&format!("{}1", "-".repeat(100_000))).is_err());
+        assert!(evaluate(&format!("{}1{}", "(".repeat(32), ")".repeat(32))).is_ok());
+    }
+}
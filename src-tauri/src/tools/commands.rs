@@ -0,0 +1,19 @@
+use crate::error::NexaError;
+use crate::tools::native::NativeToolsConfig;
+use crate::AppData;
+use tauri::State;
+
+#[tauri::command]
+pub async fn get_native_tools_config(
+    state: State<'_, AppData>,
+) -> Result<NativeToolsConfig, NexaError> {
+    Ok(state.native_tools.lock().await.get_config())
+}
+
+#[tauri::command]
+pub async fn set_native_tools_config(
+    state: State<'_, AppData>,
+    config: NativeToolsConfig,
+) -> Result<(), NexaError> {
+    state.native_tools.lock().await.set_config(config)
+}
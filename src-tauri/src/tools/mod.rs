@@ -0,0 +1,3 @@
+pub mod calculator;
+pub mod commands;
+pub mod native;
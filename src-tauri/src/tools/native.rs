@@ -0,0 +1,421 @@
+use crate::error::NexaError;
+use crate::mcp::structs::Tool;
+use crate::tools::calculator::evaluate;
+use jiff::civil::DateTime;
+use jiff::tz::TimeZone;
+use jiff::Zoned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs::{self, File};
+use std::io::{ErrorKind, Read};
+use std::path::{Path, PathBuf};
+
+pub(crate) static NATIVE_TOOLS_FILENAME: &str = "native-tools.json";
+
+// Stands in for an MCP server name, so native tools are advertised, enabled
+// and called as "builtin-_-calculate" like any other tool
+pub(crate) static NATIVE_TOOLS_SERVER_NAME: &str = "builtin";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NativeToolsConfig {
+    // The file tools only see what's inside these directories, and nothing
+    // at all without one
+    #[serde(default)]
+    pub allowed_roots: Vec<String>,
+    // Longer files are cut off, so one large file can't fill the context
+    #[serde(default = "get_default_max_read_bytes")]
+    pub max_read_bytes: u64,
+}
+
+fn get_default_max_read_bytes() -> u64 {
+    256 * 1024
+}
+
+impl Default for NativeToolsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_roots: vec![],
+            max_read_bytes: get_default_max_read_bytes(),
+        }
+    }
+}
+
+static FILE_TOOL_NAMES: [&str; 2] = ["read_file", "list_directory"];
+
+fn get_tool_definitions() -> Value {
+    json!([
+        {
+            "name": "calculate",
+            "description": "Evaluates an arithmetic expression exactly instead of estimating it. Supports + - * / % ^, parentheses, pi, e and sqrt, abs, exp, ln, log, log2, sin, cos, tan, asin, acos, atan, floor, ceil, round, pow, min, max. Angles are in radians.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "expression": {"type": "string", "description": "For example \"(1 + 2.5) * sqrt(16)\""}
+                },
+                "required": ["expression"]
+            }
+        },
+        {
+            "name": "get_current_time",
+            "description": "Gets the current date, time and weekday, in the user's timezone unless another one is given.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "timezone": {"type": "string", "description": "IANA timezone such as \"Europe/Berlin\""}
+                }
+            }
+        },
+        {
+            "name": "convert_time",
+            "description": "Converts a date and time from one timezone to another, taking daylight saving time into account.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "time": {"type": "string", "description": "Local date and time such as \"2025-03-27T10:00\""},
+                    "fromTimezone": {"type": "string", "description": "IANA timezone the time is in"},
+                    "toTimezone": {"type": "string", "description": "IANA timezone to convert to"}
+                },
+                "required": ["time", "fromTimezone", "toTimezone"]
+            }
+        },
+        {
+            "name": "read_file",
+            "description": "Reads a local text file. Only files inside the directories the user allowed can be read.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "path": {"type": "string", "description": "Absolute, or relative to the first allowed directory"}
+                },
+                "required": ["path"]
+            }
+        },
+        {
+            "name": "list_directory",
+            "description": "Lists the files and directories in a local directory. Call it without a path to get the directories the user allowed.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "path": {"type": "string", "description": "Absolute, or relative to the first allowed directory"}
+                }
+            }
+        }
+    ])
+}
+
+fn get_error(message: impl Into<String>) -> NexaError {
+    NexaError::MCPToolCall(message.into())
+}
+
+fn get_string_argument<'a>(arguments: &'a Value, name: &str) -> Result<&'a str, NexaError> {
+    arguments[name]
+        .as_str()
+        .ok_or(get_error(format!("Missing the {} argument", name)))
+}
+
+fn get_timezone(name: Option<&str>) -> Result<TimeZone, NexaError> {
+    match name {
+        Some(name) => {
+            TimeZone::get(name).map_err(|e| get_error(format!("Unknown timezone {}: {}", name, e)))
+        }
+        None => Ok(TimeZone::system()),
+    }
+}
+
+fn get_zoned_json(zoned: &Zoned) -> Value {
+    json!({
+        "timezone": zoned.time_zone().iana_name().unwrap_or("local"),
+        "datetime": zoned.strftime("%Y-%m-%dT%H:%M:%S%:z").to_string(),
+        "weekday": zoned.strftime("%A").to_string(),
+    })
+}
+
+#[derive(Default)]
+pub(crate) struct NativeTools {
+    config: NativeToolsConfig,
+    path: Option<PathBuf>,
+}
+
+impl NativeTools {
+    // No file yet means the defaults, a file that doesn't parse is an error
+    pub fn load(path: Option<PathBuf>) -> Result<Self, NexaError> {
+        let config = match path.as_ref().map(fs::read) {
+            None => NativeToolsConfig::default(),
+            Some(Err(e)) if e.kind() == ErrorKind::NotFound => NativeToolsConfig::default(),
+            Some(bytes) => serde_json::from_slice(&bytes?)?,
+        };
+
+        Ok(NativeTools { config, path })
+    }
+
+    pub fn get_config(&self) -> NativeToolsConfig {
+        self.config.clone()
+    }
+
+    pub fn set_config(&mut self, config: NativeToolsConfig) -> Result<(), NexaError> {
+        self.config = config;
+        self.save()
+    }
+
+    // Same shape as `MCPClient::get_tool_list`
+    // The file tools can't do anything before a directory is allowed, so the
+    // model isn't offered them until then
+    pub fn get_tool_list(&self) -> Vec<(String, Tool)> {
+        let tools: Vec<Tool> = serde_json::from_value(get_tool_definitions()).unwrap_or_default();
+
+        tools
+            .into_iter()
+            .filter(|tool| {
+                !self.config.allowed_roots.is_empty()
+                    || !FILE_TOOL_NAMES.contains(&tool.name.as_str())
+            })
+            .map(|tool| (tool.name.clone(), tool))
+            .collect()
+    }
+
+    pub fn call_tool(&self, name: &str, arguments: &Value) -> Result<Value, NexaError> {
+        match name {
+            "calculate" => {
+                let expression = get_string_argument(arguments, "expression")?;
+                Ok(json!({"expression": expression, "result": evaluate(expression)?}))
+            }
+            "get_current_time" => {
+                let timezone = get_timezone(arguments["timezone"].as_str())?;
+                Ok(get_zoned_json(&Zoned::now().with_time_zone(timezone)))
+            }
+            "convert_time" => {
+                let time = get_string_argument(arguments, "time")?;
+                let from = get_timezone(Some(get_string_argument(arguments, "fromTimezone")?))?;
+                let to = get_timezone(Some(get_string_argument(arguments, "toTimezone")?))?;
+
+                let zoned = time
+                    .parse::<DateTime>()
+                    .and_then(|time| time.to_zoned(from))
+                    .map_err(|e| get_error(format!("Invalid time {}: {}", time, e)))?;
+                Ok(json!({
+                    "from": get_zoned_json(&zoned),
+                    "to": get_zoned_json(&zoned.with_time_zone(to)),
+                }))
+            }
+            "read_file" => self.read_file(get_string_argument(arguments, "path")?),
+            "list_directory" => match arguments["path"].as_str() {
+                Some(path) => self.list_directory(path),
+                None => Ok(json!({"allowedRoots": self.config.allowed_roots})),
+            },
+            _ => Err(get_error(format!(
+                "There's no built-in tool called {}",
+                name
+            ))),
+        }
+    }
+
+    // Resolves symlinks and ".." before checking, so neither leads out of
+    // an allowed root
+    fn get_allowed_path(&self, path: &str) -> Result<PathBuf, NexaError> {
+        let roots: Vec<PathBuf> = self
+            .config
+            .allowed_roots
+            .iter()
+            .filter_map(|root| fs::canonicalize(root).ok())
+            .collect();
+        let Some(first_root) = roots.first() else {
+            return Err(get_error(
+                "No directory is allowed for the file tools, one has to be added in the settings first",
+            ));
+        };
+
+        let path = Path::new(path);
+        let path = match path.is_absolute() {
+            true => path.to_path_buf(),
+            false => first_root.join(path),
+        };
+        let path = fs::canonicalize(&path)
+            .map_err(|e| get_error(format!("Can't open {}: {}", path.display(), e)))?;
+
+        match roots.iter().any(|root| path.starts_with(root)) {
+            true => Ok(path),
+            false => Err(get_error(format!(
+                "{} is outside the allowed directories",
+                path.display()
+            ))),
+        }
+    }
+
+    fn read_file(&self, path: &str) -> Result<Value, NexaError> {
+        let path = self.get_allowed_path(path)?;
+        let file = File::open(&path)?;
+        let size = file.metadata()?.len();
+
+        // One byte more than allowed tells a cut file apart
+        let mut bytes = vec![];
+        file.take(self.config.max_read_bytes.saturating_add(1))
+            .read_to_end(&mut bytes)?;
+
+        let max_bytes = self.config.max_read_bytes as usize;
+        let truncated = bytes.len() > max_bytes;
+        let content = match std::str::from_utf8(&bytes[..bytes.len().min(max_bytes)]) {
+            Ok(content) => content.to_string(),
+            // The cut may have split a character
+            Err(e) if truncated && e.error_len().is_none() => {
+                String::from_utf8_lossy(&bytes[..e.valid_up_to()]).to_string()
+            }
+            Err(_) => return Err(get_error(format!("{} isn't a text file", path.display()))),
+        };
+
+        Ok(json!({
+            "path": path,
+            "size": size,
+            "truncated": truncated,
+            "content": content,
+        }))
+    }
+
+    fn list_directory(&self, path: &str) -> Result<Value, NexaError> {
+        let path = self.get_allowed_path(path)?;
+
+        let mut entries = vec![];
+        for entry in fs::read_dir(&path)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            let entry_type = match (file_type.is_dir(), file_type.is_symlink()) {
+                (true, _) => "directory",
+                (_, true) => "symlink",
+                _ => "file",
+            };
+
+            entries.push(json!({
+                "name": entry.file_name().to_string_lossy(),
+                "type": entry_type,
+                "size": entry.metadata().map(|metadata| metadata.len()).ok(),
+            }));
+        }
+        entries.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+
+        Ok(json!({"path": path, "entries": entries}))
+    }
+
+    fn save(&self) -> Result<(), NexaError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_vec_pretty(&self.config)?)?;
+
+        Ok(())
+    }
+}
+
+// Shaped like the result of an MCP tools/call, so the UI handles both the
+// same way. A failed call is still a result, the model gets to see why.
+pub(crate) fn get_native_tool_result(result: Result<Value, NexaError>) -> Value {
+    let (structured_content, is_error) = match result {
+        Ok(value) => (value, false),
+        Err(e) => (json!({"error": e.to_string()}), true),
+    };
+
+    json!({
+        "content": [{"type": "text", "text": structured_content.to_string()}],
+        "structuredContent": structured_content,
+        "isError": is_error,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn native_tools_test() {
+        let mut native_tools = NativeTools::load(None).unwrap();
+        let get_names = |native_tools: &NativeTools| -> Vec<String> {
+            native_tools
+                .get_tool_list()
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect()
+        };
+        assert_eq!(
+            get_names(&native_tools),
+            vec!["calculate", "get_current_time", "convert_time"]
+        );
+
+        let result = native_tools
+            .call_tool("calculate", &json!({"expression": "2 * (3 + 4)"}))
+            .unwrap();
+        assert_eq!(result["result"], 14.0);
+
+        // Summer time in Berlin, standard time in New York
+        let result = native_tools
+            .call_tool(
+                "convert_time",
+                &json!({"time": "2025-03-27T10:00", "fromTimezone": "Europe/Berlin", "toTimezone": "America/New_York"}),
+            )
+            .unwrap();
+        assert_eq!(result["to"]["datetime"], "2025-03-27T05:00:00-04:00");
+        assert_eq!(result["to"]["weekday"], "Thursday");
+
+        let root = std::env::temp_dir().join("nexa-native-tools-test");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("notes")).unwrap();
+        fs::write(root.join("notes").join("todo.md"), "- buy milk").unwrap();
+
+        assert!(native_tools
+            .call_tool("read_file", &json!({"path": "notes/todo.md"}))
+            .is_err());
+        native_tools
+            .set_config(NativeToolsConfig {
+                allowed_roots: vec![root.to_string_lossy().to_string()],
+                max_read_bytes: 5,
+            })
+            .unwrap();
+
+        assert_eq!(
+            get_names(&native_tools),
+            vec![
+                "calculate",
+                "get_current_time",
+                "convert_time",
+                "read_file",
+                "list_directory"
+            ]
+        );
+
+        let result = native_tools
+            .call_tool("read_file", &json!({"path": "notes/todo.md"}))
+            .unwrap();
+        assert_eq!(result["content"], "- buy");
+        assert_eq!(result["truncated"], true);
+        assert_eq!(result["size"], 10);
+
+        let result = native_tools
+            .call_tool("list_directory", &json!({"path": "."}))
+            .unwrap();
+        assert_eq!(result["entries"][0]["name"], "notes");
+        assert_eq!(result["entries"][0]["type"], "directory");
+
+        assert!(native_tools
+            .call_tool("list_directory", &json!({"path": ".."}))
+            .is_err_and(|e| e.to_string().contains("outside the allowed directories")));
+        assert_eq!(
+            get_native_tool_result(native_tools.call_tool("delete_file", &json!({})))["isError"],
+            true
+        );
+    }
+
+    #[test]
+    fn load_native_tools_test() {
+        let path =
+            std::env::temp_dir().join(format!("nexa-native-tools-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let native_tools = NativeTools::load(Some(path.clone())).unwrap();
+        assert_eq!(native_tools.get_config(), NativeToolsConfig::default());
+
+        fs::write(&path, "{\"allowedRoots\": [").unwrap();
+        assert!(NativeTools::load(Some(path.clone())).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
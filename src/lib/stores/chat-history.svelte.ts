@@ -1,10 +1,22 @@
 import { Store } from "@tauri-apps/plugin-store";
-import type { ChatMessageWithId } from "$types";
+import { invoke } from "@tauri-apps/api/core";
+import type {
+  BranchMessage,
+  ChatMessageWithId,
+  ConversationSettings,
+  ConversationSummary,
+  Model,
+} from "$types";
 
+// The chat lives in a backend conversation, this file only remembers which
+// one. stream_chat answers the conversation with its stored settings.
 class ChatHistoryStore {
   isReady = $state(false);
   chatHistory = $state<ChatMessageWithId[]>([]);
+  conversationId: string | null = null;
 
+  // Messages the conversation already has
+  #savedIds = new Set<string>();
   #store: Store | null = null;
   #FILENAME = "chat-history.json";
 
@@ -15,27 +27,67 @@ class ChatHistoryStore {
   async init() {
     try {
       this.#store = await Store.load(this.#FILENAME);
-      const savedChatHistory =
-        await this.#store.get<ChatMessageWithId[]>("chatHistory");
+      const conversationId = await this.#store.get<string>("conversationId");
 
-      if (savedChatHistory) {
-        this.chatHistory = savedChatHistory;
+      if (conversationId) {
+        await this.#load(conversationId);
+      } else {
+        // Older versions kept the messages in this file, the backend turns
+        // them into a conversation once
+        const summary =
+          (await invoke<ConversationSummary | null>(
+            "import_chat_history_store",
+          )) ??
+          (await invoke<ConversationSummary>("create_conversation", {}));
+        await this.#save("conversationId", summary.id);
+        await this.#load(summary.id);
       }
       this.isReady = true;
     } catch (e) {
-      console.error("Failed to load settings", e);
+      console.error("Failed to load the chat", e);
     }
   }
 
-  async addMessage(msg: ChatMessageWithId) {
-    this.chatHistory.push(msg);
-    await this.#save("chatHistory", this.chatHistory);
+  async #load(conversationId: string) {
+    this.conversationId = conversationId;
+    const branch = await invoke<BranchMessage[]>("get_active_branch", {
+      conversationId,
+    });
+    branch.forEach(({ message }) => this.#savedIds.add(message.id));
+    this.chatHistory = branch.map(({ message }) => ({
+      ...message,
+      done: true,
+    }));
   }
 
-  async sync(chatHistory: ChatMessageWithId[]) {
-    this.chatHistory = chatHistory;
-    await this.#save("chatHistory", this.chatHistory);
-    console.log("save!!");
+  // Adds what the conversation doesn't have yet, every message below the one
+  // before it
+  async saveMessages(chatHistory: ChatMessageWithId[]) {
+    for (const [i, msg] of chatHistory.entries()) {
+      if (this.#savedIds.has(msg.id)) {
+        continue;
+      }
+
+      await invoke("add_chat_message", {
+        conversationId: this.conversationId,
+        parentId: i > 0 ? chatHistory[i - 1].id : null,
+        message: msg,
+      });
+      this.#savedIds.add(msg.id);
+    }
+  }
+
+  // The conversation answers with the model picked last
+  async setModel(model: Model) {
+    const settings = await invoke<ConversationSettings>(
+      "get_conversation_settings",
+      { conversationId: this.conversationId },
+    );
+
+    await invoke("set_conversation_settings", {
+      conversationId: this.conversationId,
+      settings: { ...settings, provider: model.provider, model: model.modelId },
+    });
   }
 
   async #save(key: string, value: any) {
@@ -1,6 +1,8 @@
 export type ChatMessageWithId = ChatMessage & {
   id: string;
   done: boolean;
+  // Why the reply broke off, from the closing chunk
+  error?: string;
 };
 // {
 //   id: string;
@@ -23,7 +25,7 @@ export type ChatMessage =
 
 export interface UserChatMessage {
   role: "user";
-  content: TextContent | FunctionCallResponseContent;
+  content: TextContent | FunctionCallResponseContent | FileContent;
   image?: string;
 }
 
@@ -38,10 +40,224 @@ export interface SystemChatMessage {
   content: TextContent;
 }
 
+export interface GeminiFileEntry {
+  name: string;
+  uri: string;
+  mimeType: string;
+  displayName: string;
+  uploadedAt: number;
+  expiresAt: number;
+  path?: string;
+  stamp?: { size: number; modifiedAtNanos: number };
+}
+
+export interface GeminiFile {
+  name: string;
+  displayName?: string;
+  mimeType: string;
+  sizeBytes?: string;
+  createTime?: string;
+  expirationTime?: string;
+  sha256Hash?: string;
+  uri: string;
+  state?: "STATE_UNSPECIFIED" | "PROCESSING" | "ACTIVE" | "FAILED";
+}
+
+export interface GeminiListFilesResponse {
+  files: GeminiFile[];
+  nextPageToken?: string;
+}
+
+export type TreeMessage = ChatMessage & {
+  id: string;
+  parentId?: string;
+  createdAt: number;
+  provider?: Provider;
+  model?: string;
+  usage?: TokenUsage;
+  cost?: number;
+};
+
+export interface BranchMessage {
+  message: TreeMessage;
+  siblingIds: string[];
+}
+
+export interface ConversationSummary {
+  id: string;
+  title: string;
+  createdAt: number;
+  updatedAt: number;
+  messageCount: number;
+}
+
+export interface ConversationTree {
+  messages: TreeMessage[];
+  activeLeafId?: string;
+}
+
+export interface Conversation {
+  summary: ConversationSummary;
+  metadata: Record<string, any>;
+  settings: ConversationSettings;
+  tree: ConversationTree;
+}
+
+export interface GenerationConfig {
+  temperature?: number;
+  topP?: number;
+  topK?: number;
+  maxOutputTokens?: number;
+  stopSequences?: string[];
+  seed?: number;
+}
+
+export type ToolCallingMode = "auto" | "any" | "none";
+
+export interface ConversationSettings {
+  provider?: Provider;
+  model?: string;
+  credentialProfile?: string;
+  // Moves on to the provider's other profiles when a key runs out of quota
+  rotateCredentials?: boolean;
+  systemPrompt?: string;
+  generation: GenerationConfig;
+  // Unset enables every server / tool, tools are "server-_-tool" names
+  mcpServers?: string[];
+  tools?: string[];
+  toolCallingMode?: ToolCallingMode;
+  keepAlive?: OllamaKeepAlive;
+  safetySettings?: SafetySetting[];
+  autoContinue?: number;
+  maxConversationCost?: number;
+  maxAgentLoopCost?: number;
+}
+
+export interface PromptVariable {
+  name: string;
+  description?: string;
+  default?: string;
+  required: boolean;
+}
+
+export interface PromptTemplate {
+  name: string;
+  description?: string;
+  systemPrompt?: string;
+  provider?: Provider;
+  model?: string;
+  template: string;
+  variables: PromptVariable[];
+}
+
+export type PromptTemplateEntry = PromptTemplate & { id: string };
+
+// Files that didn't load are listed apart with the reason
+export interface PromptTemplateList {
+  templates: PromptTemplateEntry[];
+  errors: { id: string; error: string }[];
+}
+
+export interface RenderedPrompt {
+  message: ChatMessage;
+  systemPrompt?: string;
+  provider?: Provider;
+  model?: string;
+}
+
+export type ExportFormat = "markdown" | "json" | "html";
+
+export interface SearchFilters {
+  provider?: Provider;
+  model?: string;
+  // Unix seconds, inclusive
+  from?: number;
+  to?: number;
+  hasToolCalls?: boolean;
+}
+
+export interface SnippetPart {
+  text: string;
+  highlighted: boolean;
+}
+
+export interface SearchResult {
+  conversationId: string;
+  conversationTitle: string;
+  messageId: string;
+  role: "user" | "assistant" | "system" | "function";
+  provider?: Provider;
+  model?: string;
+  createdAt: number;
+  snippet: SnippetPart[];
+}
+
 export interface EmittedChatMessage {
   id: string;
   message: ChatMessage[];
   done: boolean;
+  usage?: TokenUsage;
+  blocked?: BlockedOutcome;
+  // Provider's own value, e.g. "STOP"/"MAX_TOKENS" (Gemini) or "stop"/"length" (Ollama).
+  // "SPEND_LIMIT" when a spend limit stopped auto-continue.
+  finishReason?: string;
+  // USD, only for models with a price
+  cost?: number;
+  // Set on a closing chunk of its own when the reply broke off
+  error?: string;
+}
+
+export interface BlockedOutcome {
+  // Gemini's block or finish reason, e.g. "SAFETY"
+  reason: string;
+  message: string;
+  categories: string[];
+}
+
+export type HarmCategory =
+  | "HARM_CATEGORY_HARASSMENT"
+  | "HARM_CATEGORY_HATE_SPEECH"
+  | "HARM_CATEGORY_SEXUALLY_EXPLICIT"
+  | "HARM_CATEGORY_DANGEROUS_CONTENT"
+  | "HARM_CATEGORY_CIVIC_INTEGRITY";
+
+export type HarmBlockThreshold =
+  | "BLOCK_LOW_AND_ABOVE"
+  | "BLOCK_MEDIUM_AND_ABOVE"
+  | "BLOCK_ONLY_HIGH"
+  | "BLOCK_NONE"
+  | "OFF";
+
+export interface SafetySetting {
+  category: HarmCategory;
+  threshold: HarmBlockThreshold;
+}
+
+export interface TokenUsage {
+  inputTokens: number;
+  outputTokens: number;
+  cachedTokens: number;
+  thinkingTokens: number;
+}
+
+export interface CompareTarget {
+  id: string;
+  provider: Provider;
+  model: string;
+  credentialProfile?: string;
+}
+
+export interface CompareResult {
+  runId: string;
+  targetId: string;
+  provider: Provider;
+  model: string;
+  messages: ChatMessage[];
+  timeToFirstTokenMs?: number;
+  totalDurationMs: number;
+  usage?: TokenUsage;
+  cost?: number;
+  error?: string;
 }
 
 export interface ModelState {
@@ -49,19 +265,52 @@ export interface ModelState {
   models: Model[];
 }
 
-export type Provider = "ollama" | "gemini";
+export type Provider = "ollama" | "gemini" | "mock";
 
 export interface Model {
   provider: Provider;
   modelId: string;
 }
 
-export type ConfigSection = "general" | "apiKeys";
+export type OllamaKeepAlive = number | string;
+
+export interface OllamaRunningModel {
+  name: string;
+  model: string;
+  size: number;
+  size_vram: number;
+  expires_at: string;
+  digest: string;
+}
+
+export type ConfigSection = "general" | "apiKeys" | "mcpServers";
 
 export interface GetItemResponse {
   data: string;
 }
 
+export type SecretBackend = "env" | "keyring" | "file";
+
+export interface SecretStatus {
+  key: string;
+  backend?: SecretBackend;
+}
+
+export interface CredentialProfileStatus {
+  provider: Provider;
+  name: string;
+  // Secret key of the profile, for set_secret / test_secret
+  key: string;
+  backend?: SecretBackend;
+}
+
+export interface SecretTestResult {
+  key: string;
+  backend?: SecretBackend;
+  valid: boolean;
+  error?: string;
+}
+
 export interface Text {
   text: string;
   _meta?: Record<string, any>;
@@ -96,10 +345,23 @@ export interface FunctionCallResponseContent {
   content: FunctionCallResponse;
 }
 
+export interface FileAttachment {
+  path: string;
+  mimeType: string;
+  displayName?: string;
+  _meta?: Record<string, any>;
+}
+
+export interface FileContent {
+  type: "file";
+  content: FileAttachment;
+}
+
 export type ChatMessageContent =
   | TextContent
   | FunctionCallRequestContent
-  | FunctionCallResponseContent;
+  | FunctionCallResponseContent
+  | FileContent;
 
 export type FunctionCallStatus = "awaiting" | "success" | "failed";
 
@@ -119,3 +381,99 @@ export interface EmittedMCPResponse {
 
   response: any;
 }
+
+export type MCPTransportType = "stdio" | "http" | "sse";
+
+// One entry of `mcpServers` in mcp.json
+export interface MCPServerConfig {
+  command?: string;
+  args?: string[];
+  env?: Record<string, string>;
+  cwd?: string;
+  url?: string;
+  headers?: Record<string, string>;
+  type?: MCPTransportType;
+  disabled?: boolean;
+}
+
+// The built-in tools are advertised as the "builtin" server
+export interface NativeToolsConfig {
+  allowedRoots: string[];
+  maxReadBytes: number;
+}
+
+export type MCPServerState = "starting" | "connected" | "failed" | "disabled";
+
+export interface MCPServerStatus {
+  name: string;
+  transportType?: MCPTransportType;
+  state: MCPServerState;
+  error?: string;
+  serverName?: string;
+  toolCount: number;
+}
+
+export interface ResponseCacheConfig {
+  enabled: boolean;
+  ttlSecs: number;
+  maxEntries: number;
+  maxBytes: number;
+}
+
+export interface RateLimit {
+  provider: Provider;
+  model?: string;
+  requestsPerMinute?: number;
+  tokensPerMinute?: number;
+  requestsPerDay?: number;
+  tokensPerDay?: number;
+}
+
+export interface DailyUsage {
+  provider: Provider;
+  model: string;
+  requests: number;
+  inputTokens: number;
+  outputTokens: number;
+}
+
+export interface QuotaStatus {
+  limit: RateLimit;
+  requestsToday: number;
+  tokensToday: number;
+  remainingRequestsToday?: number;
+  remainingTokensToday?: number;
+  remainingRequestsThisMinute?: number;
+  remainingTokensThisMinute?: number;
+}
+
+export interface QuotaReport {
+  limits: QuotaStatus[];
+  usage: DailyUsage[];
+  resetsAt: number;
+}
+
+// USD per million tokens
+export interface ModelPricing {
+  provider: Provider;
+  model: string;
+  input: number;
+  output: number;
+  cachedInput?: number;
+  thinking?: number;
+}
+
+export interface ConversationCost {
+  total: number;
+  agentLoop: number;
+}
+
+export interface HttpClientConfig {
+  proxy?: string;
+  noProxy?: string;
+  caCertificates: string[];
+  connectTimeoutSecs?: number;
+  readTimeoutSecs?: number;
+  poolIdleTimeoutSecs?: number;
+  userAgent?: string;
+}